@@ -0,0 +1,1310 @@
+#![allow(unused)]
+
+use serde::Serialize;
+use tide::http::Method;
+use tide::{Body, Endpoint, Request, Response, StatusCode};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::auth;
+use crate::core::{errors::AppError, functions, structs::*};
+use crate::data::constants::{self, TABLE_NAME_CATEGORY};
+use crate::db::store::{self, Store};
+use crate::{due, my_work, parts, schedule, State};
+
+/// Prefix for every route of the JSON API. Bump it when a response
+/// shape changes in a way existing scripts would notice.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// Where the OpenAPI description of the routes below is served.
+pub const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ErrorBody {
+    /// Body of every error response.
+    pub error: String,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Maintenance Tracker"),
+    paths(
+        list_categories,
+        create_category,
+        get_category,
+        patch_category,
+        delete_category,
+        list_items,
+        create_item,
+        get_item,
+        patch_item,
+        delete_item,
+        assign_item,
+        list_my_work,
+        list_item_entries,
+        create_item_entry,
+        get_entry,
+        patch_entry,
+        delete_entry,
+        list_due,
+        list_schedules,
+        list_item_schedules,
+        create_item_schedule,
+        get_schedule,
+        delete_schedule,
+        list_work_orders,
+        create_item_work_order,
+        get_work_order,
+        assign_work_order,
+        create_work_order_line,
+        close_work_order,
+        list_parts,
+        create_part,
+        list_low_stock,
+        get_part,
+        patch_part,
+        list_tokens,
+        create_token,
+        revoke_token,
+    ),
+    components(schemas(
+        ApiToken,
+        Category,
+        CategoryPatch,
+        DueItem,
+        Entry,
+        EntryPatch,
+        ErrorBody,
+        IssuedApiToken,
+        Item,
+        ItemAssignment,
+        ItemDetails,
+        ItemPatch,
+        MyWork,
+        NewApiToken,
+        Part,
+        PartPatch,
+        PartUse,
+        Role,
+        Schedule,
+        ScheduleKind,
+        ScheduleStatus,
+        NewWorkOrder,
+        WorkOrder,
+        WorkOrderAssignment,
+        WorkOrderClosing,
+        WorkOrderLine,
+        WorkOrderLineKind,
+        WorkOrderState,
+    ))
+)]
+pub struct ApiDoc;
+
+fn json<T: Serialize>(status: StatusCode, body: &T) -> tide::Result {
+    /// Build a JSON response with the given status.
+    let mut res = Response::new(status);
+    res.set_body(Body::from_json(body)?);
+
+    Ok(res)
+}
+
+fn created<T: Serialize>(location: String, body: &T) -> tide::Result {
+    /// Build a `201 Created` response pointing at the new resource.
+    let mut res = json(StatusCode::Created, body)?;
+    res.insert_header("Location", location);
+
+    Ok(res)
+}
+
+fn id_param(req: &Request<State>) -> store::Result<u32> {
+    /// Get the `:id` segment of the URL.
+    match req.param("id") {
+        Ok(id) => functions::parse_id(id),
+        Err(_) => Err(AppError::Parse("Missing id.".to_owned())),
+    }
+}
+
+fn user_id(req: &Request<State>) -> store::Result<u32> {
+    /// Get the id of the user behind the request, token or session.
+    auth::current_user(req)
+        .ok_or_else(|| AppError::Unauthorized(constants::LOGIN_REQUIRED_MSG.to_owned()))
+}
+
+fn check_category(store: &dyn Store, category_id: u32) -> store::Result<()> {
+    /// Reject references to a category that does not exist.
+    match store.get_category(category_id) {
+        Ok(_) => Ok(()),
+        Err(AppError::NotFound(msg)) => Err(AppError::Validation(msg)),
+        Err(e) => Err(e),
+    }
+}
+
+fn check_assignee(store: &dyn Store, user_id: u32) -> store::Result<User> {
+    /// Get a user to assign work to, rejecting ones that do not exist.
+    match store.get_user(user_id) {
+        Ok(user) => Ok(user),
+        Err(AppError::NotFound(msg)) => Err(AppError::Validation(msg)),
+        Err(e) => Err(e),
+    }
+}
+
+fn check_part(store: &dyn Store, part_id: u32) -> store::Result<Part> {
+    /// Get a part to book against a work order, rejecting ones that do
+    /// not exist.
+    match store.get_part(part_id) {
+        Ok(part) => Ok(part),
+        Err(AppError::NotFound(msg)) => Err(AppError::Validation(msg)),
+        Err(e) => Err(e),
+    }
+}
+
+fn check_title_free(store: &dyn Store, title: &str, table: &impl IsTable) -> store::Result<()> {
+    /// Reject a title already used by another category or item.
+    if store.title_taken(title, table.table_name())? {
+        return Err(AppError::Conflict(format!(
+            "{} named \"{}\" already exists.",
+            match table.table_name() {
+                TABLE_NAME_CATEGORY => "Category",
+                _ => "Item",
+            },
+            title
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn routes() -> Vec<(Method, &'static str, Box<dyn Endpoint<State>>)> {
+    /// Every JSON API route, relative to `API_PREFIX`.
+    /// Routes are only registered from this list so the OpenAPI
+    /// description can be checked against it.
+    vec![
+        (Method::Get, "/categories", Box::new(list_categories)),
+        (Method::Post, "/categories", Box::new(create_category)),
+        (Method::Get, "/categories/:id", Box::new(get_category)),
+        (Method::Patch, "/categories/:id", Box::new(patch_category)),
+        (Method::Delete, "/categories/:id", Box::new(delete_category)),
+        (Method::Get, "/items", Box::new(list_items)),
+        (Method::Post, "/items", Box::new(create_item)),
+        (Method::Get, "/items/:id", Box::new(get_item)),
+        (Method::Patch, "/items/:id", Box::new(patch_item)),
+        (Method::Delete, "/items/:id", Box::new(delete_item)),
+        (Method::Post, "/items/:id/assign", Box::new(assign_item)),
+        (Method::Get, "/my-work", Box::new(list_my_work)),
+        (
+            Method::Get,
+            "/items/:id/entries",
+            Box::new(list_item_entries),
+        ),
+        (
+            Method::Post,
+            "/items/:id/entries",
+            Box::new(create_item_entry),
+        ),
+        (Method::Get, "/entries/:id", Box::new(get_entry)),
+        (Method::Patch, "/entries/:id", Box::new(patch_entry)),
+        (Method::Delete, "/entries/:id", Box::new(delete_entry)),
+        (Method::Get, "/due", Box::new(list_due)),
+        (Method::Get, "/schedules", Box::new(list_schedules)),
+        (
+            Method::Get,
+            "/items/:id/schedules",
+            Box::new(list_item_schedules),
+        ),
+        (
+            Method::Post,
+            "/items/:id/schedules",
+            Box::new(create_item_schedule),
+        ),
+        (Method::Get, "/schedules/:id", Box::new(get_schedule)),
+        (Method::Delete, "/schedules/:id", Box::new(delete_schedule)),
+        (Method::Get, "/work-orders", Box::new(list_work_orders)),
+        (
+            Method::Post,
+            "/items/:id/work-orders",
+            Box::new(create_item_work_order),
+        ),
+        (Method::Get, "/work-orders/:id", Box::new(get_work_order)),
+        (
+            Method::Post,
+            "/work-orders/:id/assign",
+            Box::new(assign_work_order),
+        ),
+        (
+            Method::Post,
+            "/work-orders/:id/lines",
+            Box::new(create_work_order_line),
+        ),
+        (
+            Method::Post,
+            "/work-orders/:id/close",
+            Box::new(close_work_order),
+        ),
+        (Method::Get, "/parts", Box::new(list_parts)),
+        (Method::Post, "/parts", Box::new(create_part)),
+        (Method::Get, "/parts/low-stock", Box::new(list_low_stock)),
+        (Method::Get, "/parts/:id", Box::new(get_part)),
+        (Method::Patch, "/parts/:id", Box::new(patch_part)),
+        (Method::Get, "/tokens", Box::new(list_tokens)),
+        (Method::Post, "/tokens", Box::new(create_token)),
+        (Method::Delete, "/tokens/:id", Box::new(revoke_token)),
+    ]
+}
+
+pub fn register(app: &mut tide::Server<State>) {
+    /// Register the JSON API routes under `API_PREFIX`.
+    for (method, path, endpoint) in routes() {
+        app.at(&format!("{}{}", API_PREFIX, path))
+            .method(method, endpoint);
+    }
+
+    app.at(OPENAPI_PATH)
+        .get(|_| async move { json(StatusCode::Ok, &ApiDoc::openapi()) });
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "categories",
+    responses((status = 200, description = "All categories.", body = [Category]))
+)]
+async fn list_categories(req: Request<State>) -> tide::Result {
+    json(StatusCode::Ok, &req.state().store.collect_categories()?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/categories",
+    tag = "categories",
+    request_body = Category,
+    responses(
+        (status = 201, description = "The new category.", body = Category),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 409, description = "The title is taken.", body = ErrorBody),
+        (status = 422, description = "The title is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_category(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let category = functions::parse_json::<Category>(&req.body_string().await?)?;
+    category.validate()?;
+    check_title_free(store.as_ref(), &category.title, &category)?;
+
+    let id = store.insert_category(&category.title)?;
+
+    created(
+        format!("{}/categories/{}", API_PREFIX, id),
+        &store.get_category(id)?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = u32, Path, description = "Category id.")),
+    responses(
+        (status = 200, description = "The category.", body = Category),
+        (status = 404, description = "No such category.", body = ErrorBody),
+    )
+)]
+async fn get_category(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &req.state().store.get_category(id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = u32, Path, description = "Category id.")),
+    request_body = CategoryPatch,
+    responses(
+        (status = 200, description = "The changed category.", body = Category),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such category.", body = ErrorBody),
+        (status = 409, description = "The title is taken.", body = ErrorBody),
+        (status = 422, description = "The title is invalid.", body = ErrorBody),
+    )
+)]
+async fn patch_category(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut category = store.get_category(id_param(&req)?)?;
+    let patch = functions::parse_json::<CategoryPatch>(&req.body_string().await?)?;
+
+    if patch
+        .removed
+        .is_some_and(|removed| removed != category.removed)
+    {
+        auth::require_role(&req, Role::Admin)?;
+    }
+
+    if let Some(title) = &patch.title {
+        if *title != category.title {
+            check_title_free(store.as_ref(), title, &category)?;
+        }
+    }
+
+    patch.apply(&mut category);
+    category.validate()?;
+    store.update_category(&category)?;
+
+    json(StatusCode::Ok, &category)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = u32, Path, description = "Category id.")),
+    responses(
+        (status = 204, description = "The category was marked removed."),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such category.", body = ErrorBody),
+    )
+)]
+async fn delete_category(req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Admin)?;
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    store.get_category(id)?;
+    store.delete_category(id)?;
+
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/items",
+    tag = "items",
+    responses((status = 200, description = "All items with their current details.", body = [Item]))
+)]
+async fn list_items(req: Request<State>) -> tide::Result {
+    let items: Vec<Item> = req.state().store.collect_items()?.into_values().collect();
+
+    json(StatusCode::Ok, &items)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/items",
+    tag = "items",
+    request_body = Item,
+    responses(
+        (status = 201, description = "The new item.", body = Item),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 409, description = "The title is taken.", body = ErrorBody),
+        (status = 422, description = "The item is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_item(mut req: Request<State>) -> tide::Result {
+    let role = auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut item = functions::parse_json::<Item>(&req.body_string().await?)?;
+    item.id = None;
+    let details = item.details.get_or_insert_with(ItemDetails::new);
+    auth::check_details_change(&req, role, &ItemDetails::new(), details)?;
+    item.validate()?;
+    check_category(store.as_ref(), item.category_id)?;
+    check_title_free(store.as_ref(), &item.title, &item)?;
+
+    store.insert_item(&mut item, &auth::author(&req)?)?;
+    let id = item.id.unwrap_or_default();
+
+    created(format!("{}/items/{}", API_PREFIX, id), &store.get_item(id)?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/items/{id}",
+    tag = "items",
+    params(("id" = u32, Path, description = "Item id.")),
+    responses(
+        (status = 200, description = "The item.", body = Item),
+        (status = 404, description = "No such item.", body = ErrorBody),
+    )
+)]
+async fn get_item(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &req.state().store.get_item(id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/items/{id}",
+    tag = "items",
+    params(("id" = u32, Path, description = "Item id.")),
+    request_body = ItemPatch,
+    responses(
+        (status = 200, description = "The changed item.", body = Item),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+        (status = 409, description = "The title is taken.", body = ErrorBody),
+        (status = 422, description = "The item is invalid.", body = ErrorBody),
+    )
+)]
+async fn patch_item(mut req: Request<State>) -> tide::Result {
+    let role = auth::require_role(&req, Role::Technician)?;
+    let store = req.state().store.clone();
+    let old = store.get_item(id_param(&req)?)?;
+    let mut item = old.clone();
+    let patch = functions::parse_json::<ItemPatch>(&req.body_string().await?)?;
+
+    if let Some(title) = &patch.title {
+        if *title != item.title {
+            check_title_free(store.as_ref(), title, &item)?;
+        }
+    }
+
+    patch.apply(&mut item);
+    auth::check_item_change(&req, role, &old, &item)?;
+    item.validate()?;
+    check_category(store.as_ref(), item.category_id)?;
+    store.update_item(&item, &auth::author(&req)?)?;
+
+    json(
+        StatusCode::Ok,
+        &store.get_item(item.id.unwrap_or_default())?,
+    )
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/items/{id}",
+    tag = "items",
+    params(("id" = u32, Path, description = "Item id.")),
+    responses(
+        (status = 204, description = "The item was marked removed."),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+    )
+)]
+async fn delete_item(req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Admin)?;
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    store.get_item(id)?;
+    store.delete_item(id)?;
+
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/items/{id}/assign",
+    tag = "items",
+    params(("id" = u32, Path, description = "Item id.")),
+    request_body = ItemAssignment,
+    responses(
+        (status = 200, description = "The reassigned item.", body = Item),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+        (status = 422, description = "No such user.", body = ErrorBody),
+    )
+)]
+async fn assign_item(mut req: Request<State>) -> tide::Result {
+    // the new entry keeps the item's details, note included, and says who
+    // has it now
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut item = store.get_item(id_param(&req)?)?;
+    let assignment = functions::parse_json::<ItemAssignment>(&req.body_string().await?)?;
+
+    let handed_to = match assignment.assignee_id {
+        Some(id) => format!(
+            "Assigned to {}",
+            check_assignee(store.as_ref(), id)?.username
+        ),
+        None => "Unassigned".to_owned(),
+    };
+    item.assignee_id = assignment.assignee_id;
+    item.details.get_or_insert_with(ItemDetails::new).assignment = Some(handed_to);
+    store.assign_item(&item, &auth::author(&req)?)?;
+
+    json(
+        StatusCode::Ok,
+        &store.get_item(item.id.unwrap_or_default())?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/my-work",
+    tag = "items",
+    responses(
+        (status = 200, description = "The items and open work orders assigned to you.", body = MyWork),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+    )
+)]
+async fn list_my_work(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &my_work::collect(req.state().store.as_ref(), user_id(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/items/{id}/entries",
+    tag = "entries",
+    params(("id" = u32, Path, description = "Item id.")),
+    responses(
+        (status = 200, description = "The item's history.", body = [Entry]),
+        (status = 404, description = "No such item.", body = ErrorBody),
+    )
+)]
+async fn list_item_entries(req: Request<State>) -> tide::Result {
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    store.get_item(id)?;
+
+    json(StatusCode::Ok, &store.collect_item_entries(id)?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/items/{id}/entries",
+    tag = "entries",
+    params(("id" = u32, Path, description = "Item id.")),
+    request_body = ItemDetails,
+    responses(
+        (status = 201, description = "The new entry.", body = Entry),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+        (status = 409, description = "Too few of a part are on hand.", body = ErrorBody),
+        (status = 422, description = "The details are invalid.", body = ErrorBody),
+    )
+)]
+async fn create_item_entry(mut req: Request<State>) -> tide::Result {
+    // a new entry records new details for the item
+    let role = auth::require_role(&req, Role::Technician)?;
+    let store = req.state().store.clone();
+    let mut item = store.get_item(id_param(&req)?)?;
+    let details = functions::parse_json::<ItemDetails>(&req.body_string().await?)?;
+    details.validate()?;
+
+    if let Some(old) = &item.details {
+        auth::check_details_change(&req, role, old, &details)?;
+    }
+    item.details = Some(details);
+
+    let id = store.insert_entry(&item, &auth::author(&req)?)?;
+
+    created(
+        format!("{}/entries/{}", API_PREFIX, id),
+        &store.get_entry_by_id(id)?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/entries/{id}",
+    tag = "entries",
+    params(("id" = u32, Path, description = "Entry id.")),
+    responses(
+        (status = 200, description = "The entry.", body = Entry),
+        (status = 404, description = "No such entry.", body = ErrorBody),
+    )
+)]
+async fn get_entry(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &req.state().store.get_entry_by_id(id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/entries/{id}",
+    tag = "entries",
+    params(("id" = u32, Path, description = "Entry id.")),
+    request_body = EntryPatch,
+    responses(
+        (status = 200, description = "The corrected entry.", body = Entry),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such entry.", body = ErrorBody),
+        (status = 422, description = "The entry is invalid.", body = ErrorBody),
+    )
+)]
+async fn patch_entry(mut req: Request<State>) -> tide::Result {
+    // entries are history, so correcting one is a supervisor's job
+    let role = auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut entry = store.get_entry_by_id(id_param(&req)?)?;
+    let old = ItemDetails::from_entry(&entry);
+    let patch = functions::parse_json::<EntryPatch>(&req.body_string().await?)?;
+
+    patch.apply(&mut entry);
+    let details = ItemDetails::from_entry(&entry);
+    auth::check_details_change(&req, role, &old, &details)?;
+    details.validate()?;
+    store.update_entry(&entry)?;
+
+    json(StatusCode::Ok, &entry)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/entries/{id}",
+    tag = "entries",
+    params(("id" = u32, Path, description = "Entry id.")),
+    responses(
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such entry.", body = ErrorBody),
+        (status = 409, description = "Entries cannot be deleted.", body = ErrorBody),
+    )
+)]
+async fn delete_entry(req: Request<State>) -> tide::Result {
+    // the author, work order steps, assignments and parts used all hang off
+    // entries, so they are corrected rather than deleted
+    auth::require_role(&req, Role::Admin)?;
+    req.state().store.get_entry_by_id(id_param(&req)?)?;
+
+    Err(AppError::Conflict(
+        "Entries are the item's history and cannot be deleted, correct one with PATCH instead."
+            .to_owned(),
+    )
+    .into())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/due",
+    tag = "items",
+    responses((status = 200, description = "Warning and Stopped items overdue or due this week, soonest first.", body = [DueItem]))
+)]
+async fn list_due(req: Request<State>) -> tide::Result {
+    json(StatusCode::Ok, &due::collect(req.state().store.as_ref())?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/schedules",
+    tag = "schedules",
+    responses((status = 200, description = "Every schedule and when it is next due.", body = [ScheduleStatus]))
+)]
+async fn list_schedules(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &schedule::statuses(req.state().store.as_ref(), None)?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/items/{id}/schedules",
+    tag = "schedules",
+    params(("id" = u32, Path, description = "Item id.")),
+    responses(
+        (status = 200, description = "The item's schedules and when they are next due.", body = [ScheduleStatus]),
+        (status = 404, description = "No such item.", body = ErrorBody),
+    )
+)]
+async fn list_item_schedules(req: Request<State>) -> tide::Result {
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    store.get_item(id)?;
+
+    json(
+        StatusCode::Ok,
+        &schedule::statuses(store.as_ref(), Some(id))?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/items/{id}/schedules",
+    tag = "schedules",
+    params(("id" = u32, Path, description = "Item id.")),
+    request_body = Schedule,
+    responses(
+        (status = 201, description = "The new schedule and when it is first due.", body = ScheduleStatus),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+        (status = 422, description = "The schedule is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_item_schedule(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let item_id = id_param(&req)?;
+    store.get_item(item_id)?;
+
+    let mut new = functions::parse_json::<Schedule>(&req.body_string().await?)?;
+    new.id = None;
+    new.item_id = item_id;
+    new.created = None;
+    new.validate()?;
+    store.insert_schedule(&mut new)?;
+    let id = new.id.unwrap_or_default();
+
+    created(
+        format!("{}/schedules/{}", API_PREFIX, id),
+        &schedule_status(store.as_ref(), id)?,
+    )
+}
+
+fn schedule_status(store: &dyn Store, id: u32) -> store::Result<ScheduleStatus> {
+    /// Evaluate a single schedule against its item's history.
+    let found = store.get_schedule(id)?;
+    let mut entries = store.collect_item_entries(found.item_id)?;
+    entries.sort_by_key(|entry| entry.id);
+
+    schedule::evaluate(&found, &entries, &store.now()?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/schedules/{id}",
+    tag = "schedules",
+    params(("id" = u32, Path, description = "Schedule id.")),
+    responses(
+        (status = 200, description = "The schedule and when it is next due.", body = ScheduleStatus),
+        (status = 404, description = "No such schedule.", body = ErrorBody),
+    )
+)]
+async fn get_schedule(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &schedule_status(req.state().store.as_ref(), id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/schedules/{id}",
+    tag = "schedules",
+    params(("id" = u32, Path, description = "Schedule id.")),
+    responses(
+        (status = 204, description = "The schedule was removed."),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such schedule.", body = ErrorBody),
+    )
+)]
+async fn delete_schedule(req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    store.get_schedule(id)?;
+    store.remove_schedule(id)?;
+
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+fn open_work_order(store: &dyn Store, id: u32) -> store::Result<WorkOrder> {
+    /// Get a work order that can still change.
+    let order = store.get_work_order(id)?;
+
+    if order.state == WorkOrderState::Closed {
+        return Err(AppError::Conflict(format!("Work order {} is closed.", id)));
+    }
+
+    Ok(order)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/work-orders",
+    tag = "work-orders",
+    params(WorkOrderFilter),
+    responses(
+        (status = 200, description = "The matching work orders, without their lines.", body = [WorkOrder]),
+        (status = 400, description = "The filter is invalid.", body = ErrorBody),
+    )
+)]
+async fn list_work_orders(req: Request<State>) -> tide::Result {
+    let filter = req
+        .query::<WorkOrderFilter>()
+        .map_err(|e| AppError::Parse(e.to_string()))?;
+
+    json(
+        StatusCode::Ok,
+        &req.state().store.collect_work_orders(&filter)?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/items/{id}/work-orders",
+    tag = "work-orders",
+    params(("id" = u32, Path, description = "Item id.")),
+    request_body = NewWorkOrder,
+    responses(
+        (status = 201, description = "The new work order.", body = WorkOrder),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such item.", body = ErrorBody),
+        (status = 422, description = "The work order is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_item_work_order(mut req: Request<State>) -> tide::Result {
+    // opening a work order records the problem on the item's history
+    let role = auth::require_role(&req, Role::Technician)?;
+    let store = req.state().store.clone();
+    let mut item = store.get_item(id_param(&req)?)?;
+    let new = functions::parse_json::<NewWorkOrder>(&req.body_string().await?)?;
+
+    let mut order = WorkOrder {
+        id: None,
+        item_id: item.id.unwrap_or_default(),
+        state: WorkOrderState::Open,
+        problem: new.problem.trim().to_owned(),
+        assignee_id: None,
+        assignee: None,
+        resolution: None,
+        opened: None,
+        closed: None,
+        cost: 0,
+        lines: vec![],
+    };
+    order.validate()?;
+
+    let old = item.details.clone().unwrap_or_else(ItemDetails::new);
+    let mut details = old.clone();
+    details.status = new.status.unwrap_or(old.status);
+    details.due_date = new.due_date.or(old.due_date.clone());
+    details.priority = new.priority.or(old.priority);
+    details.note = Some(format!("Opened work order: {}", order.problem));
+    auth::check_details_change(&req, role, &old, &details)?;
+    details.validate()?;
+    item.details = Some(details);
+
+    store.insert_work_order(&mut order, &item, &auth::author(&req)?)?;
+    let id = order.id.unwrap_or_default();
+
+    created(
+        format!("{}/work-orders/{}", API_PREFIX, id),
+        &store.get_work_order(id)?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/work-orders/{id}",
+    tag = "work-orders",
+    params(("id" = u32, Path, description = "Work order id.")),
+    responses(
+        (status = 200, description = "The work order with its labour and parts.", body = WorkOrder),
+        (status = 404, description = "No such work order.", body = ErrorBody),
+    )
+)]
+async fn get_work_order(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &req.state().store.get_work_order(id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/work-orders/{id}/assign",
+    tag = "work-orders",
+    params(("id" = u32, Path, description = "Work order id.")),
+    request_body = WorkOrderAssignment,
+    responses(
+        (status = 200, description = "The assigned work order.", body = WorkOrder),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such work order.", body = ErrorBody),
+        (status = 409, description = "The work order is closed.", body = ErrorBody),
+        (status = 422, description = "No such user.", body = ErrorBody),
+    )
+)]
+async fn assign_work_order(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut order = open_work_order(store.as_ref(), id_param(&req)?)?;
+    let assignment = functions::parse_json::<WorkOrderAssignment>(&req.body_string().await?)?;
+
+    let assignee = check_assignee(store.as_ref(), assignment.assignee_id)?;
+    order.state = WorkOrderState::Assigned;
+    order.assignee_id = assignee.id;
+
+    let mut item = store.get_item(order.item_id)?;
+    item.details.get_or_insert_with(ItemDetails::new).assignment =
+        Some(format!("Assigned work order to {}", assignee.username));
+
+    store.update_work_order(&order, &item, &auth::author(&req)?)?;
+
+    json(
+        StatusCode::Ok,
+        &store.get_work_order(order.id.unwrap_or_default())?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/work-orders/{id}/lines",
+    tag = "work-orders",
+    params(("id" = u32, Path, description = "Work order id.")),
+    request_body = WorkOrderLine,
+    responses(
+        (status = 201, description = "The work order with the new line.", body = WorkOrder),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this, such as pricing a line by hand.", body = ErrorBody),
+        (status = 404, description = "No such work order.", body = ErrorBody),
+        (status = 409, description = "The work order is closed, or too few of the part are on hand.", body = ErrorBody),
+        (status = 422, description = "The line is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_work_order_line(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Technician)?;
+    let store = req.state().store.clone();
+    let order = open_work_order(store.as_ref(), id_param(&req)?)?;
+    let id = order.id.unwrap_or_default();
+
+    let mut line = functions::parse_json::<WorkOrderLine>(&req.body_string().await?)?;
+    line.id = None;
+    line.work_order_id = id;
+    line.created = None;
+
+    // a catalogue part is described and priced by the catalogue
+    if let Some(part_id) = line.part_id {
+        let part = check_part(store.as_ref(), part_id)?;
+        if line.description.trim().is_empty() {
+            line.description = part.description;
+        }
+        line.unit_cost = part.unit_cost;
+    } else if line.unit_cost > 0 {
+        // costs are a supervisor's to set, and closing books them on the item
+        auth::require_role(&req, Role::Supervisor)?;
+    }
+
+    line.validate()?;
+    store.insert_work_order_line(&mut line)?;
+
+    created(
+        format!("{}/work-orders/{}", API_PREFIX, id),
+        &store.get_work_order(id)?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/work-orders/{id}/close",
+    tag = "work-orders",
+    params(("id" = u32, Path, description = "Work order id.")),
+    request_body = WorkOrderClosing,
+    responses(
+        (status = 200, description = "The closed work order.", body = WorkOrder),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such work order.", body = ErrorBody),
+        (status = 409, description = "The work order is already closed.", body = ErrorBody),
+        (status = 422, description = "The resolution is invalid.", body = ErrorBody),
+    )
+)]
+async fn close_work_order(mut req: Request<State>) -> tide::Result {
+    // the closing entry carries the status the work leaves the item in
+    // and what the labour and parts cost
+    let role = auth::require_role(&req, Role::Technician)?;
+    let store = req.state().store.clone();
+    let mut order = open_work_order(store.as_ref(), id_param(&req)?)?;
+    let closing = functions::parse_json::<WorkOrderClosing>(&req.body_string().await?)?;
+
+    order.state = WorkOrderState::Closed;
+    order.resolution = Some(closing.resolution.trim().to_owned());
+    order.validate()?;
+
+    let mut item = store.get_item(order.item_id)?;
+    let old = item.details.clone().unwrap_or_else(ItemDetails::new);
+    let mut details = old.clone();
+    details.status = closing.status.unwrap_or(0);
+    details.note = Some(format!(
+        "Closed work order: {}",
+        order.resolution.as_deref().unwrap_or_default()
+    ));
+    auth::check_details_change(&req, role, &old, &details)?;
+    details.validate()?;
+
+    // added from the lines, which only supervisors and the parts catalogue
+    // price, so closing is not a way around the role check on costs
+    details.cost = Some(
+        details
+            .cost
+            .unwrap_or(0)
+            .checked_add(order.cost)
+            .ok_or_else(|| AppError::Validation("The total cost is too large.".to_owned()))?,
+    );
+    item.details = Some(details);
+
+    store.update_work_order(&order, &item, &auth::author(&req)?)?;
+
+    json(
+        StatusCode::Ok,
+        &store.get_work_order(order.id.unwrap_or_default())?,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parts",
+    tag = "parts",
+    responses((status = 200, description = "The parts catalogue, by part number.", body = [Part]))
+)]
+async fn list_parts(req: Request<State>) -> tide::Result {
+    json(StatusCode::Ok, &req.state().store.collect_parts()?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/parts",
+    tag = "parts",
+    request_body = Part,
+    responses(
+        (status = 201, description = "The new part.", body = Part),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 409, description = "The part number is taken.", body = ErrorBody),
+        (status = 422, description = "The part is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_part(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut part = functions::parse_json::<Part>(&req.body_string().await?)?;
+    part.id = None;
+    part.validate()?;
+
+    // the part number's unique key refuses a duplicate
+    store.insert_part(&mut part)?;
+
+    created(
+        format!("{}/parts/{}", API_PREFIX, part.id.unwrap_or_default()),
+        &part,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parts/low-stock",
+    tag = "parts",
+    responses((status = 200, description = "Parts at or below their reorder point, furthest below first.", body = [Part]))
+)]
+async fn list_low_stock(req: Request<State>) -> tide::Result {
+    json(StatusCode::Ok, &parts::collect(req.state().store.as_ref())?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parts/{id}",
+    tag = "parts",
+    params(("id" = u32, Path, description = "Part id.")),
+    responses(
+        (status = 200, description = "The part.", body = Part),
+        (status = 404, description = "No such part.", body = ErrorBody),
+    )
+)]
+async fn get_part(req: Request<State>) -> tide::Result {
+    json(
+        StatusCode::Ok,
+        &req.state().store.get_part(id_param(&req)?)?,
+    )
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/parts/{id}",
+    tag = "parts",
+    params(("id" = u32, Path, description = "Part id.")),
+    request_body = PartPatch,
+    responses(
+        (status = 200, description = "The changed part.", body = Part),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 403, description = "The user's role does not allow this.", body = ErrorBody),
+        (status = 404, description = "No such part.", body = ErrorBody),
+        (status = 422, description = "The part is invalid.", body = ErrorBody),
+    )
+)]
+async fn patch_part(mut req: Request<State>) -> tide::Result {
+    auth::require_role(&req, Role::Supervisor)?;
+    let store = req.state().store.clone();
+    let mut part = store.get_part(id_param(&req)?)?;
+    let patch = functions::parse_json::<PartPatch>(&req.body_string().await?)?;
+
+    patch.apply(&mut part);
+    part.validate()?;
+    store.update_part(&part)?;
+
+    json(StatusCode::Ok, &part)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "The user's API tokens.", body = [ApiToken]),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+    )
+)]
+async fn list_tokens(req: Request<State>) -> tide::Result {
+    let user_id = user_id(&req)?;
+
+    json(
+        StatusCode::Ok,
+        &req.state().store.collect_api_tokens(user_id)?,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    request_body = NewApiToken,
+    responses(
+        (status = 201, description = "The new token, shown only this once.", body = IssuedApiToken),
+        (status = 401, description = "Not logged in with a session.", body = ErrorBody),
+        (status = 422, description = "The name, scope or expiry is invalid.", body = ErrorBody),
+    )
+)]
+async fn create_token(mut req: Request<State>) -> tide::Result {
+    // a leaked token must not be able to mint more of itself
+    let user_id = auth::session_user(&req)
+        .ok_or_else(|| AppError::Unauthorized("Log in to create API tokens.".to_owned()))?;
+    let store = req.state().store.clone();
+    let new = functions::parse_json::<NewApiToken>(&req.body_string().await?)?;
+    new.validate()?;
+
+    let user = store.get_user(user_id)?;
+
+    if new.scope.is_some_and(|scope| scope > user.role) {
+        return Err(AppError::Validation(format!(
+            "A token cannot have a wider scope than your {} role.",
+            user.role
+        ))
+        .into());
+    }
+
+    let token = functions::generate_token();
+    let mut api_token = ApiToken {
+        id: None,
+        user_id,
+        name: new.name,
+        scope: new.scope,
+        expires: None,
+        last_used: None,
+        created: None,
+        revoked: false,
+    };
+    store.insert_api_token(
+        &mut api_token,
+        &functions::hash_token(&token),
+        new.expires_in_days,
+    )?;
+    let id = api_token.id.unwrap_or(0);
+
+    tide::log::info!(
+        "User \"{}\" created API token \"{}\"",
+        user.username,
+        api_token.name
+    );
+
+    created(
+        format!("{}/tokens/{}", API_PREFIX, id),
+        &IssuedApiToken {
+            token,
+            api_token: store.get_api_token(id)?,
+        },
+    )
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tokens/{id}",
+    tag = "tokens",
+    params(("id" = u32, Path, description = "Token id.")),
+    responses(
+        (status = 204, description = "The token was revoked."),
+        (status = 401, description = "Not logged in.", body = ErrorBody),
+        (status = 404, description = "No such token of the user's.", body = ErrorBody),
+    )
+)]
+async fn revoke_token(req: Request<State>) -> tide::Result {
+    let user_id = user_id(&req)?;
+    let store = &req.state().store;
+    let id = id_param(&req)?;
+    let api_token = store.get_api_token(id)?;
+
+    // admins may revoke anyone's token, everyone else only their own
+    if api_token.user_id != user_id && auth::role(&req)? < Role::Admin {
+        return Err(AppError::NotFound(format!("No API token with id {}.", id)).into());
+    }
+
+    store.revoke_api_token(id)?;
+
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn openapi_path(path: &str) -> String {
+        // tide writes `:id` where OpenAPI writes `{id}`
+        path.split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_owned(),
+            })
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
+    #[test]
+    fn openapi_matches_routes() {
+        let registered = routes()
+            .into_iter()
+            .map(|(method, path, _)| {
+                (
+                    method.to_string(),
+                    openapi_path(&format!("{}{}", API_PREFIX, path)),
+                )
+            })
+            .collect::<BTreeSet<(String, String)>>();
+
+        let documented = ApiDoc::openapi()
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.operations.keys().map(move |method| {
+                    // operations serialize as lowercase method names
+                    let method = serde_json::to_value(method).unwrap();
+                    (method.as_str().unwrap().to_uppercase(), path.clone())
+                })
+            })
+            .collect::<BTreeSet<(String, String)>>();
+
+        assert_eq!(registered, documented);
+    }
+
+    #[test]
+    fn openapi_describes_core_structs() {
+        let doc = ApiDoc::openapi();
+        let schemas = doc.components.unwrap().schemas;
+
+        for name in ["Category", "Entry", "Item", "ItemDetails"] {
+            assert!(schemas.contains_key(name), "{}", name);
+        }
+    }
+}
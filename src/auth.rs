@@ -0,0 +1,468 @@
+#![allow(unused)]
+
+use std::future::Future;
+use std::pin::Pin;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use tide::http::{mime, Method};
+use tide::sessions::{MemoryStore, SessionMiddleware};
+use tide::{Body, Next, Redirect, Request, Response, StatusCode};
+use tide_tera::prelude::*;
+
+use crate::api::API_PREFIX;
+use crate::core::{errors::AppError, functions, structs::*};
+use crate::data::constants;
+use crate::db::store::{self, Store};
+use crate::State;
+
+/// Session key holding the id of the logged-in user.
+const SESSION_USER_ID: &str = "user_id";
+
+/// Session key holding the token every change must be sent with.
+const SESSION_CSRF_TOKEN: &str = "csrf_token";
+
+/// Header scripts send the CSRF token in. Plain HTML forms send it as
+/// a `csrf_token` field instead.
+const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Routes that can be posted to without a session.
+const PUBLIC_ROUTES: &[&str] = &["/login"];
+
+/// Routes outside the JSON API that accept an API token.
+const TOKEN_ROUTES: &[&str] = &["/update/item"];
+
+/// The user an API token was issued to, kept on the request once the
+/// token has been checked.
+#[derive(Clone, Copy, Debug)]
+struct TokenUser {
+    user_id: u32,
+    scope: Option<Role>,
+}
+
+/// The one field of a form body checked before the route parses the rest.
+#[derive(Deserialize, Debug)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LoginForm {
+    username: String,
+    password: String,
+}
+
+pub fn sessions() -> SessionMiddleware<MemoryStore> {
+    /// Cookie sessions kept in memory. The signing key is generated on
+    /// every start, so restarting the server logs everyone out.
+    let mut secret = [0u8; 64];
+    OsRng.fill_bytes(&mut secret);
+
+    SessionMiddleware::new(MemoryStore::new(), &secret)
+        .with_cookie_name("maintenance_tracker.sid")
+        .without_save_unchanged()
+}
+
+pub fn current_user(req: &Request<State>) -> Option<u32> {
+    /// Get the id of the logged-in user or the owner of the API token
+    /// sent with the request, if any.
+    match req.ext::<TokenUser>() {
+        Some(token) => Some(token.user_id),
+        None => req.session().get::<u32>(SESSION_USER_ID),
+    }
+}
+
+pub fn session_user(req: &Request<State>) -> Option<u32> {
+    /// Get the id of the logged-in user, ignoring any API token.
+    match req.ext::<TokenUser>() {
+        Some(_) => None,
+        None => req.session().get::<u32>(SESSION_USER_ID),
+    }
+}
+
+pub fn csrf_token(req: &mut Request<State>) -> tide::Result<String> {
+    /// Get the session's CSRF token, issuing one on first use.
+    if let Some(token) = req.session().get::<String>(SESSION_CSRF_TOKEN) {
+        return Ok(token);
+    }
+
+    let token = functions::generate_csrf_token();
+    req.session_mut().insert(SESSION_CSRF_TOKEN, &token)?;
+
+    Ok(token)
+}
+
+pub fn role(req: &Request<State>) -> store::Result<Role> {
+    /// Get the role of whoever sent the request. Everyone is an admin
+    /// when login is turned off, and a viewer until they log in.
+    if !req.state().config.require_login {
+        return Ok(Role::Admin);
+    }
+
+    match current_user(req) {
+        Some(id) => match req.state().store.get_user(id) {
+            // a scoped token never acts above its scope
+            Ok(user) => Ok(match req.ext::<TokenUser>().and_then(|token| token.scope) {
+                Some(scope) => user.role.min(scope),
+                None => user.role,
+            }),
+            // the account was deleted after logging in
+            Err(AppError::NotFound(_)) => Ok(Role::Viewer),
+            Err(e) => Err(e),
+        },
+        None => Ok(Role::Viewer),
+    }
+}
+
+pub fn author(req: &Request<State>) -> store::Result<Author> {
+    /// Get who new entries are written by: the logged-in user, or the
+    /// configured name when login is turned off.
+    let config = &req.state().config;
+
+    if !config.require_login {
+        return Ok(Author {
+            user_id: None,
+            name: config.author.clone(),
+        });
+    }
+
+    match current_user(req) {
+        Some(id) => {
+            let user = req.state().store.get_user(id)?;
+
+            Ok(Author {
+                user_id: user.id,
+                name: user.username,
+            })
+        }
+        None => Err(AppError::Unauthorized(
+            constants::LOGIN_REQUIRED_MSG.to_owned(),
+        )),
+    }
+}
+
+fn deny(req: &Request<State>, msg: String) -> AppError {
+    /// Log a denied attempt and build the error sent back for it.
+    tide::log::warn!(
+        "Denied {} {} for user {:?}: {}",
+        req.method(),
+        req.url().path(),
+        current_user(req),
+        msg
+    );
+
+    AppError::Forbidden(msg)
+}
+
+pub fn require_role(req: &Request<State>, needed: Role) -> store::Result<Role> {
+    /// Reject the request unless the user has at least the `needed` role.
+    let role = role(req)?;
+
+    if role < needed {
+        return Err(deny(
+            req,
+            format!("This action requires the {} role.", needed),
+        ));
+    }
+
+    Ok(role)
+}
+
+fn check_fields(
+    req: &Request<State>,
+    role: Role,
+    changes: &[(&str, bool, Role)],
+) -> store::Result<()> {
+    /// Reject the request if any changed field needs a higher role.
+    for (field, changed, needed) in changes {
+        if *changed && role < *needed {
+            return Err(deny(
+                req,
+                format!("Changing {} requires the {} role.", field, needed),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn check_details_change(
+    req: &Request<State>,
+    role: Role,
+    old: &ItemDetails,
+    new: &ItemDetails,
+) -> store::Result<()> {
+    /// Check each changed field of `ItemDetails` against the user's role.
+    check_fields(
+        req,
+        role,
+        &[
+            ("status", old.status != new.status, Role::Technician),
+            ("note", old.note != new.note, Role::Technician),
+            ("visible", old.visible != new.visible, Role::Technician),
+            // the web page sends 0 for an item without a cost
+            (
+                "cost",
+                old.cost.unwrap_or(0) != new.cost.unwrap_or(0),
+                Role::Supervisor,
+            ),
+            ("removed", old.removed != new.removed, Role::Admin),
+        ],
+    )
+}
+
+pub fn check_item_change(
+    req: &Request<State>,
+    role: Role,
+    old: &Item,
+    new: &Item,
+) -> store::Result<()> {
+    /// Check each changed field of an `Item` against the user's role.
+    check_fields(
+        req,
+        role,
+        &[
+            ("title", old.title != new.title, Role::Supervisor),
+            (
+                "category",
+                old.category_id != new.category_id,
+                Role::Supervisor,
+            ),
+        ],
+    )?;
+
+    match (&old.details, &new.details) {
+        (Some(old), Some(new)) => check_details_change(req, role, old, new),
+        (None, Some(new)) => check_details_change(req, role, &ItemDetails::new(), new),
+        _ => Ok(()),
+    }
+}
+
+fn mutates(method: Method) -> bool {
+    !matches!(method, Method::Get | Method::Head | Method::Options)
+}
+
+fn accepts_token(path: &str) -> bool {
+    TOKEN_ROUTES.contains(&path)
+        || path == API_PREFIX
+        || path.starts_with(&format!("{}/", API_PREFIX))
+}
+
+pub fn bearer_token<'a>(
+    mut req: Request<State>,
+    next: Next<'a, State>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    /// Accept an `Authorization: Bearer` API token in place of a session
+    /// on the routes that take one, and record when it was last used.
+    Box::pin(async move {
+        let token = req
+            .header("Authorization")
+            .and_then(|header| header.as_str().strip_prefix("Bearer "))
+            .map(|token| token.trim().to_owned());
+
+        if let Some(token) = token.filter(|_| accepts_token(req.url().path())) {
+            let store = req.state().store.clone();
+
+            let api_token = match store.find_api_token(&functions::hash_token(&token)) {
+                Ok(api_token) => api_token,
+                Err(AppError::NotFound(_)) => {
+                    tide::log::warn!(
+                        "Rejected API token for {} {}",
+                        req.method(),
+                        req.url().path()
+                    );
+
+                    return Err(AppError::Unauthorized(
+                        constants::API_TOKEN_INVALID_MSG.to_owned(),
+                    )
+                    .into());
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some(id) = api_token.id {
+                store.touch_api_token(id)?;
+            }
+
+            req.set_ext(TokenUser {
+                user_id: api_token.user_id,
+                scope: api_token.scope,
+            });
+        }
+
+        Ok(next.run(req).await)
+    })
+}
+
+pub fn require_login<'a>(
+    req: Request<State>,
+    next: Next<'a, State>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    /// Reject any change made without a logged-in user.
+    Box::pin(async move {
+        if req.state().config.require_login
+            && mutates(req.method())
+            && !PUBLIC_ROUTES.contains(&req.url().path())
+            && current_user(&req).is_none()
+        {
+            return Err(AppError::Unauthorized(constants::LOGIN_REQUIRED_MSG.to_owned()).into());
+        }
+
+        Ok(next.run(req).await)
+    })
+}
+
+async fn form_csrf_token(req: &mut Request<State>) -> tide::Result<Option<String>> {
+    /// Read the `csrf_token` field of a form body, then put the body back
+    /// for the route to parse.
+    let content_type = match req.content_type() {
+        Some(content_type) if content_type.essence() == mime::FORM.essence() => content_type,
+        _ => return Ok(None),
+    };
+
+    let bytes = req.body_bytes().await?;
+    let form: CsrfForm = Body::from_bytes(bytes.clone()).into_form().await?;
+
+    let mut body = Body::from_bytes(bytes);
+    body.set_mime(content_type);
+    req.set_body(body);
+
+    Ok(form.csrf_token)
+}
+
+pub fn verify_csrf<'a>(
+    mut req: Request<State>,
+    next: Next<'a, State>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    /// Reject any change not sent with the session's CSRF token, so other
+    /// sites cannot make changes through a visitor's browser. Requests
+    /// carrying an API token send no cookies and are let through, as is
+    /// logging in, which has no session to ride on yet.
+    Box::pin(async move {
+        if mutates(req.method())
+            && !PUBLIC_ROUTES.contains(&req.url().path())
+            && req.ext::<TokenUser>().is_none()
+        {
+            let sent = match req.header(CSRF_HEADER) {
+                Some(header) => Some(header.as_str().to_owned()),
+                None => form_csrf_token(&mut req).await?,
+            };
+            let expected = req.session().get::<String>(SESSION_CSRF_TOKEN);
+
+            let valid = match (&sent, &expected) {
+                (Some(sent), Some(expected)) => functions::constant_time_eq(sent, expected),
+                _ => false,
+            };
+
+            if !valid {
+                return Err(deny(&req, constants::CSRF_INVALID_MSG.to_owned()).into());
+            }
+        }
+
+        Ok(next.run(req).await)
+    })
+}
+
+fn render_login(req: &Request<State>, status: StatusCode, error: Option<&str>) -> tide::Result {
+    /// Render the login page.
+    let mut res = req.state().tera.render_response(
+        "login.html",
+        &context! {
+            "app_title" => constants::APP_TITLE.to_owned(),
+            "app_version" => constants::APP_VERSION.to_owned(),
+            "error" => error,
+        },
+    )?;
+    res.set_status(status);
+
+    Ok(res)
+}
+
+pub fn register(app: &mut tide::Server<State>) {
+    /// Register the login and logout routes.
+    app.at("/login")
+        .get(|mut req: Request<State>| async move { render_login(&req, StatusCode::Ok, None) })
+        .post(|mut req: Request<State>| async move {
+            let form: LoginForm = req.body_form().await?;
+            let store = req.state().store.clone();
+
+            let user = match store.get_user_by_name(&form.username) {
+                Ok(user) => Some(user),
+                Err(AppError::NotFound(_)) => None,
+                Err(e) => return Err(e.into()),
+            };
+
+            match user {
+                Some(user) if functions::verify_password(&form.password, &user.password_hash) => {
+                    // a fresh session id on login, so a planted cookie is useless
+                    let session = req.session_mut();
+                    session.regenerate();
+                    session.insert(SESSION_USER_ID, user.id)?;
+
+                    tide::log::info!("User \"{}\" logged in", user.username);
+
+                    Ok(Redirect::see_other("/").into())
+                }
+                _ => {
+                    tide::log::warn!("Failed login for \"{}\"", form.username);
+
+                    render_login(
+                        &req,
+                        StatusCode::Unauthorized,
+                        Some(constants::LOGIN_FAILED_MSG),
+                    )
+                }
+            }
+        });
+
+    app.at("/logout")
+        .post(|mut req: Request<State>| async move {
+            req.session_mut().destroy();
+
+            Ok(Redirect::see_other("/login"))
+        });
+}
+
+pub fn add_user(store: &dyn Store, role: Role) -> store::Result<User> {
+    /// Prompt for a new account until a valid, unused one is entered.
+    /// Input that cannot be read ends the prompt with its error.
+    loop {
+        let mut user = match User::from_prompt(role) {
+            Ok(user) => user,
+            Err(AppError::Validation(e)) => {
+                println!("{}", e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        match store.get_user_by_name(&user.username) {
+            Ok(_) => println!("User \"{}\" already exists.", user.username),
+            Err(AppError::NotFound(_)) => {
+                store.insert_user(&mut user)?;
+                return Ok(user);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn create_first_admin(store: &dyn Store, interactive: bool) -> store::Result<()> {
+    /// Prompt for an administrator account when none exists yet, as in a
+    /// new database or one upgraded from before roles existed. Without a
+    /// terminal to prompt at, fail and say how to make one.
+    if store.count_admins()? > 0 {
+        return Ok(());
+    }
+
+    if !interactive {
+        return Err(AppError::Input(
+            constants::NO_USERS_NON_INTERACTIVE_MSG.to_owned(),
+        ));
+    }
+
+    println!("{}", constants::NO_USERS_MSG);
+    add_user(store, Role::Admin)?;
+
+    Ok(())
+}
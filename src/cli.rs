@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::core::structs::Role;
+
+#[derive(Parser, Debug)]
+#[command(name = "maintenance_tracker", version, about)]
+pub struct Cli {
+    /// Config file to read, `config.json` if not given. Files ending in
+    /// `.toml` are read as TOML.
+    #[arg(long, global = true, env = "MT_CONFIG", value_name = "PATH")]
+    pub config: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn command(&self) -> &Command {
+        /// The subcommand to run, serving when none was given.
+        self.command.as_ref().unwrap_or(&Command::Serve)
+    }
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Run the web server (the default)
+    Serve,
+    /// Prompt for the settings and database credentials, save them, then serve
+    Setup,
+    /// Prompt for the settings and database credentials for this run only, then serve
+    Once,
+    /// Apply pending schema migrations and exit
+    Migrate {
+        /// Only list the pending migrations and their SQL
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write every category, item and entry as JSON
+    Export {
+        /// File to write, standard output if not given
+        path: Option<PathBuf>,
+    },
+    /// Load categories, items and entries written by `export` into an empty database
+    Import {
+        /// File written by `export`
+        path: PathBuf,
+    },
+    /// Manage user accounts
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Check that the database can be reached and its schema is up to date
+    CheckDb,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+impl Command {
+    pub fn interactive(&self) -> bool {
+        /// Whether missing settings may be asked for in the terminal.
+        /// Checks and exports are read by scripts, so they neither prompt
+        /// nor log, and fail instead.
+        !matches!(
+            self,
+            Command::CheckDb
+                | Command::Export { .. }
+                | Command::Config {
+                    command: ConfigCommand::Check
+                }
+        )
+    }
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum UserCommand {
+    /// Prompt for a username and password and create an account
+    Add {
+        /// Role of the new account
+        #[arg(default_value_t = Role::Technician)]
+        role: Role,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Print the settings the server would run with, password masked
+    Check,
+}
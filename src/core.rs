@@ -3,14 +3,25 @@
 pub mod structs {
     use std::collections::HashMap;
     use std::io;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::path::{Path, PathBuf};
 
     use mysql::prelude::*;
     use mysql::{FromRowError, FromValueError, Row, Value};
     use rpassword::read_password;
     use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
 
+    use super::errors::{self, AppError};
+    use super::functions;
     use crate::constants::{
-        REFERENCE_ID_CATEGORY, REFERENCE_ID_ITEM, TABLE_NAME_CATEGORY, TABLE_NAME_ITEM,
+        DEFAULT_AUTHOR, DEFAULT_POOL_ACQUIRE_TIMEOUT_MS, DEFAULT_POOL_CONNECT_TIMEOUT_MS,
+        DEFAULT_POOL_MAX_CONNECTIONS, DEFAULT_POOL_MIN_CONNECTIONS, DEFAULT_PORT,
+        DEFAULT_SQLITE_PATH, ENV_PREFIX, MAX_API_TOKEN_NAME_LEN, MAX_CATEGORY_TITLE_LEN,
+        MAX_ENTRY_NOTE_LEN, MAX_ITEM_PRIORITY, MAX_ITEM_STATUS, MAX_ITEM_TITLE_LEN,
+        MAX_PART_DESCRIPTION_LEN, MAX_PART_NUMBER_LEN, MAX_SCHEDULE_TITLE_LEN, MAX_USERNAME_LEN,
+        MAX_WORK_ORDER_LINE_LEN, MIN_PASSWORD_LEN, REFERENCE_ID_CATEGORY, REFERENCE_ID_ITEM,
+        TABLE_NAME_CATEGORY, TABLE_NAME_ITEM,
     };
 
     pub trait IsTable {
@@ -21,11 +32,12 @@ pub mod structs {
         fn table_name(&self) -> &'static str;
     }
 
-    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, ToSchema)]
     pub struct Category {
         /// Category for sorting items.
         pub id: Option<u32>,
         pub title: String,
+        #[serde(default)]
         pub removed: bool,
     }
 
@@ -39,6 +51,28 @@ pub mod structs {
         }
     }
 
+    fn validate_title(title: &str, max_len: u8) -> errors::Result<()> {
+        /// Titles must be non-empty and fit in their VARCHAR column.
+        if title.trim().is_empty() {
+            return Err(AppError::Validation("Title cannot be empty.".to_owned()));
+        }
+
+        if title.chars().count() > max_len as usize {
+            return Err(AppError::Validation(format!(
+                "Title cannot be longer than {} characters.",
+                max_len
+            )));
+        }
+
+        Ok(())
+    }
+
+    impl Category {
+        pub fn validate(&self) -> errors::Result<()> {
+            validate_title(&self.title, MAX_CATEGORY_TITLE_LEN)
+        }
+    }
+
     impl IsTable for Category {
         fn reference_id(&self) -> Option<&'static str> {
             REFERENCE_ID_CATEGORY
@@ -53,37 +87,379 @@ pub mod structs {
             /// Convert a row of data into a Category.
             let mut row = row;
 
-            let result = Category {
-                id: row.take("id").unwrap(),
-                title: row.take("title").unwrap(),
-                removed: row.take("removed").unwrap(),
+            match (
+                row.take_opt("id"),
+                row.take_opt("title"),
+                row.take_opt("removed"),
+            ) {
+                (Some(Ok(id)), Some(Ok(title)), Some(Ok(removed))) => {
+                    Ok(Category { id, title, removed })
+                }
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for Category {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Category> {
+            /// Convert a SQLite row into a Category.
+            Ok(Category {
+                id: row.get("id")?,
+                title: row.get("title")?,
+                removed: row.get("removed")?,
+            })
+        }
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Backend {
+        /// Database server used to store categories, items and entries.
+        #[default]
+        MySql,
+        Sqlite,
+    }
+
+    impl std::str::FromStr for Backend {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<Backend> {
+            match s.trim().to_lowercase().as_str() {
+                "mysql" => Ok(Backend::MySql),
+                "sqlite" => Ok(Backend::Sqlite),
+                other => Err(AppError::Parse(format!("Unknown backend \"{}\".", other))),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Backend {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(match self {
+                Backend::MySql => "mysql",
+                Backend::Sqlite => "sqlite",
+            })
+        }
+    }
+
+    fn env_var<F, T>(
+        var: &F,
+        name: &str,
+        parse: impl Fn(&str) -> errors::Result<T>,
+    ) -> errors::Result<Option<T>>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        /// Read and parse one `MT_` environment variable, naming it on
+        /// failure. Empty variables count as unset.
+        let name = format!("{}{}", ENV_PREFIX, name);
+
+        match var(&name) {
+            Some(value) if !value.trim().is_empty() => parse(value.trim())
+                .map(Some)
+                .map_err(|e| AppError::Parse(format!("{}: {}", name, e))),
+            _ => Ok(None),
+        }
+    }
+
+    fn text(value: &str) -> errors::Result<String> {
+        Ok(value.to_owned())
+    }
+
+    fn secret_file(path: &str) -> errors::Result<String> {
+        /// Read a secret from a file, such as a Docker secret, without
+        /// the line break editors and `echo` leave at the end.
+        std::fs::read_to_string(path)
+            .map(|secret| secret.trim_end_matches(['\r', '\n']).to_owned())
+            .map_err(|e| AppError::Parse(format!("Could not read \"{}\": {}", path, e)))
+    }
+
+    fn number<T: std::str::FromStr>(value: &str) -> errors::Result<T> {
+        value
+            .parse()
+            .map_err(|_| AppError::Parse(format!("\"{}\" is not a valid number.", value)))
+    }
+
+    fn boolean(value: &str) -> errors::Result<bool> {
+        match value.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err(AppError::Parse(format!(
+                "\"{}\" is not true or false.",
+                value
+            ))),
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(try_from = "String", into = "String")]
+    pub enum BindAddress {
+        /// Where the server accepts connections: an IP address, with a
+        /// port overriding `Config::port`, or a Unix socket for a reverse
+        /// proxy to connect to.
+        Tcp(IpAddr, Option<u16>),
+        Unix(PathBuf),
+    }
+
+    impl BindAddress {
+        pub fn socket_addr(&self, default_port: u16) -> Option<SocketAddr> {
+            match self {
+                BindAddress::Tcp(ip, port) => {
+                    Some(SocketAddr::new(*ip, port.unwrap_or(default_port)))
+                }
+                BindAddress::Unix(_) => None,
+            }
+        }
+    }
+
+    impl std::fmt::Display for BindAddress {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                BindAddress::Tcp(ip, None) => write!(f, "{}", ip),
+                BindAddress::Tcp(ip, Some(port)) => write!(f, "{}", SocketAddr::new(*ip, *port)),
+                BindAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+            }
+        }
+    }
+
+    impl std::str::FromStr for BindAddress {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<BindAddress> {
+            let s = s.trim();
+
+            if let Some(path) = s.strip_prefix("unix:") {
+                return match path {
+                    "" => Err(AppError::Parse("Missing Unix socket path.".to_owned())),
+                    path => Ok(BindAddress::Unix(PathBuf::from(path))),
+                };
+            }
+
+            let invalid = || {
+                AppError::Parse(format!(
+                    "Invalid bind address \"{}\", expected an IP address, \"localhost\" or \"unix:<path>\".",
+                    s
+                ))
+            };
+            let host_ip = |host: &str| match host {
+                "localhost" => Ok(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                host => host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .map_err(|_| invalid()),
             };
 
-            Ok(result)
+            if let Ok(ip) = host_ip(s) {
+                return Ok(BindAddress::Tcp(ip, None));
+            }
+
+            // `host:port`, with IPv6 hosts in brackets
+            match s.rsplit_once(':') {
+                Some((host, port)) if !host.contains(':') || host.starts_with('[') => Ok(
+                    BindAddress::Tcp(host_ip(host)?, Some(functions::parse_port(port)?)),
+                ),
+                _ => Err(invalid()),
+            }
+        }
+    }
+
+    impl TryFrom<String> for BindAddress {
+        type Error = AppError;
+
+        fn try_from(s: String) -> errors::Result<BindAddress> {
+            s.parse()
+        }
+    }
+
+    impl From<BindAddress> for String {
+        fn from(address: BindAddress) -> String {
+            address.to_string()
         }
     }
 
+    fn port<'de, D>(deserializer: D) -> Result<u16, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        /// Read a port, rejecting out-of-range values with a clear message.
+        functions::check_port(i64::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+
+    fn optional_port<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Option::<i64>::deserialize(deserializer)?
+            .map(|port| functions::check_port(port).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct Config {
-        pub port: u32,
+        #[serde(default = "Config::default_port", deserialize_with = "port")]
+        pub port: u16,
+        /// Addresses to listen on, all sharing `port` unless they name
+        /// their own.
+        #[serde(default = "Config::default_bind")]
+        pub bind: Vec<BindAddress>,
+        #[serde(default)]
+        pub backend: Backend,
+        #[serde(default = "Config::default_sqlite_path")]
+        pub sqlite_path: String,
+        #[serde(default)]
+        pub pool: PoolConfig,
+        /// Require a logged-in user for every change. Only turn this off
+        /// when the server cannot be reached by anyone untrusted.
+        #[serde(default = "Config::default_require_login")]
+        pub require_login: bool,
+        /// Name entries are recorded under while login is turned off.
+        #[serde(default = "Config::default_author")]
+        pub author: String,
+        /// Serve HTTPS instead of plain HTTP when set.
+        #[serde(default)]
+        pub tls: Option<TlsConfig>,
     }
 
     impl Config {
-        pub fn from_prompt() -> Self {
-            let mut port_int: u32 = 80;
+        pub fn load(path: &Path) -> errors::Result<Self> {
+            /// Read and check the config file, naming the file and the
+            /// problem on failure.
+            let invalid = |e: String| AppError::Parse(format!("{}: {}", path.display(), e));
+
+            let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+            let config: Config = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?,
+                _ => serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?,
+            };
+
+            if config.bind.is_empty() {
+                return Err(invalid("\"bind\" needs at least one address.".to_owned()));
+            }
+
+            Ok(config)
+        }
+
+        pub fn apply_env<F>(&mut self, var: F) -> errors::Result<()>
+        where
+            F: Fn(&str) -> Option<String>,
+        {
+            /// Override settings with `MT_` environment variables, which
+            /// take precedence over the config file.
+            if let Some(port) = env_var(&var, "PORT", functions::parse_port)? {
+                self.port = port;
+            }
+            if let Some(bind) = env_var(&var, "BIND", |value| {
+                value.split(',').map(|address| address.parse()).collect()
+            })? {
+                self.bind = bind;
+            }
+            if let Some(backend) = env_var(&var, "BACKEND", |value| value.parse())? {
+                self.backend = backend;
+            }
+            if let Some(sqlite_path) = env_var(&var, "SQLITE_PATH", text)? {
+                self.sqlite_path = sqlite_path;
+            }
+            if let Some(require_login) = env_var(&var, "REQUIRE_LOGIN", boolean)? {
+                self.require_login = require_login;
+            }
+            if let Some(author) = env_var(&var, "AUTHOR", text)? {
+                self.author = author;
+            }
+
+            let pool = &mut self.pool;
+            if let Some(min) = env_var(&var, "POOL_MIN_CONNECTIONS", number)? {
+                pool.min_connections = min;
+            }
+            if let Some(max) = env_var(&var, "POOL_MAX_CONNECTIONS", number)? {
+                pool.max_connections = max;
+            }
+            if let Some(timeout) = env_var(&var, "POOL_ACQUIRE_TIMEOUT_MS", number)? {
+                pool.acquire_timeout_ms = timeout;
+            }
+            if let Some(timeout) = env_var(&var, "POOL_CONNECT_TIMEOUT_MS", number)? {
+                pool.connect_timeout_ms = timeout;
+            }
+
+            let cert_path = env_var(&var, "TLS_CERT_PATH", text)?;
+            let key_path = env_var(&var, "TLS_KEY_PATH", text)?;
+            let redirect_port = env_var(&var, "TLS_REDIRECT_PORT", functions::parse_port)?;
+
+            match (&mut self.tls, cert_path, key_path) {
+                (Some(tls), cert_path, key_path) => {
+                    tls.cert_path = cert_path.unwrap_or(tls.cert_path.clone());
+                    tls.key_path = key_path.unwrap_or(tls.key_path.clone());
+                }
+                (None, Some(cert_path), Some(key_path)) => {
+                    self.tls = Some(TlsConfig {
+                        cert_path,
+                        key_path,
+                        redirect_port: None,
+                    });
+                }
+                (None, None, None) => {}
+                (None, _, _) => {
+                    return Err(AppError::Parse(format!(
+                        "{0}TLS_CERT_PATH and {0}TLS_KEY_PATH must be set together.",
+                        ENV_PREFIX
+                    )))
+                }
+            }
 
+            if let Some(redirect_port) = redirect_port {
+                match &mut self.tls {
+                    Some(tls) => tls.redirect_port = Some(redirect_port),
+                    None => {
+                        return Err(AppError::Parse(format!(
+                            "{}TLS_REDIRECT_PORT needs a certificate and key to be set.",
+                            ENV_PREFIX
+                        )))
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn prompt(&mut self, input: &mut impl io::BufRead) -> errors::Result<()> {
+            /// Ask for the port, backend and SQLite file. An empty answer
+            /// keeps the current value, and every other setting is left as
+            /// it is.
             loop {
-                println!("Port (Default=80):");
-                let mut port_string = String::new();
-                io::stdin().read_line(&mut port_string);
+                println!("Port (Default={}):", self.port);
+                let port_string = functions::read_line_from(input)?;
 
                 if port_string.trim() == "" {
                     break;
                 }
 
-                match port_string.trim().parse::<u32>() {
+                match functions::parse_port(port_string.trim()) {
                     Ok(port) => {
-                        port_int = port;
+                        self.port = port;
+                        break;
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                }
+            }
+
+            loop {
+                println!(
+                    "Database Backend [mysql/sqlite] (Default={}):",
+                    self.backend
+                );
+                let backend_string = functions::read_line_from(input)?;
+
+                if backend_string.trim() == "" {
+                    break;
+                }
+
+                match backend_string.parse() {
+                    Ok(parsed) => {
+                        self.backend = parsed;
                         break;
                     }
                     Err(e) => {
@@ -93,10 +469,89 @@ pub mod structs {
                 }
             }
 
-            Config { port: port_int }
+            if self.backend == Backend::Sqlite {
+                println!("SQLite File (Default={}):", self.sqlite_path);
+                let path_string = functions::read_line_from(input)?;
+
+                if path_string.trim() != "" {
+                    self.sqlite_path = path_string.trim().to_owned();
+                }
+            }
+
+            Ok(())
+        }
+
+        fn default_port() -> u16 {
+            DEFAULT_PORT
+        }
+
+        fn default_bind() -> Vec<BindAddress> {
+            vec![BindAddress::Tcp(IpAddr::V4(Ipv4Addr::UNSPECIFIED), None)]
+        }
+
+        fn default_sqlite_path() -> String {
+            DEFAULT_SQLITE_PATH.to_owned()
+        }
+
+        fn default_require_login() -> bool {
+            true
+        }
+
+        fn default_author() -> String {
+            DEFAULT_AUTHOR.to_owned()
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            /// Settings used when there is no config file to read.
+            Config {
+                port: Config::default_port(),
+                bind: Config::default_bind(),
+                backend: Backend::default(),
+                sqlite_path: Config::default_sqlite_path(),
+                pool: PoolConfig::default(),
+                require_login: Config::default_require_login(),
+                author: Config::default_author(),
+                tls: None,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct PoolConfig {
+        /// Connection pool settings for the MySQL backend.
+        pub min_connections: usize,
+        pub max_connections: usize,
+        /// How long a request waits for a free connection.
+        pub acquire_timeout_ms: u32,
+        /// How long opening a new connection may take.
+        pub connect_timeout_ms: u64,
+    }
+
+    impl Default for PoolConfig {
+        fn default() -> Self {
+            PoolConfig {
+                min_connections: DEFAULT_POOL_MIN_CONNECTIONS,
+                max_connections: DEFAULT_POOL_MAX_CONNECTIONS,
+                acquire_timeout_ms: DEFAULT_POOL_ACQUIRE_TIMEOUT_MS,
+                connect_timeout_ms: DEFAULT_POOL_CONNECT_TIMEOUT_MS,
+            }
         }
     }
 
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct TlsConfig {
+        /// Certificate chain and private key, both PEM files. They are
+        /// read again when the server receives SIGHUP.
+        pub cert_path: String,
+        pub key_path: String,
+        /// Port to answer plain HTTP on with a redirect to HTTPS.
+        #[serde(default, deserialize_with = "optional_port")]
+        pub redirect_port: Option<u16>,
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct DbCredentials {
         pub user: String,
@@ -106,28 +561,81 @@ pub mod structs {
     }
 
     impl DbCredentials {
-        pub fn from_prompt() -> Self {
-            /// Get user input for database information.
+        pub fn from_prompt() -> errors::Result<Self> {
+            /// Get user input for database information. Fails when there
+            /// is no input to read, rather than waiting or guessing.
             println!("Username:");
-            let mut username = String::new();
-            io::stdin().read_line(&mut username);
+            let username = functions::read_line()?;
 
             println!("Password:");
-            let password = read_password().unwrap();
+            let password = read_password()?;
 
             println!("MySQL URL:");
-            let mut url = String::new();
-            io::stdin().read_line(&mut url);
+            let url = functions::read_line()?;
 
             println!("Database Name:");
-            let mut name = String::new();
-            io::stdin().read_line(&mut name);
+            let name = functions::read_line()?;
 
-            DbCredentials {
+            Ok(DbCredentials {
                 user: username.trim().to_owned(),
                 pass: password.trim().to_owned(),
                 db_url: url.trim().to_owned(),
                 db_name: name.trim().to_owned(),
+            })
+        }
+
+        pub fn from_env<F>(saved: Option<DbCredentials>, var: F) -> errors::Result<Option<Self>>
+        where
+            F: Fn(&str) -> Option<String>,
+        {
+            /// Override saved credentials with `MT_DB_` environment
+            /// variables. The password may instead be read from the file
+            /// named by `MT_DB_PASSWORD_FILE`. Without saved credentials,
+            /// either all four settings are needed or none.
+            let user = env_var(&var, "DB_USER", text)?;
+            let pass = match (
+                env_var(&var, "DB_PASSWORD", text)?,
+                env_var(&var, "DB_PASSWORD_FILE", secret_file)?,
+            ) {
+                (Some(_), Some(_)) => {
+                    return Err(AppError::Parse(format!(
+                        "Set {0}DB_PASSWORD or {0}DB_PASSWORD_FILE, not both.",
+                        ENV_PREFIX
+                    )))
+                }
+                (pass, pass_file) => pass.or(pass_file),
+            };
+            let db_url = env_var(&var, "DB_HOST", text)?;
+            let db_name = env_var(&var, "DB_NAME", text)?;
+
+            match (saved, user, pass, db_url, db_name) {
+                (Some(saved), user, pass, db_url, db_name) => Ok(Some(DbCredentials {
+                    user: user.unwrap_or(saved.user),
+                    pass: pass.unwrap_or(saved.pass),
+                    db_url: db_url.unwrap_or(saved.db_url),
+                    db_name: db_name.unwrap_or(saved.db_name),
+                })),
+                (None, Some(user), Some(pass), Some(db_url), Some(db_name)) => {
+                    Ok(Some(DbCredentials {
+                        user,
+                        pass,
+                        db_url,
+                        db_name,
+                    }))
+                }
+                (None, None, None, None, None) => Ok(None),
+                _ => Err(AppError::Parse(format!(
+                    "Set all of {0}DB_USER, {0}DB_PASSWORD, {0}DB_HOST and {0}DB_NAME, or none of them.",
+                    ENV_PREFIX
+                ))),
+            }
+        }
+
+        pub fn masked(&self) -> Self {
+            /// Copy safe to print, with the password hidden.
+            DbCredentials {
+                pass: "********".to_owned(),
+                ..self.clone()
             }
         }
 
@@ -139,15 +647,35 @@ pub mod structs {
         }
     }
 
-    #[derive(Debug)]
+    #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
     pub struct Entry {
+        /// A recorded change to an Item's details.
         pub id: Option<u32>,
+        pub item_id: u32,
         pub cost: Option<u32>,
         pub note: Option<String>,
         pub status: Option<u32>,
         pub visible: bool,
         pub removed: bool,
+        pub user_id: Option<u32>,
+        pub author: Option<String>,
         pub date: Option<String>,
+        /// Schedule whose work this entry records as done.
+        pub schedule_id: Option<u32>,
+        /// Meter reading (hours, kilometres, cycles) taken with this entry.
+        pub meter: Option<u32>,
+        /// Day a Warning or Stopped status must be fixed by, `YYYY-MM-DD`.
+        pub due_date: Option<String>,
+        /// 1 (high) to 3 (low).
+        pub priority: Option<u32>,
+        /// Work order whose opening, assignment or closing this entry records.
+        pub work_order_id: Option<u32>,
+        /// Who the item or its work order was handed to by this entry, such
+        /// as "Assigned to sam"; the note is left as it was.
+        pub assignment: Option<String>,
+        /// Parts taken from stock for this entry; their cost is in `cost`.
+        #[serde(default)]
+        pub parts: Vec<PartUse>,
     }
 
     impl FromRow for Entry {
@@ -155,27 +683,105 @@ pub mod structs {
             /// Convert a row of data into an Entry.
             let mut row = row;
 
-            let result = Entry {
-                id: row.take("id").unwrap(),
-                cost: row.take("cost").unwrap(),
-                note: row.take("note").unwrap(),
-                status: row.take("status").unwrap(),
-                visible: row.take("visible").unwrap(),
-                removed: row.take("removed").unwrap(),
-                date: row.take("date").unwrap(),
-            };
+            match (
+                row.take_opt("id"),
+                row.take_opt("item_id"),
+                row.take_opt("cost"),
+                row.take_opt("note"),
+                row.take_opt("status"),
+                row.take_opt("visible"),
+                row.take_opt("removed"),
+                row.take_opt("user_id"),
+                row.take_opt("author"),
+                row.take_opt("date"),
+                row.take_opt("schedule_id"),
+                row.take_opt("meter"),
+                row.take_opt("due_date"),
+                row.take_opt("priority"),
+                row.take_opt("work_order_id"),
+                row.take_opt("assignment"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(item_id)),
+                    Some(Ok(cost)),
+                    Some(Ok(note)),
+                    Some(Ok(status)),
+                    Some(Ok(visible)),
+                    Some(Ok(removed)),
+                    Some(Ok(user_id)),
+                    Some(Ok(author)),
+                    Some(Ok(date)),
+                    Some(Ok(schedule_id)),
+                    Some(Ok(meter)),
+                    Some(Ok(due_date)),
+                    Some(Ok(priority)),
+                    Some(Ok(work_order_id)),
+                    Some(Ok(assignment)),
+                ) => Ok(Entry {
+                    id,
+                    item_id,
+                    cost,
+                    note,
+                    status,
+                    visible,
+                    removed,
+                    user_id,
+                    author,
+                    date,
+                    schedule_id,
+                    meter,
+                    due_date,
+                    priority,
+                    work_order_id,
+                    assignment,
+                    parts: vec![],
+                }),
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
 
-            Ok(result)
+    impl TryFrom<&rusqlite::Row<'_>> for Entry {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Entry> {
+            /// Convert a SQLite row into an Entry.
+            Ok(Entry {
+                id: row.get("id")?,
+                item_id: row.get("item_id")?,
+                cost: row.get("cost")?,
+                note: row.get("note")?,
+                status: row.get("status")?,
+                visible: row.get("visible")?,
+                removed: row.get("removed")?,
+                user_id: row.get("user_id")?,
+                author: row.get("author")?,
+                date: row.get("date")?,
+                schedule_id: row.get("schedule_id")?,
+                meter: row.get("meter")?,
+                due_date: row.get("due_date")?,
+                priority: row.get("priority")?,
+                work_order_id: row.get("work_order_id")?,
+                assignment: row.get("assignment")?,
+                parts: vec![],
+            })
         }
     }
 
-    #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+    #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, ToSchema)]
     pub struct Item {
         /// An item in the database.
         pub id: Option<u32>,
         pub title: String,
         pub category_id: u32,
         pub details: Option<ItemDetails>,
+        /// Who the item is assigned to, and their name. Only changed by
+        /// assigning the item; ignored when it is saved.
+        #[serde(default)]
+        pub assignee_id: Option<u32>,
+        #[serde(default)]
+        pub assignee: Option<String>,
     }
 
     impl Item {
@@ -185,6 +791,19 @@ pub mod structs {
                 title,
                 category_id,
                 details,
+                assignee_id: None,
+                assignee: None,
+            }
+        }
+    }
+
+    impl Item {
+        pub fn validate(&self) -> errors::Result<()> {
+            validate_title(&self.title, MAX_ITEM_TITLE_LEN)?;
+
+            match &self.details {
+                Some(details) => details.validate(),
+                None => Ok(()),
             }
         }
     }
@@ -202,84 +821,1574 @@ pub mod structs {
         fn from_row_opt(row: Row) -> Result<Item, FromRowError> {
             /// Convert a row of data into an Item.
             let mut row = row;
-            let result = Item {
-                id: row.take("id"),
-                title: row.take("title").unwrap(),
-                category_id: row.take("category_id").unwrap(),
+
+            match (
+                row.take_opt("id"),
+                row.take_opt("title"),
+                row.take_opt("category_id"),
+                row.take_opt("assignee_id"),
+                row.take_opt("assignee"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(title)),
+                    Some(Ok(category_id)),
+                    Some(Ok(assignee_id)),
+                    Some(Ok(assignee)),
+                ) => Ok(Item {
+                    id: Some(id),
+                    title,
+                    category_id,
+                    details: None,
+                    assignee_id,
+                    assignee,
+                }),
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for Item {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Item> {
+            /// Convert a SQLite row into an Item.
+            Ok(Item {
+                id: row.get("id")?,
+                title: row.get("title")?,
+                category_id: row.get("category_id")?,
                 details: None,
-            };
+                assignee_id: row.get("assignee_id")?,
+                assignee: row.get("assignee")?,
+            })
+        }
+    }
 
-            Ok(result)
+    #[derive(
+        Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+    )]
+    #[serde(rename_all = "lowercase")]
+    pub enum Role {
+        /// What a user may change. Each role can do everything the ones
+        /// before it can.
+        /// - `Viewer`: Read only.
+        /// - `Technician`: Status, notes and visibility of items.
+        /// - `Supervisor`: Costs, titles and categories; adds categories and items.
+        /// - `Admin`: Removes and deletes categories, items and entries.
+        Viewer,
+        Technician,
+        Supervisor,
+        Admin,
+    }
+
+    impl Role {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Role::Viewer => "viewer",
+                Role::Technician => "technician",
+                Role::Supervisor => "supervisor",
+                Role::Admin => "admin",
+            }
         }
     }
 
-    #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
-    pub struct ItemDetails {
-        /// Mutable details about an Item.
-        pub cost: Option<u32>,
-        pub note: Option<String>,
-        pub status: u32,
-        pub visible: bool,
-        pub removed: bool,
+    impl std::fmt::Display for Role {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.as_str())
+        }
     }
 
-    impl ItemDetails {
-        pub fn new() -> ItemDetails {
-            ItemDetails {
-                cost: None,
-                note: None,
-                status: 0,
-                visible: true,
-                removed: false,
+    impl std::str::FromStr for Role {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<Role> {
+            match s.trim().to_lowercase().as_str() {
+                "viewer" => Ok(Role::Viewer),
+                "technician" => Ok(Role::Technician),
+                "supervisor" => Ok(Role::Supervisor),
+                "admin" => Ok(Role::Admin),
+                other => Err(AppError::Parse(format!("Unknown role \"{}\".", other))),
             }
         }
+    }
 
-        pub fn from_entry(entry: &Entry) -> ItemDetails {
-            ItemDetails {
-                cost: entry.cost,
-                note: entry.note.clone(),
-                status: entry.status.unwrap_or(0),
-                visible: entry.visible,
-                removed: entry.removed,
+    #[derive(Serialize, Clone, Debug)]
+    pub struct User {
+        /// Someone who can log in to the web UI.
+        pub id: Option<u32>,
+        pub username: String,
+        #[serde(skip_serializing)]
+        pub password_hash: String,
+        pub role: Role,
+    }
+
+    impl User {
+        pub fn new(username: String, password: &str, role: Role) -> errors::Result<User> {
+            /// Validate a new account and hash its password.
+            if username.trim().is_empty() || username.chars().count() > MAX_USERNAME_LEN as usize {
+                return Err(AppError::Validation(format!(
+                    "Username must be between 1 and {} characters.",
+                    MAX_USERNAME_LEN
+                )));
+            }
+
+            if password.chars().count() < MIN_PASSWORD_LEN {
+                return Err(AppError::Validation(format!(
+                    "Password must be at least {} characters.",
+                    MIN_PASSWORD_LEN
+                )));
+            }
+
+            Ok(User {
+                id: None,
+                username,
+                password_hash: functions::hash_password(password)?,
+                role,
+            })
+        }
+
+        pub fn from_prompt(role: Role) -> errors::Result<Self> {
+            /// Get user input for a new account.
+            println!("Username:");
+            let username = functions::read_line()?;
+
+            println!("Password:");
+            let password = read_password()?;
+
+            println!("Confirm password:");
+            if read_password()? != password {
+                return Err(AppError::Validation("Passwords do not match.".to_owned()));
             }
+
+            User::new(username.trim().to_owned(), &password, role)
         }
     }
 
-    impl FromRow for ItemDetails {
-        fn from_row_opt(row: Row) -> Result<ItemDetails, FromRowError> {
-            /// Convert a row of data into an Item.
+    impl FromRow for User {
+        fn from_row_opt(row: Row) -> Result<User, FromRowError> {
+            /// Convert a row of data into a User.
             let mut row = row;
 
-            let result = ItemDetails {
-                cost: row.take("cost").unwrap(),
-                note: row.take("note").unwrap(),
-                status: row.take("status").unwrap(),
-                visible: row.take("visible").unwrap(),
-                removed: row.take("removed").unwrap(),
-            };
+            match (
+                row.take_opt("id"),
+                row.take_opt("username"),
+                row.take_opt("password_hash"),
+                row.take_opt::<String, _>("role"),
+            ) {
+                (Some(Ok(id)), Some(Ok(username)), Some(Ok(password_hash)), Some(Ok(role))) => {
+                    match role.parse() {
+                        Ok(role) => Ok(User {
+                            id,
+                            username,
+                            password_hash,
+                            role,
+                        }),
+                        Err(_) => Err(FromRowError(row)),
+                    }
+                }
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
 
-            Ok(result)
+    impl TryFrom<&rusqlite::Row<'_>> for User {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<User> {
+            /// Convert a SQLite row into a User.
+            Ok(User {
+                id: row.get("id")?,
+                username: row.get("username")?,
+                password_hash: row.get("password_hash")?,
+                role: row.get::<_, String>("role")?.parse().map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?,
+            })
         }
     }
-}
 
-pub mod functions {
-    use std::collections::HashMap;
+    #[derive(Serialize, Clone, Debug, ToSchema)]
+    pub struct ApiToken {
+        /// A token a script can send instead of logging in. Only a hash of
+        /// the token itself is stored.
+        pub id: Option<u32>,
+        pub user_id: u32,
+        pub name: String,
+        /// Highest role the token acts with, whatever its owner's role.
+        pub scope: Option<Role>,
+        pub expires: Option<String>,
+        pub last_used: Option<String>,
+        pub created: Option<String>,
+        pub revoked: bool,
+    }
 
-    use serde_json;
+    impl FromRow for ApiToken {
+        fn from_row_opt(row: Row) -> Result<ApiToken, FromRowError> {
+            /// Convert a row of data into an ApiToken.
+            let mut row = row;
 
-    use crate::Item;
+            match (
+                row.take_opt("id"),
+                row.take_opt("user_id"),
+                row.take_opt("name"),
+                row.take_opt::<Option<String>, _>("scope"),
+                row.take_opt("expires"),
+                row.take_opt("last_used"),
+                row.take_opt("created"),
+                row.take_opt("revoked"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(user_id)),
+                    Some(Ok(name)),
+                    Some(Ok(scope)),
+                    Some(Ok(expires)),
+                    Some(Ok(last_used)),
+                    Some(Ok(created)),
+                    Some(Ok(revoked)),
+                ) => match scope.map(|scope| scope.parse()).transpose() {
+                    Ok(scope) => Ok(ApiToken {
+                        id,
+                        user_id,
+                        name,
+                        scope,
+                        expires,
+                        last_used,
+                        created,
+                        revoked,
+                    }),
+                    Err(_) => Err(FromRowError(row)),
+                },
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
 
-    pub fn parse_json_string(req: String) -> HashMap<u32, Item> {
-        /// Takes JSON data from a POST request and converts it
-        /// into a HashMap of items to update in the database.
-        let mut result: HashMap<u32, Item> = HashMap::new();
-        let mut items: Vec<Item> = serde_json::from_str(&req).unwrap();
+    impl TryFrom<&rusqlite::Row<'_>> for ApiToken {
+        type Error = rusqlite::Error;
 
-        for item in items.iter() {
-            result.insert(item.id.unwrap(), item.clone());
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<ApiToken> {
+            /// Convert a SQLite row into an ApiToken.
+            Ok(ApiToken {
+                id: row.get("id")?,
+                user_id: row.get("user_id")?,
+                name: row.get("name")?,
+                scope: row
+                    .get::<_, Option<String>>("scope")?
+                    .map(|scope| scope.parse())
+                    .transpose()
+                    .map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?,
+                expires: row.get("expires")?,
+                last_used: row.get("last_used")?,
+                created: row.get("created")?,
+                revoked: row.get("revoked")?,
+            })
+        }
+    }
+
+    #[derive(Deserialize, Debug, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct NewApiToken {
+        /// Request to issue an API token.
+        pub name: String,
+        pub scope: Option<Role>,
+        pub expires_in_days: Option<u32>,
+    }
+
+    impl NewApiToken {
+        pub fn validate(&self) -> errors::Result<()> {
+            validate_title(&self.name, MAX_API_TOKEN_NAME_LEN)?;
+
+            if self.expires_in_days == Some(0) {
+                return Err(AppError::Validation(
+                    "A token must last at least one day.".to_owned(),
+                ));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Debug, ToSchema)]
+    pub struct IssuedApiToken {
+        /// A newly issued token. This is the only time `token` is shown.
+        pub token: String,
+        pub api_token: ApiToken,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ScheduleKind {
+        /// How a schedule's period is measured.
+        /// - `Calendar`: Days since the work was last done.
+        /// - `Usage`: Meter units since the work was last done.
+        Calendar,
+        Usage,
+    }
+
+    impl ScheduleKind {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                ScheduleKind::Calendar => "calendar",
+                ScheduleKind::Usage => "usage",
+            }
+        }
+    }
+
+    impl std::str::FromStr for ScheduleKind {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<ScheduleKind> {
+            match s.trim().to_lowercase().as_str() {
+                "calendar" => Ok(ScheduleKind::Calendar),
+                "usage" => Ok(ScheduleKind::Usage),
+                other => Err(AppError::Parse(format!(
+                    "Unknown schedule kind \"{}\".",
+                    other
+                ))),
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+    pub struct Schedule {
+        /// Preventive maintenance due on an item every `period` days, or
+        /// every `period` meter units for usage-based schedules. Once due,
+        /// the item may run `grace` more days or units before it is stopped.
+        pub id: Option<u32>,
+        /// Taken from the URL when a schedule is created.
+        #[serde(default)]
+        pub item_id: u32,
+        pub title: String,
+        pub kind: ScheduleKind,
+        pub period: u32,
+        #[serde(default)]
+        pub grace: u32,
+        /// Meter reading counting starts from until the work is first done.
+        #[serde(default)]
+        pub meter_start: u32,
+        pub created: Option<String>,
+    }
+
+    impl Schedule {
+        pub fn validate(&self) -> errors::Result<()> {
+            validate_title(&self.title, MAX_SCHEDULE_TITLE_LEN)?;
+
+            if self.period == 0 {
+                return Err(AppError::Validation(
+                    "A schedule's period must be at least 1.".to_owned(),
+                ));
+            }
+
+            Ok(())
+        }
+    }
+
+    impl FromRow for Schedule {
+        fn from_row_opt(row: Row) -> Result<Schedule, FromRowError> {
+            /// Convert a row of data into a Schedule.
+            let mut row = row;
+
+            match (
+                row.take_opt("id"),
+                row.take_opt("item_id"),
+                row.take_opt("title"),
+                row.take_opt::<String, _>("kind"),
+                row.take_opt("period"),
+                row.take_opt("grace"),
+                row.take_opt("meter_start"),
+                row.take_opt("created"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(item_id)),
+                    Some(Ok(title)),
+                    Some(Ok(kind)),
+                    Some(Ok(period)),
+                    Some(Ok(grace)),
+                    Some(Ok(meter_start)),
+                    Some(Ok(created)),
+                ) => match kind.parse() {
+                    Ok(kind) => Ok(Schedule {
+                        id,
+                        item_id,
+                        title,
+                        kind,
+                        period,
+                        grace,
+                        meter_start,
+                        created,
+                    }),
+                    Err(_) => Err(FromRowError(row)),
+                },
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for Schedule {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Schedule> {
+            /// Convert a SQLite row into a Schedule.
+            Ok(Schedule {
+                id: row.get("id")?,
+                item_id: row.get("item_id")?,
+                title: row.get("title")?,
+                kind: parse_column(row.get("kind")?)?,
+                period: row.get("period")?,
+                grace: row.get("grace")?,
+                meter_start: row.get("meter_start")?,
+                created: row.get("created")?,
+            })
+        }
+    }
+
+    #[derive(Serialize, Debug, ToSchema)]
+    pub struct ScheduleStatus {
+        /// Where a schedule stands, worked out from its item's history.
+        /// `status` is the item status the schedule calls for: 0 while not
+        /// due, 1 once due and 2 once past the grace period.
+        pub schedule: Schedule,
+        pub last_done: Option<String>,
+        pub due_date: Option<String>,
+        pub due_meter: Option<u32>,
+        pub meter: Option<u32>,
+        pub status: u32,
+    }
+
+    fn parse_column<T>(value: String) -> rusqlite::Result<T>
+    where
+        T: std::str::FromStr<Err = AppError>,
+    {
+        /// Parse a text column of a SQLite row into one of the enums above.
+        value.parse().map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+    pub struct Part {
+        /// A spare part in the catalogue. `unit_cost` is in cents and
+        /// `quantity` is how many are on hand; at or below `reorder_point`
+        /// the part is low on stock.
+        pub id: Option<u32>,
+        pub part_number: String,
+        pub description: String,
+        #[serde(default)]
+        pub unit_cost: u32,
+        #[serde(default)]
+        pub quantity: u32,
+        #[serde(default)]
+        pub reorder_point: u32,
+    }
+
+    impl Part {
+        pub fn validate(&self) -> errors::Result<()> {
+            if self.part_number.trim().is_empty()
+                || self.part_number.chars().count() > MAX_PART_NUMBER_LEN as usize
+            {
+                return Err(AppError::Validation(format!(
+                    "Part number must be between 1 and {} characters.",
+                    MAX_PART_NUMBER_LEN
+                )));
+            }
+
+            if self.description.trim().is_empty()
+                || self.description.chars().count() > MAX_PART_DESCRIPTION_LEN as usize
+            {
+                return Err(AppError::Validation(format!(
+                    "Description must be between 1 and {} characters.",
+                    MAX_PART_DESCRIPTION_LEN
+                )));
+            }
+
+            Ok(())
+        }
+
+        pub fn low_stock(&self) -> bool {
+            self.quantity <= self.reorder_point
+        }
+    }
+
+    impl FromRow for Part {
+        fn from_row_opt(row: Row) -> Result<Part, FromRowError> {
+            /// Convert a row of data into a Part.
+            let mut row = row;
+
+            match (
+                row.take_opt("id"),
+                row.take_opt("part_number"),
+                row.take_opt("description"),
+                row.take_opt("unit_cost"),
+                row.take_opt("quantity"),
+                row.take_opt("reorder_point"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(part_number)),
+                    Some(Ok(description)),
+                    Some(Ok(unit_cost)),
+                    Some(Ok(quantity)),
+                    Some(Ok(reorder_point)),
+                ) => Ok(Part {
+                    id,
+                    part_number,
+                    description,
+                    unit_cost,
+                    quantity,
+                    reorder_point,
+                }),
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for Part {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Part> {
+            /// Convert a SQLite row into a Part.
+            Ok(Part {
+                id: row.get("id")?,
+                part_number: row.get("part_number")?,
+                description: row.get("description")?,
+                unit_cost: row.get("unit_cost")?,
+                quantity: row.get("quantity")?,
+                reorder_point: row.get("reorder_point")?,
+            })
+        }
+    }
+
+    #[derive(Deserialize, Debug, Default, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct PartPatch {
+        /// Fields of a Part to change, such as the stock after a delivery.
+        /// Missing fields are left alone.
+        pub description: Option<String>,
+        pub unit_cost: Option<u32>,
+        pub quantity: Option<u32>,
+        pub reorder_point: Option<u32>,
+    }
+
+    impl PartPatch {
+        pub fn apply(self, part: &mut Part) {
+            if let Some(description) = self.description {
+                part.description = description;
+            }
+            if let Some(unit_cost) = self.unit_cost {
+                part.unit_cost = unit_cost;
+            }
+            if let Some(quantity) = self.quantity {
+                part.quantity = quantity;
+            }
+            if let Some(reorder_point) = self.reorder_point {
+                part.reorder_point = reorder_point;
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+    pub struct PartUse {
+        /// Parts an entry took from stock, at the unit cost they had then.
+        pub part_id: u32,
+        pub quantity: u32,
+        /// Set by the server from the catalogue.
+        #[serde(default)]
+        pub unit_cost: u32,
+    }
+
+    impl FromRow for PartUse {
+        fn from_row_opt(row: Row) -> Result<PartUse, FromRowError> {
+            /// Convert a row of data into a PartUse.
+            let mut row = row;
+
+            match (
+                row.take_opt("part_id"),
+                row.take_opt("quantity"),
+                row.take_opt("unit_cost"),
+            ) {
+                (Some(Ok(part_id)), Some(Ok(quantity)), Some(Ok(unit_cost))) => Ok(PartUse {
+                    part_id,
+                    quantity,
+                    unit_cost,
+                }),
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for PartUse {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<PartUse> {
+            /// Convert a SQLite row into a PartUse.
+            Ok(PartUse {
+                part_id: row.get("part_id")?,
+                quantity: row.get("quantity")?,
+                unit_cost: row.get("unit_cost")?,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub enum WorkOrderState {
+        /// Where a work order is in its life.
+        /// - `Open`: Reported, nobody is on it yet.
+        /// - `Assigned`: Someone is on it.
+        /// - `Closed`: Resolved. Closed work orders cannot change.
+        Open,
+        Assigned,
+        Closed,
+    }
+
+    impl WorkOrderState {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                WorkOrderState::Open => "open",
+                WorkOrderState::Assigned => "assigned",
+                WorkOrderState::Closed => "closed",
+            }
+        }
+    }
+
+    impl std::str::FromStr for WorkOrderState {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<WorkOrderState> {
+            match s.trim().to_lowercase().as_str() {
+                "open" => Ok(WorkOrderState::Open),
+                "assigned" => Ok(WorkOrderState::Assigned),
+                "closed" => Ok(WorkOrderState::Closed),
+                other => Err(AppError::Parse(format!(
+                    "Unknown work order state \"{}\".",
+                    other
+                ))),
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub enum WorkOrderLineKind {
+        /// What a work order line books.
+        /// - `Labour`: Hours worked, `unit_cost` per hour.
+        /// - `Part`: Parts used, `unit_cost` per part.
+        Labour,
+        Part,
+    }
+
+    impl WorkOrderLineKind {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                WorkOrderLineKind::Labour => "labour",
+                WorkOrderLineKind::Part => "part",
+            }
+        }
+    }
+
+    impl std::str::FromStr for WorkOrderLineKind {
+        type Err = AppError;
+
+        fn from_str(s: &str) -> errors::Result<WorkOrderLineKind> {
+            match s.trim().to_lowercase().as_str() {
+                "labour" => Ok(WorkOrderLineKind::Labour),
+                "part" => Ok(WorkOrderLineKind::Part),
+                other => Err(AppError::Parse(format!(
+                    "Unknown work order line kind \"{}\".",
+                    other
+                ))),
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+    pub struct WorkOrderLine {
+        /// Labour or parts booked against a work order. `unit_cost` is in
+        /// cents, like entry costs.
+        pub id: Option<u32>,
+        /// Taken from the URL when a line is added.
+        #[serde(default)]
+        pub work_order_id: u32,
+        pub kind: WorkOrderLineKind,
+        /// Catalogue part a `Part` line takes from stock. The description
+        /// and unit cost then default to the part's.
+        #[serde(default)]
+        pub part_id: Option<u32>,
+        #[serde(default)]
+        pub description: String,
+        pub quantity: u32,
+        #[serde(default)]
+        pub unit_cost: u32,
+        pub created: Option<String>,
+    }
+
+    impl WorkOrderLine {
+        pub fn validate(&self) -> errors::Result<()> {
+            if self.description.trim().is_empty() {
+                return Err(AppError::Validation(
+                    "Description cannot be empty.".to_owned(),
+                ));
+            }
+
+            if self.description.chars().count() > MAX_WORK_ORDER_LINE_LEN as usize {
+                return Err(AppError::Validation(format!(
+                    "Description cannot be longer than {} characters.",
+                    MAX_WORK_ORDER_LINE_LEN
+                )));
+            }
+
+            if self.quantity == 0 {
+                return Err(AppError::Validation(
+                    "Quantity must be at least 1.".to_owned(),
+                ));
+            }
+
+            if self.part_id.is_some() && self.kind != WorkOrderLineKind::Part {
+                return Err(AppError::Validation(
+                    "Only part lines can take parts from stock.".to_owned(),
+                ));
+            }
+
+            Ok(())
+        }
+
+        pub fn cost(&self) -> u32 {
+            self.quantity.saturating_mul(self.unit_cost)
+        }
+    }
+
+    impl FromRow for WorkOrderLine {
+        fn from_row_opt(row: Row) -> Result<WorkOrderLine, FromRowError> {
+            /// Convert a row of data into a WorkOrderLine.
+            let mut row = row;
+
+            match (
+                row.take_opt("id"),
+                row.take_opt("work_order_id"),
+                row.take_opt::<String, _>("kind"),
+                row.take_opt("part_id"),
+                row.take_opt("description"),
+                row.take_opt("quantity"),
+                row.take_opt("unit_cost"),
+                row.take_opt("created"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(work_order_id)),
+                    Some(Ok(kind)),
+                    Some(Ok(part_id)),
+                    Some(Ok(description)),
+                    Some(Ok(quantity)),
+                    Some(Ok(unit_cost)),
+                    Some(Ok(created)),
+                ) => match kind.parse() {
+                    Ok(kind) => Ok(WorkOrderLine {
+                        id,
+                        work_order_id,
+                        kind,
+                        part_id,
+                        description,
+                        quantity,
+                        unit_cost,
+                        created,
+                    }),
+                    Err(_) => Err(FromRowError(row)),
+                },
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for WorkOrderLine {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<WorkOrderLine> {
+            /// Convert a SQLite row into a WorkOrderLine.
+            Ok(WorkOrderLine {
+                id: row.get("id")?,
+                work_order_id: row.get("work_order_id")?,
+                kind: parse_column(row.get("kind")?)?,
+                part_id: row.get("part_id")?,
+                description: row.get("description")?,
+                quantity: row.get("quantity")?,
+                unit_cost: row.get("unit_cost")?,
+                created: row.get("created")?,
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+    pub struct WorkOrder {
+        /// A repair on an item, from the problem reported to its
+        /// resolution. `assignee` is the assigned user's name and `cost`
+        /// the total of the lines in cents; both are only read back.
+        pub id: Option<u32>,
+        pub item_id: u32,
+        pub state: WorkOrderState,
+        pub problem: String,
+        pub assignee_id: Option<u32>,
+        pub assignee: Option<String>,
+        pub resolution: Option<String>,
+        pub opened: Option<String>,
+        pub closed: Option<String>,
+        #[serde(default)]
+        pub cost: u32,
+        /// Only filled in when a single work order is read.
+        #[serde(default)]
+        pub lines: Vec<WorkOrderLine>,
+    }
+
+    impl WorkOrder {
+        pub fn validate(&self) -> errors::Result<()> {
+            if self.problem.trim().is_empty() {
+                return Err(AppError::Validation("Problem cannot be empty.".to_owned()));
+            }
+
+            if self
+                .resolution
+                .as_ref()
+                .is_some_and(|resolution| resolution.trim().is_empty())
+            {
+                return Err(AppError::Validation(
+                    "Resolution cannot be empty.".to_owned(),
+                ));
+            }
+
+            self.lines.iter().try_for_each(WorkOrderLine::validate)
+        }
+    }
+
+    impl FromRow for WorkOrder {
+        fn from_row_opt(row: Row) -> Result<WorkOrder, FromRowError> {
+            /// Convert a row of data into a WorkOrder without its lines.
+            let mut row = row;
+
+            match (
+                row.take_opt("id"),
+                row.take_opt("item_id"),
+                row.take_opt::<String, _>("state"),
+                row.take_opt("problem"),
+                row.take_opt("assignee_id"),
+                row.take_opt("assignee"),
+                row.take_opt("resolution"),
+                row.take_opt("opened"),
+                row.take_opt("closed"),
+                row.take_opt("cost"),
+            ) {
+                (
+                    Some(Ok(id)),
+                    Some(Ok(item_id)),
+                    Some(Ok(state)),
+                    Some(Ok(problem)),
+                    Some(Ok(assignee_id)),
+                    Some(Ok(assignee)),
+                    Some(Ok(resolution)),
+                    Some(Ok(opened)),
+                    Some(Ok(closed)),
+                    Some(Ok(cost)),
+                ) => match state.parse() {
+                    Ok(state) => Ok(WorkOrder {
+                        id,
+                        item_id,
+                        state,
+                        problem,
+                        assignee_id,
+                        assignee,
+                        resolution,
+                        opened,
+                        closed,
+                        cost,
+                        lines: vec![],
+                    }),
+                    Err(_) => Err(FromRowError(row)),
+                },
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for WorkOrder {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<WorkOrder> {
+            /// Convert a SQLite row into a WorkOrder without its lines.
+            Ok(WorkOrder {
+                id: row.get("id")?,
+                item_id: row.get("item_id")?,
+                state: parse_column(row.get("state")?)?,
+                problem: row.get("problem")?,
+                assignee_id: row.get("assignee_id")?,
+                assignee: row.get("assignee")?,
+                resolution: row.get("resolution")?,
+                opened: row.get("opened")?,
+                closed: row.get("closed")?,
+                cost: row.get("cost")?,
+                lines: vec![],
+            })
+        }
+    }
+
+    #[derive(Deserialize, Debug, Default, utoipa::IntoParams)]
+    #[into_params(parameter_in = Query)]
+    pub struct WorkOrderFilter {
+        /// Which work orders to list. Every field left out matches all.
+        pub state: Option<WorkOrderState>,
+        pub item_id: Option<u32>,
+        pub category_id: Option<u32>,
+        pub assignee_id: Option<u32>,
+    }
+
+    #[derive(Deserialize, Debug, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct NewWorkOrder {
+        /// A work order to open. The item keeps its status, due date and
+        /// priority unless they are given.
+        pub problem: String,
+        pub status: Option<u32>,
+        pub due_date: Option<String>,
+        pub priority: Option<u32>,
+    }
+
+    #[derive(Deserialize, Debug, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct WorkOrderAssignment {
+        /// The user to put on a work order.
+        pub assignee_id: u32,
+    }
+
+    #[derive(Deserialize, Debug, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct ItemAssignment {
+        /// The user to put on an item, or `null` to take everyone off it.
+        pub assignee_id: Option<u32>,
+    }
+
+    #[derive(Deserialize, Debug, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct WorkOrderClosing {
+        /// How a work order was resolved, and the status it leaves the
+        /// item in, OK (0) unless given.
+        pub resolution: String,
+        pub status: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Archive {
+        /// Every category, item and entry, as written by `export` and
+        /// read back by `import`.
+        pub version: String,
+        pub categories: Vec<Category>,
+        #[serde(default)]
+        pub parts: Vec<Part>,
+        pub items: Vec<ArchivedItem>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct ArchivedItem {
+        /// An item with its schedules, work orders and full history.
+        #[serde(flatten)]
+        pub item: Item,
+        #[serde(default)]
+        pub schedules: Vec<Schedule>,
+        #[serde(default)]
+        pub work_orders: Vec<WorkOrder>,
+        pub entries: Vec<Entry>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Author {
+        /// Who an entry is recorded as written by.
+        pub user_id: Option<u32>,
+        pub name: String,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct ItemSaveResult {
+        /// Outcome of saving one item as part of a bulk save.
+        pub id: Option<u32>,
+        pub error: Option<String>,
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct SaveReport {
+        /// Outcome of a bulk save. Nothing is saved unless every item is.
+        pub saved: bool,
+        pub items: Vec<ItemSaveResult>,
+    }
+
+    #[derive(Serialize, Debug, ToSchema)]
+    pub struct DueItem {
+        /// A Warning or Stopped item due to be fixed, as listed by the
+        /// overdue view. `days_left` is negative once overdue.
+        pub item: Item,
+        pub days_left: i64,
+        pub overdue: bool,
+    }
+
+    #[derive(Serialize, Debug, ToSchema)]
+    pub struct MyWork {
+        /// What one user is assigned: items still in use and work orders
+        /// not yet closed.
+        pub items: Vec<Item>,
+        pub work_orders: Vec<WorkOrder>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, ToSchema)]
+    pub struct ItemDetails {
+        /// Mutable details about an Item.
+        pub cost: Option<u32>,
+        pub note: Option<String>,
+        pub status: u32,
+        pub visible: bool,
+        pub removed: bool,
+        /// Only recorded with the entry a save creates, never read back:
+        /// the schedule whose work was just done, and a meter reading.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub schedule_id: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub meter: Option<u32>,
+        /// Parts taken from stock by the entry a save creates, never read
+        /// back either. Their cost is added to `cost`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub parts: Vec<PartUse>,
+        /// Day the current status must be fixed by, `YYYY-MM-DD`.
+        #[serde(default)]
+        pub due_date: Option<String>,
+        /// 1 (high) to 3 (low).
+        #[serde(default)]
+        pub priority: Option<u32>,
+        /// Set by the server on the entries work orders record.
+        #[serde(skip)]
+        pub work_order_id: Option<u32>,
+        /// Set by the server on the entries assignments record.
+        #[serde(skip)]
+        pub assignment: Option<String>,
+    }
+
+    impl ItemDetails {
+        pub fn new() -> ItemDetails {
+            ItemDetails {
+                cost: None,
+                note: None,
+                status: 0,
+                visible: true,
+                removed: false,
+                schedule_id: None,
+                meter: None,
+                parts: vec![],
+                due_date: None,
+                priority: None,
+                work_order_id: None,
+                assignment: None,
+            }
+        }
+
+        pub fn validate(&self) -> errors::Result<()> {
+            if self.status > MAX_ITEM_STATUS {
+                return Err(AppError::Validation(format!(
+                    "Invalid status {}.",
+                    self.status
+                )));
+            }
+
+            if let Some(note) = &self.note {
+                if note.len() > MAX_ENTRY_NOTE_LEN as usize {
+                    return Err(AppError::Validation(format!(
+                        "Note cannot be longer than {} bytes.",
+                        MAX_ENTRY_NOTE_LEN
+                    )));
+                }
+            }
+
+            if let Some(due_date) = &self.due_date {
+                if chrono::NaiveDate::parse_from_str(due_date, "%Y-%m-%d").is_err() {
+                    return Err(AppError::Validation(format!(
+                        "Invalid due date \"{}\", expected YYYY-MM-DD.",
+                        due_date
+                    )));
+                }
+            }
+
+            if let Some(priority) = self.priority {
+                if !(1..=MAX_ITEM_PRIORITY).contains(&priority) {
+                    return Err(AppError::Validation(format!(
+                        "Invalid priority {}, expected 1 (high) to {} (low).",
+                        priority, MAX_ITEM_PRIORITY
+                    )));
+                }
+            }
+
+            if let Some(part) = self.parts.iter().find(|part| part.quantity == 0) {
+                return Err(AppError::Validation(format!(
+                    "Quantity of part {} must be at least 1.",
+                    part.part_id
+                )));
+            }
+
+            Ok(())
+        }
+
+        pub fn from_entry(entry: &Entry) -> ItemDetails {
+            ItemDetails {
+                cost: entry.cost,
+                note: entry.note.clone(),
+                status: entry.status.unwrap_or(0),
+                visible: entry.visible,
+                removed: entry.removed,
+                schedule_id: None,
+                meter: None,
+                parts: vec![],
+                due_date: entry.due_date.clone(),
+                priority: entry.priority,
+                work_order_id: None,
+                assignment: None,
+            }
+        }
+    }
+
+    impl FromRow for ItemDetails {
+        fn from_row_opt(row: Row) -> Result<ItemDetails, FromRowError> {
+            /// Convert a row of data into ItemDetails.
+            let mut row = row;
+
+            match (
+                row.take_opt("cost"),
+                row.take_opt("note"),
+                row.take_opt("status"),
+                row.take_opt("visible"),
+                row.take_opt("removed"),
+                row.take_opt("due_date"),
+                row.take_opt("priority"),
+            ) {
+                (
+                    Some(Ok(cost)),
+                    Some(Ok(note)),
+                    Some(Ok(status)),
+                    Some(Ok(visible)),
+                    Some(Ok(removed)),
+                    Some(Ok(due_date)),
+                    Some(Ok(priority)),
+                ) => Ok(ItemDetails {
+                    cost,
+                    note,
+                    status,
+                    visible,
+                    removed,
+                    schedule_id: None,
+                    meter: None,
+                    parts: vec![],
+                    due_date,
+                    priority,
+                    work_order_id: None,
+                    assignment: None,
+                }),
+                _ => Err(FromRowError(row)),
+            }
+        }
+    }
+
+    fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        /// Tell an explicit `null` apart from a field left out of a patch.
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+
+    #[derive(Deserialize, Debug, Default, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct CategoryPatch {
+        /// Fields of a Category to change. Missing fields are left alone.
+        pub title: Option<String>,
+        pub removed: Option<bool>,
+    }
+
+    impl CategoryPatch {
+        pub fn apply(self, category: &mut Category) {
+            if let Some(title) = self.title {
+                category.title = title;
+            }
+            if let Some(removed) = self.removed {
+                category.removed = removed;
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Default, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct ItemPatch {
+        /// Fields of an Item to change. Missing fields are left alone.
+        pub title: Option<String>,
+        pub category_id: Option<u32>,
+        pub details: Option<ItemDetails>,
+    }
+
+    impl ItemPatch {
+        pub fn apply(self, item: &mut Item) {
+            if let Some(title) = self.title {
+                item.title = title;
+            }
+            if let Some(category_id) = self.category_id {
+                item.category_id = category_id;
+            }
+            if let Some(details) = self.details {
+                item.details = Some(details);
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Default, ToSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct EntryPatch {
+        /// Fields of an Entry to correct. Missing fields are left alone;
+        /// `cost`, `note`, `due_date` and `priority` can be cleared with an
+        /// explicit `null`.
+        #[serde(default, deserialize_with = "nullable")]
+        pub cost: Option<Option<u32>>,
+        #[serde(default, deserialize_with = "nullable")]
+        pub note: Option<Option<String>>,
+        pub status: Option<u32>,
+        pub visible: Option<bool>,
+        pub removed: Option<bool>,
+        #[serde(default, deserialize_with = "nullable")]
+        pub due_date: Option<Option<String>>,
+        #[serde(default, deserialize_with = "nullable")]
+        pub priority: Option<Option<u32>>,
+    }
+
+    impl EntryPatch {
+        pub fn apply(self, entry: &mut Entry) {
+            if let Some(cost) = self.cost {
+                entry.cost = cost;
+            }
+            if let Some(note) = self.note {
+                entry.note = note;
+            }
+            if let Some(status) = self.status {
+                entry.status = Some(status);
+            }
+            if let Some(visible) = self.visible {
+                entry.visible = visible;
+            }
+            if let Some(removed) = self.removed {
+                entry.removed = removed;
+            }
+            if let Some(due_date) = self.due_date {
+                entry.due_date = due_date;
+            }
+            if let Some(priority) = self.priority {
+                entry.priority = priority;
+            }
+        }
+    }
+
+    impl TryFrom<&rusqlite::Row<'_>> for ItemDetails {
+        type Error = rusqlite::Error;
+
+        fn try_from(row: &rusqlite::Row) -> rusqlite::Result<ItemDetails> {
+            /// Convert a SQLite row into ItemDetails.
+            Ok(ItemDetails {
+                cost: row.get("cost")?,
+                note: row.get("note")?,
+                status: row.get("status")?,
+                visible: row.get("visible")?,
+                removed: row.get("removed")?,
+                schedule_id: None,
+                meter: None,
+                parts: vec![],
+                due_date: row.get("due_date")?,
+                priority: row.get("priority")?,
+                work_order_id: None,
+                assignment: None,
+            })
+        }
+    }
+}
+
+pub mod errors {
+    use std::fmt;
+
+    use tide::StatusCode;
+
+    pub type Result<T> = std::result::Result<T, AppError>;
+
+    #[derive(Debug)]
+    pub enum AppError {
+        /// Every failure a request can run into, each mapped to one HTTP status.
+        MySql(mysql::Error),
+        Sqlite(rusqlite::Error),
+        Conflict(String),
+        Forbidden(String),
+        Input(String),
+        InvalidTable(String),
+        NotFound(String),
+        Parse(String),
+        PoolExhausted,
+        Schema(String),
+        Unauthorized(String),
+        Validation(String),
+    }
+
+    impl AppError {
+        pub fn status(&self) -> StatusCode {
+            match self {
+                AppError::Conflict(_) => StatusCode::Conflict,
+                AppError::Forbidden(_) => StatusCode::Forbidden,
+                AppError::NotFound(_) => StatusCode::NotFound,
+                AppError::Parse(_) => StatusCode::BadRequest,
+                AppError::PoolExhausted => StatusCode::ServiceUnavailable,
+                AppError::Unauthorized(_) => StatusCode::Unauthorized,
+                AppError::Validation(_) => StatusCode::UnprocessableEntity,
+                AppError::MySql(_)
+                | AppError::Sqlite(_)
+                | AppError::Input(_)
+                | AppError::InvalidTable(_)
+                | AppError::Schema(_) => StatusCode::InternalServerError,
+            }
+        }
+
+        pub fn public_message(&self) -> String {
+            /// Message safe to send to the client. Server-side failures are
+            /// logged in full but only described in general terms.
+            match self.status() {
+                StatusCode::InternalServerError => "Internal database error.".to_owned(),
+                _ => self.to_string(),
+            }
+        }
+    }
+
+    impl fmt::Display for AppError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                AppError::MySql(e) => write!(f, "{}", e),
+                AppError::Sqlite(e) => write!(f, "{}", e),
+                AppError::Conflict(e) => write!(f, "{}", e),
+                AppError::Forbidden(e) => write!(f, "{}", e),
+                AppError::Input(e) => write!(f, "{}", e),
+                AppError::InvalidTable(name) => write!(f, "Invalid table name \"{}\"", name),
+                AppError::NotFound(e) => write!(f, "{}", e),
+                AppError::Parse(e) => write!(f, "{}", e),
+                AppError::PoolExhausted => {
+                    write!(f, "No database connection available, try again later.")
+                }
+                AppError::Schema(e) => write!(f, "{}", e),
+                AppError::Unauthorized(e) => write!(f, "{}", e),
+                AppError::Validation(e) => write!(f, "{}", e),
+            }
+        }
+    }
+
+    impl std::error::Error for AppError {}
+
+    /// Error for a change that would leave a reference dangling.
+    const FOREIGN_KEY_MSG: &str =
+        "Other records still refer to this one, or it refers to one that does not exist.";
+
+    impl From<mysql::Error> for AppError {
+        fn from(e: mysql::Error) -> Self {
+            match e {
+                // ER_ROW_IS_REFERENCED_2 and ER_NO_REFERENCED_ROW_2
+                mysql::Error::MySqlError(mysql::MySqlError {
+                    code: 1451 | 1452, ..
+                }) => AppError::Conflict(FOREIGN_KEY_MSG.to_owned()),
+                e => AppError::MySql(e),
+            }
+        }
+    }
+
+    impl From<mysql::FromRowError> for AppError {
+        fn from(e: mysql::FromRowError) -> Self {
+            AppError::MySql(mysql::Error::FromRowError(e.0))
+        }
+    }
+
+    impl From<std::io::Error> for AppError {
+        fn from(e: std::io::Error) -> Self {
+            AppError::Input(format!("Cannot read input: {}", e))
+        }
+    }
+
+    impl From<rusqlite::Error> for AppError {
+        fn from(e: rusqlite::Error) -> Self {
+            match e {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY =>
+                {
+                    AppError::Conflict(FOREIGN_KEY_MSG.to_owned())
+                }
+                e => AppError::Sqlite(e),
+            }
+        }
+    }
+
+    impl From<serde_json::Error> for AppError {
+        fn from(e: serde_json::Error) -> Self {
+            AppError::Parse(format!("Invalid JSON: {}", e))
+        }
+    }
+}
+
+pub mod functions {
+    use std::collections::HashMap;
+
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+    use argon2::password_hash::SaltString;
+    use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+    use serde_json;
+    use sha2::{Digest, Sha256};
+
+    use crate::constants::API_TOKEN_PREFIX;
+    use crate::core::errors::{AppError, Result};
+    use crate::Item;
+
+    pub fn read_line() -> Result<String> {
+        /// Read a line typed at a prompt.
+        read_line_from(&mut std::io::stdin().lock())
+    }
+
+    pub fn read_line_from(input: &mut impl std::io::BufRead) -> Result<String> {
+        /// Read a line from a prompt's input. Closed input is an error, so
+        /// prompts do not keep asking when nobody can answer.
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(AppError::Input(
+                "No input to read, standard input is closed.".to_owned(),
+            ));
+        }
+
+        Ok(line)
+    }
+
+    pub fn hash_password(password: &str) -> Result<String> {
+        /// Hash a password with Argon2 and a random salt.
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AppError::Validation(format!("Could not hash password: {}.", e)))
+    }
+
+    pub fn check_port(port: i64) -> Result<u16> {
+        /// Reject ports outside 1-65535.
+        match u16::try_from(port) {
+            Ok(port) if port > 0 => Ok(port),
+            _ => Err(AppError::Parse(format!(
+                "Port {} is out of range, it must be between 1 and 65535.",
+                port
+            ))),
+        }
+    }
+
+    pub fn parse_port(port: &str) -> Result<u16> {
+        /// Parse a port typed by a user or read from a bind address.
+        match port.trim().parse::<i64>() {
+            Ok(port) => check_port(port),
+            Err(_) => Err(AppError::Parse(format!("Invalid port \"{}\".", port))),
+        }
+    }
+
+    pub fn constant_time_eq(a: &str, b: &str) -> bool {
+        /// Compare two secrets without leaking where they first differ.
+        let diff = a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b));
+
+        a.len() == b.len() && diff == 0
+    }
+
+    pub fn generate_csrf_token() -> String {
+        /// Generate a random token tying form posts to a session.
+        random_hex()
+    }
+
+    pub fn generate_token() -> String {
+        /// Generate a random API token.
+        format!("{}{}", API_TOKEN_PREFIX, random_hex())
+    }
+
+    pub fn hash_token(token: &str) -> String {
+        /// Hash an API token for storage. Tokens are random, so unlike
+        /// passwords a fast unsalted hash is enough to look them up by.
+        to_hex(&Sha256::digest(token.as_bytes()))
+    }
+
+    fn random_hex() -> String {
+        /// Hex-encode 32 random bytes.
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+
+        to_hex(&bytes)
+    }
+
+    pub fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+        /// Decode a hex string, or `None` if it is not one.
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect()
+    }
+
+    pub fn verify_password(password: &str, password_hash: &str) -> bool {
+        /// Check a password against a stored Argon2 hash.
+        match PasswordHash::new(password_hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn parse_id(param: &str) -> Result<u32> {
+        /// Parse a numeric id taken from a URL.
+        param
+            .parse::<u32>()
+            .map_err(|_| AppError::Parse(format!("Invalid id \"{}\".", param)))
+    }
+
+    pub fn parse_json<T>(req: &str) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        /// Parse the JSON body of a request.
+        Ok(serde_json::from_str::<T>(req)?)
+    }
+
+    pub fn parse_json_string(req: String) -> Result<HashMap<u32, Item>> {
+        /// Takes JSON data from a POST request and converts it
+        /// into a HashMap of items to update in the database.
+        let mut result: HashMap<u32, Item> = HashMap::new();
+        let mut items: Vec<Item> = parse_json(&req)?;
+
+        for item in items.iter() {
+            match item.id {
+                Some(id) => result.insert(id, item.clone()),
+                None => return Err(AppError::Validation("Missing item id.".to_owned())),
+            };
         }
 
-        result
+        Ok(result)
     }
 }
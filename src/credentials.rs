@@ -0,0 +1,219 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+use crate::core::functions;
+use crate::core::structs::DbCredentials;
+use crate::data::constants::ENV_PREFIX;
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredCredentials {
+    /// Contents of the credentials file, sealed when a key is configured.
+    Encrypted {
+        nonce: String,
+        ciphertext: String,
+    },
+    Plain(DbCredentials),
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn check_private(path: &Path) -> io::Result<()> {
+    /// Refuse secrets other users of the machine can read.
+    let mode = fs::metadata(path)
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Could not open \"{}\": {}", path.display(), e),
+            )
+        })?
+        .permissions()
+        .mode();
+
+    if mode & 0o007 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "\"{0}\" can be read by every user, run `chmod 600 {0}` first.",
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_key(hex: &str, source: &str) -> io::Result<Key> {
+    match functions::from_hex(hex.trim()) {
+        Some(bytes) if bytes.len() == 32 => Ok(*Key::from_slice(&bytes)),
+        _ => Err(invalid_data(format!(
+            "{}: the key must be 64 hex characters (32 bytes).",
+            source
+        ))),
+    }
+}
+
+pub fn key<F>(var: F) -> io::Result<Option<Key>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    /// The key sealing the credentials file, from `MT_CREDENTIALS_KEY`
+    /// or the file named by `MT_CREDENTIALS_KEY_FILE`.
+    let key_var = format!("{}CREDENTIALS_KEY", ENV_PREFIX);
+    let key_file_var = format!("{}CREDENTIALS_KEY_FILE", ENV_PREFIX);
+    let set = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+
+    match (set(&key_var), set(&key_file_var)) {
+        (Some(_), Some(_)) => Err(invalid_data(format!(
+            "Set {} or {}, not both.",
+            key_var, key_file_var
+        ))),
+        (Some(hex), None) => parse_key(&hex, &key_var).map(Some),
+        (None, Some(path)) => {
+            let path = Path::new(path.trim());
+            check_private(path)?;
+
+            parse_key(&fs::read_to_string(path)?, &path.display().to_string()).map(Some)
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+pub fn load(path: &Path, key: Option<&Key>) -> io::Result<Option<DbCredentials>> {
+    /// Read saved credentials, decrypting them if they were sealed.
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    check_private(path)?;
+
+    let unreadable = |e: &dyn std::fmt::Display| {
+        invalid_data(format!("Could not read \"{}\": {}", path.display(), e))
+    };
+    let stored = serde_json::from_str::<StoredCredentials>(&fs::read_to_string(path)?)
+        .map_err(|e| unreadable(&e))?;
+
+    let (nonce, ciphertext) = match stored {
+        StoredCredentials::Plain(credentials) => return Ok(Some(credentials)),
+        StoredCredentials::Encrypted { nonce, ciphertext } => (nonce, ciphertext),
+    };
+
+    let key = key.ok_or_else(|| {
+        unreadable(&format!(
+            "it is encrypted, set {0}CREDENTIALS_KEY or {0}CREDENTIALS_KEY_FILE",
+            ENV_PREFIX
+        ))
+    })?;
+
+    let plaintext = match (
+        functions::from_hex(&nonce),
+        functions::from_hex(&ciphertext),
+    ) {
+        (Some(nonce), Some(ciphertext)) if nonce.len() == 12 => ChaCha20Poly1305::new(key)
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| unreadable(&"the key does not match"))?,
+        _ => return Err(unreadable(&"the encrypted contents are damaged")),
+    };
+
+    serde_json::from_slice(&plaintext)
+        .map(Some)
+        .map_err(|e| unreadable(&e))
+}
+
+pub fn save(path: &Path, credentials: &DbCredentials, key: Option<&Key>) -> io::Result<()> {
+    /// Write credentials readable by the owner only, sealed when a key
+    /// is given.
+    let stored = match key {
+        Some(key) => {
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let ciphertext = ChaCha20Poly1305::new(key)
+                .encrypt(&nonce, serde_json::to_vec(credentials)?.as_slice())
+                .map_err(|_| invalid_data("Could not encrypt the credentials.".to_owned()))?;
+
+            StoredCredentials::Encrypted {
+                nonce: functions::to_hex(&nonce),
+                ciphertext: functions::to_hex(&ciphertext),
+            }
+        }
+        None => StoredCredentials::Plain(credentials.clone()),
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+
+    // `mode` only applies to new files, so tighten an older one too
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(serde_json::to_string_pretty(&stored)?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_credentials() -> DbCredentials {
+        DbCredentials {
+            user: "tracker".to_owned(),
+            pass: "hunter22".to_owned(),
+            db_url: "db.plant.local".to_owned(),
+            db_name: "maintenance".to_owned(),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mt-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn sealed_credentials_need_the_key() {
+        let path = temp_path("sealed");
+        let hex = "2b".repeat(32);
+        let sealing_key = key(|name| (name == "MT_CREDENTIALS_KEY").then(|| hex.clone()))
+            .unwrap()
+            .unwrap();
+
+        save(&path, &test_credentials(), Some(&sealing_key)).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+
+        let loaded = load(&path, Some(&sealing_key));
+        let without_key = load(&path, None);
+        let wrong_key = load(&path, Some(&parse_key(&"00".repeat(32), "test").unwrap()));
+        fs::remove_file(&path).unwrap();
+
+        assert!(!contents.contains("hunter22"));
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(loaded.unwrap().unwrap().pass, "hunter22");
+        assert!(without_key.unwrap_err().to_string().contains("encrypted"));
+        assert!(wrong_key
+            .unwrap_err()
+            .to_string()
+            .contains("key does not match"));
+        assert!(key(|name| (name == "MT_CREDENTIALS_KEY").then(|| "abc".to_owned())).is_err());
+    }
+
+    #[test]
+    fn world_readable_credentials_are_refused() {
+        let path = temp_path("plain");
+        save(&path, &test_credentials(), None).unwrap();
+        let private = load(&path, None);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let shared = load(&path, None);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(private.unwrap().unwrap().user, "tracker");
+        assert!(shared.unwrap_err().to_string().contains("chmod 600"));
+    }
+}
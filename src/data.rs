@@ -1,19 +1,58 @@
 #![allow(unused)]
 
 pub mod constants {
+    pub const API_TOKEN_PREFIX: &str = "mt_";
+    pub const API_TOKEN_INVALID_MSG: &str = "Invalid, expired or revoked API token.";
     pub const APP_TITLE: &str = "Maintenance Tracker";
     pub const APP_VERSION: &str = "1.0.0";
     pub const CONFIG_FILE: &str = "config.json";
     pub const CREDENTIALS_FILE: &str = "credentials.json";
     pub const CREDENTIALS_INVALID_MSG: &str = "ERROR: Invalid login credentials, please try again.";
+    pub const CSRF_INVALID_MSG: &str = "Missing or invalid CSRF token, please reload the page.";
+    pub const DEFAULT_AUTHOR: &str = "Maintenance";
+    pub const DEFAULT_POOL_ACQUIRE_TIMEOUT_MS: u32 = 5_000;
+    pub const DEFAULT_POOL_CONNECT_TIMEOUT_MS: u64 = 10_000;
+    pub const DEFAULT_POOL_MAX_CONNECTIONS: usize = 10;
+    pub const DEFAULT_POOL_MIN_CONNECTIONS: usize = 1;
+    pub const DEFAULT_PORT: u16 = 80;
+    pub const DEFAULT_SQLITE_PATH: &str = "maintenance_tracker.db";
+    pub const DUE_SOON_DAYS: i64 = 7;
+    pub const ENV_PREFIX: &str = "MT_";
+    pub const EXIT_CODE_DATABASE: u8 = 3;
+    pub const LOGIN_REQUIRED_MSG: &str = "Log in to make changes.";
+    pub const LOGIN_FAILED_MSG: &str = "Invalid username or password.";
+    pub const MAX_API_TOKEN_NAME_LEN: u8 = 50;
     pub const MAX_CATEGORY_TITLE_LEN: u8 = 30;
+    pub const MAX_ITEM_PRIORITY: u32 = 3;
+    pub const MAX_ITEM_STATUS: u32 = 2;
     pub const MAX_ITEM_TITLE_LEN: u8 = 30;
     pub const MAX_ENTRY_NOTE_LEN: u32 = 65_535;
+    pub const MAX_PART_DESCRIPTION_LEN: u8 = 100;
+    pub const MAX_PART_NUMBER_LEN: u8 = 30;
+    pub const MAX_SCHEDULE_TITLE_LEN: u8 = 30;
+    pub const MAX_USERNAME_LEN: u8 = 30;
+    pub const MAX_WORK_ORDER_LINE_LEN: u8 = 100;
+    pub const MIN_PASSWORD_LEN: usize = 8;
+    pub const NO_CREDENTIALS_MSG: &str = "No database credentials are saved, run `maintenance_tracker setup` or set the MT_DB_ variables.";
+    pub const NO_USERS_MSG: &str = "No administrator account exists yet, please create one.";
+    pub const NO_USERS_NON_INTERACTIVE_MSG: &str = "No administrator account exists yet, run `maintenance_tracker user add admin` in a terminal to create an administrator.";
     pub const REFERENCE_ID_CATEGORY: Option<&'static str> = None;
     pub const REFERENCE_ID_ENTRY: Option<&'static str> = Some("item_id");
     pub const REFERENCE_ID_ITEM: Option<&'static str> = Some("category_id");
-    pub const SAVED_CREDENTIALS_INVALID_MSG: &str = "ERROR: Saved login credentials are invalid, please run with the -s flag and enter the correct information.";
+    pub const SAVED_CREDENTIALS_INVALID_MSG: &str = "Saved database credentials were rejected, run `maintenance_tracker setup` and enter the correct information.";
+    pub const SCHEDULE_AUTHOR: &str = "Schedule";
+    pub const SCHEDULE_CHECK_INTERVAL_SECS: u64 = 900;
+    pub const SCHEMA_PENDING_MSG: &str =
+        "Database schema is out of date, run `maintenance_tracker migrate`.";
+    pub const SCHEMA_UP_TO_DATE_MSG: &str = "Database schema is up to date.";
+    pub const TABLE_NAME_API_TOKEN: &str = "api_token";
     pub const TABLE_NAME_CATEGORY: &str = "category";
     pub const TABLE_NAME_ENTRY: &str = "entry";
+    pub const TABLE_NAME_ENTRY_PART: &str = "entry_part";
     pub const TABLE_NAME_ITEM: &str = "item";
+    pub const TABLE_NAME_PART: &str = "part";
+    pub const TABLE_NAME_SCHEDULE: &str = "schedule";
+    pub const TABLE_NAME_USER: &str = "user";
+    pub const TABLE_NAME_WORK_ORDER: &str = "work_order";
+    pub const TABLE_NAME_WORK_ORDER_LINE: &str = "work_order_line";
 }
@@ -2,88 +2,185 @@
 
 pub mod database {
     use std::collections::BTreeMap;
+    use std::time::Duration;
 
     use mysql::{prelude::*, *};
 
+    use super::migrations::{self, Migration};
+    use super::store::{self, Store};
+    use crate::core::errors::AppError;
     use crate::core::structs::*;
     use crate::data::constants::{TABLE_NAME_CATEGORY, TABLE_NAME_ENTRY, TABLE_NAME_ITEM};
 
-    pub fn collect_categories(conn: &mut PooledConn) -> Vec<Category> {
+    /// Columns of `api_token`, with its dates cast to text like the
+    /// entry queries do.
+    const API_TOKEN_COLUMNS: &str = r"
+        id, user_id, name, scope, CAST(expires AS CHAR) AS expires,
+        CAST(last_used AS CHAR) AS last_used, CAST(created AS CHAR) AS created, revoked
+        ";
+
+    /// Columns of `entry`, with `date` cast so the binary protocol hands it
+    /// back as text.
+    const ENTRY_COLUMNS: &str = r"
+        id, item_id, cost, note, status, visible, removed, user_id, author,
+        CAST(date AS CHAR) AS date, schedule_id, meter, CAST(due_date AS CHAR) AS due_date,
+        priority, work_order_id, assignment
+        ";
+
+    /// Columns of `schedule`, with its date cast like the entry columns.
+    const SCHEDULE_COLUMNS: &str = r"
+        id, item_id, title, kind, period, grace, meter_start, CAST(created AS CHAR) AS created
+        ";
+
+    fn convert_rows<T: FromRow>(rows: Vec<Row>) -> store::Result<Vec<T>> {
+        /// Convert rows without panicking when a column is missing or mistyped.
+        rows.into_iter()
+            .map(|row| Ok(T::from_row_opt(row)?))
+            .collect()
+    }
+
+    pub fn collect_api_tokens(conn: &mut PooledConn, user_id: u32) -> store::Result<Vec<ApiToken>> {
+        /// Get every API token issued to a user.
+        convert_rows(conn.exec(
+            format!(
+                "SELECT {} FROM api_token WHERE user_id = :user_id ORDER BY id",
+                API_TOKEN_COLUMNS
+            ),
+            params! {
+                "user_id" => user_id,
+            },
+        )?)
+    }
+
+    pub fn collect_categories(conn: &mut PooledConn) -> store::Result<Vec<Category>> {
         /// Get all categories from the database.
-        conn.query("SELECT * FROM category ORDER BY title").unwrap()
+        convert_rows(conn.query("SELECT * FROM category ORDER BY title")?)
     }
 
-    pub fn collect_items(conn: &mut PooledConn) -> BTreeMap<u32, Item> {
-        /// Get all items from the database.
+    pub fn collect_items(conn: &mut PooledConn) -> store::Result<BTreeMap<u32, Item>> {
+        /// Get all items from the database, each with the details from its
+        /// most recent entry, in a single query.
         /// Returns a BTreeMap to preserve order of insertion.
-        let mut items: Vec<Item> = conn.query("SELECT * FROM item").unwrap();
+        items_from_rows(conn.query(store::COLLECT_ITEMS_QUERY)?)
+    }
 
-        // get vector of most recent entries for each item
-        let mut details_list: Vec<ItemDetails> = Vec::new();
-        for item in items.iter() {
-            let details: ItemDetails = match conn
-                .exec_first(
-                    r"
-                    SELECT cost, note, status, visible, removed
-                    FROM entry WHERE item_id = :item_id ORDER BY id DESC
-                    ",
-                    params! {
-                        "item_id" => item.id,
-                    },
-                )
-                .unwrap()
-            {
-                Some(details) => details,
-                None => ItemDetails::new(),
-            };
+    fn items_from_rows(rows: Vec<Row>) -> store::Result<BTreeMap<u32, Item>> {
+        /// Convert rows of `store::COLLECT_ITEMS_QUERY` into items by id.
+        let mut result: BTreeMap<u32, Item> = BTreeMap::new();
+        for row in rows {
+            let has_entry = matches!(row.get_opt::<Option<u32>, _>("entry_id"), Some(Ok(Some(_))));
+            let mut item = Item::from_row_opt(row.clone())?;
 
-            details_list.push(details);
-        }
+            item.details = Some(match has_entry {
+                true => ItemDetails::from_row_opt(row)?,
+                false => ItemDetails::new(),
+            });
 
-        let mut result: BTreeMap<u32, Item> = BTreeMap::new();
-        for (item, details) in items.iter_mut().zip(details_list.iter()) {
-            item.details = Some(details.clone());
-            result.insert(item.id.unwrap(), item.clone());
+            if let Some(id) = item.id {
+                result.insert(id, item);
+            }
         }
 
-        result
+        Ok(result)
     }
 
-    pub fn collect_item_entries(conn: &mut PooledConn, item_id: &str) -> Vec<Entry> {
+    pub fn collect_item_entries(conn: &mut PooledConn, item_id: u32) -> store::Result<Vec<Entry>> {
         /// Get all entries from the database.
         /// Returns a Vector of Entry.
-        let mut entries: Vec<Entry> = conn
-            .query(&format!("SELECT * FROM entry WHERE item_id = {}", item_id))
-            .unwrap();
+        let mut entries = convert_rows(conn.exec(
+            format!(
+                "SELECT {} FROM entry WHERE item_id = :item_id ORDER BY date, id",
+                ENTRY_COLUMNS
+            ),
+            params! {
+                "item_id" => item_id,
+            },
+        )?)?;
+        store::attach_parts(
+            &mut entries,
+            collect_entry_parts(conn, Some(item_id), None)?,
+        );
+
+        Ok(entries)
+    }
+
+    pub fn collect_schedules(
+        conn: &mut PooledConn,
+        item_id: Option<u32>,
+    ) -> store::Result<Vec<Schedule>> {
+        /// Get the schedules of one item, or of every item.
+        convert_rows(conn.exec(
+            format!(
+                r"
+                SELECT {} FROM schedule
+                WHERE removed = 0 AND (:item_id IS NULL OR item_id = :item_id)
+                ORDER BY id
+                ",
+                SCHEDULE_COLUMNS
+            ),
+            params! {
+                "item_id" => item_id,
+            },
+        )?)
+    }
 
-        entries
+    pub fn collect_work_orders(
+        conn: &mut PooledConn,
+        filter: &WorkOrderFilter,
+    ) -> store::Result<Vec<WorkOrder>> {
+        /// Get the work orders matching a filter, without their lines.
+        convert_rows(conn.exec(
+            format!("{} {}", store::WORK_ORDER_QUERY, store::WORK_ORDER_FILTER),
+            params! {
+                "state" => filter.state.map(|state| state.as_str()),
+                "item_id" => filter.item_id,
+                "category_id" => filter.category_id,
+                "assignee_id" => filter.assignee_id,
+            },
+        )?)
     }
 
-    pub fn connect(credentials: &DbCredentials) -> Result<mysql::PooledConn> {
-        /// Get options from url and create a pooled connection
+    pub fn connect(credentials: &DbCredentials) -> Result<Conn> {
+        /// Get options from url and open a single connection
         let opts = Opts::from_url(&credentials.mysql_url())?;
-        let pool = Pool::new(opts)?;
 
-        match pool.get_conn() {
-            Ok(conn) => Ok(conn),
-            Err(e) => Err(e),
-        }
+        Conn::new(opts)
+    }
+
+    pub fn count_admins(conn: &mut PooledConn) -> store::Result<u32> {
+        /// Count the administrator accounts in the database.
+        let count: Option<u32> = conn.exec_first(
+            "SELECT COUNT(*) FROM user WHERE role = :role",
+            params! { "role" => Role::Admin.as_str() },
+        )?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    pub fn create_pool(credentials: &DbCredentials, pool_config: &PoolConfig) -> Result<Pool> {
+        /// Get options from url and create a connection pool
+        let opts = OptsBuilder::from_opts(Opts::from_url(&credentials.mysql_url())?)
+            .tcp_connect_timeout(Some(Duration::from_millis(pool_config.connect_timeout_ms)));
+
+        Pool::new_manual(
+            pool_config.min_connections,
+            pool_config.max_connections,
+            opts,
+        )
     }
 
-    pub fn delete_category(conn: &mut PooledConn, id: u32) {
+    pub fn delete_category(conn: &mut PooledConn, id: u32) -> Result<()> {
         /// Delete a category from the database.
-        let result = conn
-            .exec_drop(
-                r"
-                UPDATE category
-                SET removed = 1
-                WHERE id = :id;
-                ",
-                params! {
-                    "id" => id,
-                },
-            )
-            .unwrap();
+        conn.exec_drop(
+            r"
+            UPDATE category
+            SET removed = 1
+            WHERE id = :id;
+            ",
+            params! {
+                "id" => id,
+            },
+        )
     }
 
     pub fn delete_item(conn: &mut PooledConn, item_id: u32) -> Result<()> {
@@ -100,122 +197,480 @@ pub mod database {
         )
     }
 
-    pub fn get_autoincremented_id(conn: &mut PooledConn, table_name: &str) -> u32 {
+    pub fn get_autoincremented_id(conn: &mut PooledConn, table_name: &str) -> store::Result<u32> {
         /// Get the autoincremented id of the last inserted row.
-        let new_id: u32 = conn
-            .query(format!(
-                "SELECT id FROM {} ORDER BY id DESC LIMIT 1",
-                table_name
-            ))
-            .unwrap()[0];
-        // update original item's id
+        // table names cannot be bound, so only known tables are formatted in
+        let table_name = store::checked_table_name(table_name)?;
+        let new_id: Option<u32> = conn.query_first(format!(
+            "SELECT id FROM {} ORDER BY id DESC LIMIT 1",
+            table_name
+        ))?;
+
+        Ok(new_id.unwrap_or(0))
+    }
+
+    pub fn find_api_token(conn: &mut PooledConn, token_hash: &str) -> store::Result<ApiToken> {
+        /// Get the unexpired, unrevoked API token with the given hash.
+        let row: Option<Row> = conn.exec_first(
+            format!(
+                r"
+                SELECT {} FROM api_token
+                WHERE token_hash = :token_hash AND revoked = 0
+                AND (expires IS NULL OR expires > NOW())
+                ",
+                API_TOKEN_COLUMNS
+            ),
+            params! {
+                "token_hash" => token_hash,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(ApiToken::from_row_opt(row)?),
+            None => Err(AppError::NotFound("No such API token.".to_owned())),
+        }
+    }
 
-        new_id
+    pub fn get_api_token(conn: &mut PooledConn, id: u32) -> store::Result<ApiToken> {
+        /// Get an API token from the database.
+        let row: Option<Row> = conn.exec_first(
+            format!("SELECT {} FROM api_token WHERE id = :id", API_TOKEN_COLUMNS),
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(ApiToken::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!("No API token with id {}.", id))),
+        }
     }
 
-    pub fn get_category(conn: &mut PooledConn, id: u32) -> Category {
+    pub fn get_category(conn: &mut PooledConn, id: u32) -> store::Result<Category> {
         /// Get a category from the database.
-        match conn
-            .exec_first(
-                "SELECT * FROM category WHERE id = :id",
-                params! {
-                    "id" => id,
-                },
-            )
-            .unwrap()
-        {
-            Some(category) => category,
-            None => panic!("No category with id {}", id),
+        let row: Option<Row> = conn.exec_first(
+            "SELECT * FROM category WHERE id = :id",
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(Category::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!("No category with id {}.", id))),
         }
     }
 
-    pub fn get_entry(conn: &mut PooledConn, id: u32) -> Entry {
-        match conn
-            .exec_first(
-                r"
-                SELECT cost, note, status, visible, removed
-                FROM entry WHERE item_id = :item_id ORDER BY id DESC
-                ",
-                params! {
-                    "item_id" => id,
-                },
-            )
-            .unwrap()
-        {
-            Some(entry) => entry,
-            None => panic!("No entries with item_id {}", id),
+    pub fn get_entry(conn: &mut PooledConn, id: u32) -> store::Result<Entry> {
+        /// Get the most recent entry for an item.
+        let row: Option<Row> = conn.exec_first(
+            format!(
+                "SELECT {} FROM entry WHERE item_id = :item_id ORDER BY id DESC",
+                ENTRY_COLUMNS
+            ),
+            params! {
+                "item_id" => id,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(Entry::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!(
+                "No entries with item_id {}.",
+                id
+            ))),
+        }
+    }
+
+    pub fn get_entry_by_id(conn: &mut PooledConn, id: u32) -> store::Result<Entry> {
+        /// Get a single entry from the database.
+        let row: Option<Row> = conn.exec_first(
+            format!("SELECT {} FROM entry WHERE id = :id", ENTRY_COLUMNS),
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        let mut entry = match row {
+            Some(row) => Entry::from_row_opt(row)?,
+            None => return Err(AppError::NotFound(format!("No entry with id {}.", id))),
+        };
+        store::attach_parts(
+            std::slice::from_mut(&mut entry),
+            collect_entry_parts(conn, None, Some(id))?,
+        );
+
+        Ok(entry)
+    }
+
+    fn collect_entry_parts(
+        conn: &mut PooledConn,
+        item_id: Option<u32>,
+        entry_id: Option<u32>,
+    ) -> store::Result<Vec<(u32, PartUse)>> {
+        /// Get the parts used by an item's entries, or by one entry.
+        let rows: Vec<Row> = conn.exec(
+            store::ENTRY_PARTS_QUERY,
+            params! {
+                "item_id" => item_id,
+                "entry_id" => entry_id,
+            },
+        )?;
+
+        rows.into_iter()
+            .map(|row| {
+                let entry_id: Option<u32> = row.get_opt("entry_id").and_then(|id| id.ok());
+                match entry_id {
+                    Some(entry_id) => Ok((entry_id, PartUse::from_row_opt(row)?)),
+                    None => Err(FromRowError(row).into()),
+                }
+            })
+            .collect()
+    }
+
+    pub fn collect_parts(conn: &mut PooledConn) -> store::Result<Vec<Part>> {
+        /// Get the parts catalogue.
+        convert_rows(conn.query("SELECT * FROM part ORDER BY part_number")?)
+    }
+
+    pub fn get_part(conn: &mut PooledConn, id: u32) -> store::Result<Part> {
+        /// Get a part from the catalogue.
+        let row: Option<Row> = conn.exec_first(
+            "SELECT * FROM part WHERE id = :id",
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(Part::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!("No part with id {}.", id))),
         }
     }
 
-    pub fn get_item(conn: &mut PooledConn, id: u32) -> Item {
+    pub fn insert_part(conn: &mut PooledConn, part: &mut Part) -> mysql::Result<()> {
+        /// Add a part to the catalogue.
+        conn.exec_drop(
+            r"
+            INSERT INTO part (part_number, description, unit_cost, quantity, reorder_point)
+            VALUES (:part_number, :description, :unit_cost, :quantity, :reorder_point)
+            ",
+            params! {
+                "part_number" => &part.part_number,
+                "description" => &part.description,
+                "unit_cost" => part.unit_cost,
+                "quantity" => part.quantity,
+                "reorder_point" => part.reorder_point,
+            },
+        )?;
+
+        part.id = Some(conn.last_insert_id() as u32);
+
+        Ok(())
+    }
+
+    pub fn update_part(conn: &mut PooledConn, part: &Part) -> mysql::Result<()> {
+        /// Save a part's description, cost and stock.
+        conn.exec_drop(
+            r"
+            UPDATE part
+            SET description = :description, unit_cost = :unit_cost,
+            quantity = :quantity, reorder_point = :reorder_point
+            WHERE id = :id
+            ",
+            params! {
+                "id" => part.id,
+                "description" => &part.description,
+                "unit_cost" => part.unit_cost,
+                "quantity" => part.quantity,
+                "reorder_point" => part.reorder_point,
+            },
+        )
+    }
+
+    pub fn get_item(conn: &mut PooledConn, id: u32) -> store::Result<Item> {
         /// Get an item from the database.
-        let item: Option<Item> = conn
-            .exec_first(
-                "SELECT * FROM item WHERE id = :id",
-                params! {
-                    "id" => id,
-                },
-            )
-            .unwrap();
+        let row: Option<Row> = conn.exec_first(
+            store::ITEM_QUERY,
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        let mut item = match row {
+            Some(row) => Item::from_row_opt(row)?,
+            None => return Err(AppError::NotFound(format!("No item with id {}.", id))),
+        };
+
+        item.details = Some(match get_entry(conn, id) {
+            Ok(entry) => ItemDetails::from_entry(&entry),
+            Err(AppError::NotFound(_)) => ItemDetails::new(),
+            Err(e) => return Err(e),
+        });
+
+        Ok(item)
+    }
+
+    pub fn get_schedule(conn: &mut PooledConn, id: u32) -> store::Result<Schedule> {
+        /// Get a schedule that has not been removed.
+        let row: Option<Row> = conn.exec_first(
+            format!(
+                "SELECT {} FROM schedule WHERE id = :id AND removed = 0",
+                SCHEDULE_COLUMNS
+            ),
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(Schedule::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!("No schedule with id {}.", id))),
+        }
+    }
 
-        match item {
-            Some(Item {
-                category_id, title, ..
-            }) => Item {
-                id: Some(id),
-                category_id: category_id,
-                title: title,
-                details: Some(ItemDetails::from_entry(&get_entry(conn, id))),
+    pub fn get_user(conn: &mut PooledConn, id: u32) -> store::Result<User> {
+        /// Get a user account from the database.
+        let row: Option<Row> = conn.exec_first(
+            "SELECT id, username, password_hash, role FROM user WHERE id = :id",
+            params! {
+                "id" => id,
             },
-            None => panic!("No item with id {}", id),
+        )?;
+
+        match row {
+            Some(row) => Ok(User::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!("No user with id {}.", id))),
         }
     }
 
-    pub fn insert_category(conn: &mut PooledConn, title: &str) -> mysql::Result<()> {
-        /// Insert a category into the database.
-        match conn.query_drop(format!(
-            "INSERT INTO category (title, removed) VALUES ('{}', 0)",
-            title
-        )) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+    pub fn get_user_by_name(conn: &mut PooledConn, username: &str) -> store::Result<User> {
+        /// Get a user account by its username.
+        let row: Option<Row> = conn.exec_first(
+            "SELECT id, username, password_hash, role FROM user WHERE username = :username",
+            params! {
+                "username" => username,
+            },
+        )?;
+
+        match row {
+            Some(row) => Ok(User::from_row_opt(row)?),
+            None => Err(AppError::NotFound(format!(
+                "No user named \"{}\".",
+                username
+            ))),
         }
     }
 
-    pub fn insert_entry(conn: &mut PooledConn, item: &Item) -> mysql::Result<()> {
-        /// Insert an entry into the database.
-        let details = item.details.as_ref().unwrap();
-        match conn.exec_drop(
+    pub fn get_work_order(conn: &mut PooledConn, id: u32) -> store::Result<WorkOrder> {
+        /// Get a work order with its lines.
+        let row: Option<Row> = conn.exec_first(
+            format!("{} WHERE work_order.id = :id", store::WORK_ORDER_QUERY),
+            params! {
+                "id" => id,
+            },
+        )?;
+
+        let mut order = match row {
+            Some(row) => WorkOrder::from_row_opt(row)?,
+            None => return Err(AppError::NotFound(format!("No work order with id {}.", id))),
+        };
+
+        order.lines = convert_rows(conn.exec(
+            store::WORK_ORDER_LINES_QUERY,
+            params! {
+                "work_order_id" => id,
+            },
+        )?)?;
+
+        Ok(order)
+    }
+
+    pub fn insert_api_token(
+        conn: &mut PooledConn,
+        token: &mut ApiToken,
+        token_hash: &str,
+        expires_in_days: Option<u32>,
+    ) -> mysql::Result<()> {
+        /// Insert an API token into the database. A token without
+        /// `expires_in_days` never expires.
+        conn.exec_drop(
+            r"
+            INSERT INTO api_token (user_id, name, token_hash, scope, expires)
+            VALUES (
+                :user_id,
+                :name,
+                :token_hash,
+                :scope,
+                DATE_ADD(NOW(), INTERVAL :expires_in_days DAY)
+            )
+            ",
+            params! {
+                "user_id" => token.user_id,
+                "name" => &token.name,
+                "token_hash" => token_hash,
+                "scope" => token.scope.map(|scope| scope.as_str()),
+                "expires_in_days" => expires_in_days,
+            },
+        )?;
+
+        token.id = Some(conn.last_insert_id() as u32);
+
+        Ok(())
+    }
+
+    pub fn insert_category(conn: &mut PooledConn, title: &str) -> mysql::Result<u32> {
+        /// Insert a category into the database and return its id.
+        conn.exec_drop(
+            "INSERT INTO category (title, removed) VALUES (:title, 0)",
+            params! {
+                "title" => title,
+            },
+        )?;
+
+        Ok(conn.last_insert_id() as u32)
+    }
+
+    fn consume_part<Q: Queryable>(conn: &mut Q, part_id: u32, quantity: u32) -> store::Result<u32> {
+        /// Take parts out of stock, returning their unit cost. Fails
+        /// rather than leaving fewer than none on hand.
+        let row: Option<Row> = conn.exec_first(
+            "SELECT * FROM part WHERE id = :id",
+            params! {
+                "id" => part_id,
+            },
+        )?;
+        let part = match row {
+            Some(row) => Part::from_row_opt(row)?,
+            None => return Err(store::unknown_part(part_id)),
+        };
+
+        let consumed = conn
+            .exec_iter(
+                store::CONSUME_PART,
+                params! {
+                    "id" => part_id,
+                    "quantity" => quantity,
+                },
+            )?
+            .affected_rows();
+        if consumed == 0 {
+            return Err(store::short_of_part(&part, quantity));
+        }
+
+        Ok(part.unit_cost)
+    }
+
+    fn insert_entry_parts<Q: Queryable>(
+        conn: &mut Q,
+        entry_id: u32,
+        parts: &[PartUse],
+    ) -> mysql::Result<()> {
+        /// Record the parts an entry used.
+        conn.exec_batch(
             r"
-            INSERT INTO entry (item_id, cost, note, status, visible, removed)
+            INSERT INTO entry_part (entry_id, part_id, quantity, unit_cost)
+            VALUES (:entry_id, :part_id, :quantity, :unit_cost)
+            ",
+            parts.iter().map(|part| {
+                params! {
+                    "entry_id" => entry_id,
+                    "part_id" => part.part_id,
+                    "quantity" => part.quantity,
+                    "unit_cost" => part.unit_cost,
+                }
+            }),
+        )
+    }
+
+    pub fn insert_entry<Q: Queryable>(
+        conn: &mut Q,
+        item: &Item,
+        author: &Author,
+    ) -> store::Result<u32> {
+        /// Insert an entry into the database, taking the parts it uses out
+        /// of stock and adding them to its cost. Returns the entry's id.
+        let details = item
+            .details
+            .as_ref()
+            .ok_or_else(|| AppError::Validation("Missing item details.".to_owned()))?;
+
+        if let Some(schedule_id) = details.schedule_id {
+            let owned: Option<u32> = conn.exec_first(
+                "SELECT id FROM schedule WHERE id = :id AND item_id = :item_id AND removed = 0",
+                params! {
+                    "id" => schedule_id,
+                    "item_id" => item.id,
+                },
+            )?;
+            if owned.is_none() {
+                return Err(store::foreign_schedule(schedule_id, item));
+            }
+        }
+
+        let mut cost = details.cost;
+        let mut parts = details.parts.clone();
+        for part in parts.iter_mut() {
+            part.unit_cost = consume_part(conn, part.part_id, part.quantity)?;
+            cost = Some(store::add_part_cost(cost, part)?);
+        }
+
+        let entry_id = conn
+            .exec_iter(
+                r"
+            INSERT INTO entry (
+                item_id, cost, note, status, visible, removed, user_id, author, schedule_id, meter,
+                due_date, priority, work_order_id, assignment
+            )
             VALUES (
                 :item_id,
                 :cost,
                 :note,
                 :status,
                 :visible,
-                :removed
+                :removed,
+                :user_id,
+                :author,
+                :schedule_id,
+                :meter,
+                :due_date,
+                :priority,
+                :work_order_id,
+                :assignment
             );
             ",
-            params! {
-                "item_id" => item.id,
-                "cost" => details.cost,
-                "note" => &details.note,
-                "status" => details.status,
-                "visible" => details.visible,
-                "removed" => details.removed,
-            },
-        ) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
-        }
-    }
+                params! {
+                    "item_id" => item.id,
+                    "cost" => cost,
+                    "note" => &details.note,
+                    "status" => details.status,
+                    "visible" => details.visible,
+                    "removed" => details.removed,
+                    "user_id" => author.user_id,
+                    "author" => &author.name,
+                    "schedule_id" => details.schedule_id,
+                    "meter" => details.meter,
+                    "due_date" => &details.due_date,
+                    "priority" => details.priority,
+                    "work_order_id" => details.work_order_id,
+                    "assignment" => &details.assignment,
+                },
+            )?
+            .last_insert_id()
+            .unwrap_or_default() as u32;
+
+        insert_entry_parts(conn, entry_id, &parts)?;
 
-    pub fn insert_item(conn: &mut PooledConn, item: &mut Item) -> mysql::Result<()> {
-        /// Insert an item into the database.
-        let details = item.details.as_ref().unwrap();
-        println!("{:?}", details);
+        Ok(entry_id)
+    }
 
+    pub fn insert_item(
+        conn: &mut Transaction,
+        item: &mut Item,
+        author: &Author,
+    ) -> store::Result<()> {
+        /// Insert an item and its first entry into the database.
+        /// Runs inside a transaction so neither row is kept without the other.
         match conn.exec_drop(
             r"INSERT INTO item (title, category_id)
             VALUES (
@@ -229,66 +684,3554 @@ pub mod database {
             },
         ) {
             Ok(_) => {}
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         }
 
-        item.id = Some(get_autoincremented_id(conn, "item"));
+        // the id generated by this connection, not the newest row overall
+        item.id = conn.last_insert_id().map(|id| id as u32);
 
-        insert_entry(conn, item)?;
+        insert_entry(conn, item, author)?;
 
         Ok(())
     }
 
-    pub fn title_taken(conn: &mut PooledConn, title: &str, table_name: &str) -> bool {
-        /// Check if a title is taken by a category or item.
-        if table_name == TABLE_NAME_CATEGORY {
-            for category in collect_categories(conn) {
-                if category.title == title && !category.removed {
-                    return true;
-                }
-            }
+    pub fn import(conn: &mut Transaction, archive: &Archive) -> store::Result<()> {
+        /// Insert a checked archive's categories, parts and items, giving
+        /// each a new id.
+        let used: Option<u8> = conn.query_first(store::ARCHIVED_TABLES_USED)?;
+        if used.unwrap_or_default() != 0 {
+            return Err(store::database_not_empty());
+        }
 
-            return false;
-        } else if table_name == TABLE_NAME_ITEM {
-            for (_, item) in collect_items(conn) {
-                if item.title == title && !item.details.unwrap().removed {
-                    return true;
-                }
-            }
+        let mut category_ids = BTreeMap::new();
+        for category in &archive.categories {
+            conn.exec_drop(
+                store::IMPORT_CATEGORY,
+                params! {
+                    "title" => &category.title,
+                    "removed" => category.removed,
+                },
+            )?;
+            category_ids.insert(
+                category.id.unwrap_or_default(),
+                conn.last_insert_id().unwrap_or_default() as u32,
+            );
+        }
 
-            return false;
-        } else {
-            panic!("Invalid table name");
+        let mut part_ids = BTreeMap::new();
+        for part in &archive.parts {
+            conn.exec_drop(
+                store::IMPORT_PART,
+                params! {
+                    "part_number" => &part.part_number,
+                    "description" => &part.description,
+                    "unit_cost" => part.unit_cost,
+                    "quantity" => part.quantity,
+                    "reorder_point" => part.reorder_point,
+                },
+            )?;
+            part_ids.insert(
+                part.id.unwrap_or_default(),
+                conn.last_insert_id().unwrap_or_default() as u32,
+            );
         }
-    }
 
-    pub fn test_auth(credentials: &DbCredentials) -> Result<()> {
-        match connect(&credentials) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        for archived in &archive.items {
+            let mut archived = store::imported_item(archived, &category_ids, &part_ids)?;
+            import_item(conn, &mut archived)?;
         }
+
+        Ok(())
     }
 
-    pub fn update_item(conn: &mut PooledConn, item: &Item) -> mysql::Result<()> {
-        /// Update an item in the database.
-        match conn.exec_drop(
-            r"
-            UPDATE item
-            SET title = :title,
-            category_id = :category_id
-            WHERE id = :id;
-            ",
+    fn import_item(conn: &mut Transaction, archived: &mut ArchivedItem) -> store::Result<()> {
+        /// Insert an exported item with its schedules and entries, keeping
+        /// the entries' dates and author names.
+        conn.exec_drop(
+            store::IMPORT_ITEM,
             params! {
-                "id" => item.id,
-                "title" => &item.title,
-                "category_id" => item.category_id,
+                "title" => &archived.item.title,
+                "category_id" => archived.item.category_id,
+                "assignee" => &archived.item.assignee,
             },
-        ) {
-            Ok(_) => {}
-            Err(e) => return Err(e),
+        )?;
+
+        let item_id = conn.last_insert_id().map(|id| id as u32);
+        archived.item.id = item_id;
+
+        let mut schedule_ids = BTreeMap::new();
+        for schedule in archived.schedules.iter_mut() {
+            let old_id = schedule.id;
+            schedule.item_id = item_id.unwrap_or_default();
+            insert_schedule(conn, schedule)?;
+
+            if let Some(old_id) = old_id {
+                schedule_ids.insert(old_id, schedule.id);
+            }
+        }
+
+        let mut work_order_ids = BTreeMap::new();
+        for order in archived.work_orders.iter_mut() {
+            let old_id = order.id;
+            order.item_id = item_id.unwrap_or_default();
+            import_work_order(conn, order)?;
+
+            if let Some(old_id) = old_id {
+                work_order_ids.insert(old_id, order.id);
+            }
+        }
+
+        for entry in archived.entries.iter() {
+            conn.exec_drop(
+                r"
+                INSERT INTO entry (
+                    item_id, cost, note, status, visible, removed, author, date, schedule_id,
+                    meter, due_date, priority, work_order_id, assignment
+                )
+                VALUES (
+                    :item_id,
+                    :cost,
+                    :note,
+                    :status,
+                    :visible,
+                    :removed,
+                    :author,
+                    COALESCE(:date, NOW()),
+                    :schedule_id,
+                    :meter,
+                    :due_date,
+                    :priority,
+                    :work_order_id,
+                    :assignment
+                )
+                ",
+                params! {
+                    "item_id" => item_id,
+                    "cost" => entry.cost,
+                    "note" => &entry.note,
+                    "status" => entry.status,
+                    "visible" => entry.visible,
+                    "removed" => entry.removed,
+                    "author" => &entry.author,
+                    "date" => &entry.date,
+                    "schedule_id" => store::imported_id(
+                        entry,
+                        entry.schedule_id,
+                        &schedule_ids,
+                        "schedule"
+                    )?,
+                    "meter" => entry.meter,
+                    "due_date" => &entry.due_date,
+                    "priority" => entry.priority,
+                    "work_order_id" => store::imported_id(
+                        entry,
+                        entry.work_order_id,
+                        &work_order_ids,
+                        "work order"
+                    )?,
+                    "assignment" => &entry.assignment,
+                },
+            )?;
+
+            let entry_id = conn.last_insert_id().unwrap_or_default() as u32;
+            insert_entry_parts(conn, entry_id, &entry.parts)?;
+        }
+
+        Ok(())
+    }
+
+    fn import_work_order(conn: &mut Transaction, order: &mut WorkOrder) -> Result<()> {
+        /// Insert an exported work order with its lines, assigned to the
+        /// user of the same name if there is one.
+        conn.exec_drop(
+            r"
+            INSERT INTO work_order (
+                item_id, state, problem, assignee_id, resolution, opened, closed
+            )
+            VALUES (
+                :item_id,
+                :state,
+                :problem,
+                (SELECT id FROM user WHERE username = :assignee),
+                :resolution,
+                COALESCE(:opened, NOW()),
+                :closed
+            )
+            ",
+            params! {
+                "item_id" => order.item_id,
+                "state" => order.state.as_str(),
+                "problem" => &order.problem,
+                "assignee" => &order.assignee,
+                "resolution" => &order.resolution,
+                "opened" => &order.opened,
+                "closed" => &order.closed,
+            },
+        )?;
+
+        order.id = conn.last_insert_id().map(|id| id as u32);
+
+        for line in order.lines.iter_mut() {
+            line.work_order_id = order.id.unwrap_or_default();
+            conn.exec_drop(
+                r"
+                INSERT INTO work_order_line (
+                    work_order_id, kind, part_id, description, quantity, unit_cost, created
+                )
+                VALUES (
+                    :work_order_id,
+                    :kind,
+                    :part_id,
+                    :description,
+                    :quantity,
+                    :unit_cost,
+                    COALESCE(:created, NOW())
+                )
+                ",
+                params! {
+                    "work_order_id" => line.work_order_id,
+                    "kind" => line.kind.as_str(),
+                    "part_id" => line.part_id,
+                    "description" => &line.description,
+                    "quantity" => line.quantity,
+                    "unit_cost" => line.unit_cost,
+                    "created" => &line.created,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn insert_schedule(conn: &mut Transaction, schedule: &mut Schedule) -> Result<()> {
+        /// Insert a schedule into the database.
+        conn.exec_drop(
+            r"
+            INSERT INTO schedule (item_id, title, kind, period, grace, meter_start)
+            VALUES (:item_id, :title, :kind, :period, :grace, :meter_start)
+            ",
+            params! {
+                "item_id" => schedule.item_id,
+                "title" => &schedule.title,
+                "kind" => schedule.kind.as_str(),
+                "period" => schedule.period,
+                "grace" => schedule.grace,
+                "meter_start" => schedule.meter_start,
+            },
+        )?;
+
+        schedule.id = conn.last_insert_id().map(|id| id as u32);
+
+        Ok(())
+    }
+
+    pub fn insert_user(conn: &mut PooledConn, user: &mut User) -> mysql::Result<()> {
+        /// Insert a user account into the database.
+        conn.exec_drop(
+            r"
+            INSERT INTO user (username, password_hash, role)
+            VALUES (:username, :password_hash, :role)
+            ",
+            params! {
+                "username" => &user.username,
+                "password_hash" => &user.password_hash,
+                "role" => user.role.as_str(),
+            },
+        )?;
+
+        user.id = Some(conn.last_insert_id() as u32);
+
+        Ok(())
+    }
+
+    pub fn insert_work_order(
+        conn: &mut Transaction,
+        order: &mut WorkOrder,
+        item: &Item,
+        author: &Author,
+    ) -> store::Result<()> {
+        /// Open a work order and record the item's details as its first entry.
+        conn.exec_drop(
+            r"
+            INSERT INTO work_order (item_id, state, problem, assignee_id)
+            VALUES (:item_id, :state, :problem, :assignee_id)
+            ",
+            params! {
+                "item_id" => order.item_id,
+                "state" => order.state.as_str(),
+                "problem" => &order.problem,
+                "assignee_id" => order.assignee_id,
+            },
+        )?;
+
+        order.id = conn.last_insert_id().map(|id| id as u32);
+
+        insert_entry(conn, &store::work_order_entry(order, item), author)?;
+
+        Ok(())
+    }
+
+    pub fn insert_work_order_line(
+        conn: &mut Transaction,
+        line: &mut WorkOrderLine,
+    ) -> store::Result<()> {
+        /// Book labour or parts against a work order. A catalogue part is
+        /// taken out of stock at its current unit cost.
+        if let Some(part_id) = line.part_id {
+            line.unit_cost = consume_part(conn, part_id, line.quantity)?;
+        }
+
+        conn.exec_drop(
+            r"
+            INSERT INTO work_order_line (
+                work_order_id, kind, part_id, description, quantity, unit_cost
+            )
+            VALUES (:work_order_id, :kind, :part_id, :description, :quantity, :unit_cost)
+            ",
+            params! {
+                "work_order_id" => line.work_order_id,
+                "kind" => line.kind.as_str(),
+                "part_id" => line.part_id,
+                "description" => &line.description,
+                "quantity" => line.quantity,
+                "unit_cost" => line.unit_cost,
+            },
+        )?;
+
+        line.id = conn.last_insert_id().map(|id| id as u32);
+
+        Ok(())
+    }
+
+    pub fn remove_schedule(conn: &mut PooledConn, id: u32) -> Result<()> {
+        /// Mark a schedule removed. Entries that completed it keep pointing at it.
+        conn.exec_drop(
+            "UPDATE schedule SET removed = 1 WHERE id = :id",
+            params! {
+                "id" => id,
+            },
+        )
+    }
+
+    pub fn revoke_api_token(conn: &mut PooledConn, id: u32) -> mysql::Result<()> {
+        /// Stop an API token from being accepted.
+        conn.exec_drop(
+            "UPDATE api_token SET revoked = 1 WHERE id = :id",
+            params! {
+                "id" => id,
+            },
+        )
+    }
+
+    pub fn touch_api_token(conn: &mut PooledConn, id: u32) -> mysql::Result<()> {
+        /// Record that an API token was just used.
+        conn.exec_drop(
+            "UPDATE api_token SET last_used = NOW() WHERE id = :id",
+            params! {
+                "id" => id,
+            },
+        )
+    }
+
+    pub fn title_taken(
+        conn: &mut PooledConn,
+        title: &str,
+        table_name: &str,
+    ) -> store::Result<bool> {
+        /// Check if a title is taken by a category or item.
+        let taken: Option<u8> = conn.exec_first(
+            store::title_taken_query(table_name)?,
+            params! {
+                "title" => title,
+            },
+        )?;
+
+        Ok(taken.is_some())
+    }
+
+    pub fn test_auth(credentials: &DbCredentials) -> Result<()> {
+        match connect(credentials) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn update_category(conn: &mut PooledConn, category: &Category) -> Result<()> {
+        /// Update a category in the database.
+        conn.exec_drop(
+            r"
+            UPDATE category
+            SET title = :title,
+            removed = :removed
+            WHERE id = :id;
+            ",
+            params! {
+                "id" => category.id,
+                "title" => &category.title,
+                "removed" => category.removed,
+            },
+        )
+    }
+
+    pub fn update_entry(conn: &mut PooledConn, entry: &Entry) -> Result<()> {
+        /// Correct a recorded entry in place.
+        conn.exec_drop(
+            r"
+            UPDATE entry
+            SET cost = :cost,
+            note = :note,
+            status = :status,
+            visible = :visible,
+            removed = :removed,
+            due_date = :due_date,
+            priority = :priority
+            WHERE id = :id;
+            ",
+            params! {
+                "id" => entry.id,
+                "cost" => entry.cost,
+                "note" => &entry.note,
+                "status" => entry.status.unwrap_or(0),
+                "visible" => entry.visible,
+                "removed" => entry.removed,
+                "due_date" => &entry.due_date,
+                "priority" => entry.priority,
+            },
+        )
+    }
+
+    pub fn assign_item(conn: &mut Transaction, item: &Item, author: &Author) -> store::Result<()> {
+        /// Put an item on its `assignee_id` and record the item's details
+        /// as an entry saying so.
+        conn.exec_drop(
+            "UPDATE item SET assignee_id = :assignee_id WHERE id = :id",
+            params! {
+                "id" => item.id,
+                "assignee_id" => item.assignee_id,
+            },
+        )?;
+
+        insert_entry(conn, item, author)?;
+
+        Ok(())
+    }
+
+    pub fn update_item<Q: Queryable>(
+        conn: &mut Q,
+        item: &Item,
+        author: &Author,
+    ) -> store::Result<()> {
+        /// Update an item in the database.
+        // MySQL only counts rows that actually changed, so check for the item first
+        let exists: Option<u32> = conn.exec_first(
+            "SELECT id FROM item WHERE id = :id",
+            params! {
+                "id" => item.id,
+            },
+        )?;
+        if exists.is_none() {
+            return Err(AppError::NotFound(format!(
+                "No item with id {}.",
+                item.id.unwrap_or_default()
+            )));
+        }
+
+        match conn.exec_drop(
+            r"
+            UPDATE item
+            SET title = :title,
+            category_id = :category_id
+            WHERE id = :id;
+            ",
+            params! {
+                "id" => item.id,
+                "title" => &item.title,
+                "category_id" => item.category_id,
+            },
+        ) {
+            Ok(_) => {}
+            Err(e) => return Err(e.into()),
         };
 
         // create a new entry with updated information
-        insert_entry(conn, item)
+        insert_entry(conn, item, author)?;
+
+        Ok(())
+    }
+
+    pub fn update_work_order(
+        conn: &mut Transaction,
+        order: &WorkOrder,
+        item: &Item,
+        author: &Author,
+    ) -> store::Result<()> {
+        /// Save a work order's new state and record the item's details
+        /// as an entry for it.
+        conn.exec_drop(
+            store::UPDATE_WORK_ORDER,
+            params! {
+                "id" => order.id,
+                "state" => order.state.as_str(),
+                "assignee_id" => order.assignee_id,
+                "resolution" => &order.resolution,
+            },
+        )?;
+
+        insert_entry(conn, &store::work_order_entry(order, item), author)?;
+
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    pub struct MySqlStore {
+        /// Store backed by a MySQL connection pool shared by every request.
+        pool: Pool,
+        acquire_timeout_ms: u32,
+    }
+
+    impl MySqlStore {
+        pub fn new(credentials: &DbCredentials, pool_config: &PoolConfig) -> store::Result<Self> {
+            Ok(MySqlStore {
+                pool: create_pool(credentials, pool_config)?,
+                acquire_timeout_ms: pool_config.acquire_timeout_ms,
+            })
+        }
+
+        fn conn(&self) -> store::Result<PooledConn> {
+            /// Borrow a connection from the pool, giving up once the
+            /// acquire timeout has passed.
+            match self.pool.try_get_conn(self.acquire_timeout_ms) {
+                Ok(conn) => Ok(conn),
+                Err(Error::DriverError(DriverError::Timeout)) => Err(AppError::PoolExhausted),
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+
+    impl Store for MySqlStore {
+        fn assign_item(&self, item: &Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            assign_item(&mut tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::MySql
+        }
+
+        fn applied_migrations(&self) -> store::Result<Vec<u32>> {
+            let mut conn = self.conn()?;
+            conn.query_drop(migrations::MYSQL_MIGRATIONS_TABLE)?;
+
+            Ok(conn.query("SELECT version FROM schema_migrations ORDER BY version")?)
+        }
+
+        fn apply_migration(&self, migration: &Migration) -> store::Result<()> {
+            // MySQL commits DDL implicitly, so statements run one at a time
+            // and the version is only recorded once all of them succeed;
+            // statements a failed run already applied are skipped on retry
+            let mut conn = self.conn()?;
+
+            for statement in migration.mysql {
+                match conn.query_drop(statement) {
+                    Err(e) if migrations::already_applied(&e) => {}
+                    result => result?,
+                }
+            }
+
+            conn.exec_drop(
+                "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+                params! {
+                    "version" => migration.version,
+                    "name" => migration.name,
+                },
+            )?;
+
+            Ok(())
+        }
+
+        fn check_columns(&self, table_name: &str, columns: &[&str]) -> store::Result<()> {
+            self.conn()?.query_drop(format!(
+                "SELECT {} FROM {} LIMIT 0",
+                columns.join(", "),
+                store::checked_table_name(table_name)?
+            ))?;
+
+            Ok(())
+        }
+
+        fn collect_api_tokens(&self, user_id: u32) -> store::Result<Vec<ApiToken>> {
+            collect_api_tokens(&mut self.conn()?, user_id)
+        }
+
+        fn collect_categories(&self) -> store::Result<Vec<Category>> {
+            collect_categories(&mut self.conn()?)
+        }
+
+        fn count_admins(&self) -> store::Result<u32> {
+            count_admins(&mut self.conn()?)
+        }
+
+        fn collect_items(&self) -> store::Result<BTreeMap<u32, Item>> {
+            collect_items(&mut self.conn()?)
+        }
+
+        fn collect_item_entries(&self, item_id: u32) -> store::Result<Vec<Entry>> {
+            collect_item_entries(&mut self.conn()?, item_id)
+        }
+
+        fn collect_parts(&self) -> store::Result<Vec<Part>> {
+            collect_parts(&mut self.conn()?)
+        }
+
+        fn collect_schedules(&self, item_id: Option<u32>) -> store::Result<Vec<Schedule>> {
+            collect_schedules(&mut self.conn()?, item_id)
+        }
+
+        fn collect_work_orders(&self, filter: &WorkOrderFilter) -> store::Result<Vec<WorkOrder>> {
+            collect_work_orders(&mut self.conn()?, filter)
+        }
+
+        fn delete_category(&self, id: u32) -> store::Result<()> {
+            Ok(delete_category(&mut self.conn()?, id)?)
+        }
+
+        fn delete_item(&self, item_id: u32) -> store::Result<()> {
+            Ok(delete_item(&mut self.conn()?, item_id)?)
+        }
+
+        fn find_api_token(&self, token_hash: &str) -> store::Result<ApiToken> {
+            find_api_token(&mut self.conn()?, token_hash)
+        }
+
+        fn get_api_token(&self, id: u32) -> store::Result<ApiToken> {
+            get_api_token(&mut self.conn()?, id)
+        }
+
+        fn get_category(&self, id: u32) -> store::Result<Category> {
+            get_category(&mut self.conn()?, id)
+        }
+
+        fn get_entry(&self, item_id: u32) -> store::Result<Entry> {
+            get_entry(&mut self.conn()?, item_id)
+        }
+
+        fn get_entry_by_id(&self, id: u32) -> store::Result<Entry> {
+            get_entry_by_id(&mut self.conn()?, id)
+        }
+
+        fn get_item(&self, id: u32) -> store::Result<Item> {
+            get_item(&mut self.conn()?, id)
+        }
+
+        fn get_part(&self, id: u32) -> store::Result<Part> {
+            get_part(&mut self.conn()?, id)
+        }
+
+        fn get_schedule(&self, id: u32) -> store::Result<Schedule> {
+            get_schedule(&mut self.conn()?, id)
+        }
+
+        fn get_user(&self, id: u32) -> store::Result<User> {
+            get_user(&mut self.conn()?, id)
+        }
+
+        fn get_user_by_name(&self, username: &str) -> store::Result<User> {
+            get_user_by_name(&mut self.conn()?, username)
+        }
+
+        fn get_work_order(&self, id: u32) -> store::Result<WorkOrder> {
+            get_work_order(&mut self.conn()?, id)
+        }
+
+        fn insert_api_token(
+            &self,
+            token: &mut ApiToken,
+            token_hash: &str,
+            expires_in_days: Option<u32>,
+        ) -> store::Result<()> {
+            Ok(insert_api_token(
+                &mut self.conn()?,
+                token,
+                token_hash,
+                expires_in_days,
+            )?)
+        }
+
+        fn import(&self, archive: &Archive) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            import(&mut tx, archive)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_category(&self, title: &str) -> store::Result<u32> {
+            Ok(insert_category(&mut self.conn()?, title)?)
+        }
+
+        fn insert_entry(&self, item: &Item, author: &Author) -> store::Result<u32> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            let id = insert_entry(&mut tx, item, author)?;
+            tx.commit()?;
+
+            Ok(id)
+        }
+
+        fn insert_item(&self, item: &mut Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            // dropping the transaction on error rolls it back
+            insert_item(&mut tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_part(&self, part: &mut Part) -> store::Result<()> {
+            match insert_part(&mut self.conn()?, part) {
+                // ER_DUP_ENTRY, from the part number's unique key
+                Err(mysql::Error::MySqlError(e)) if e.code == 1062 => {
+                    Err(store::part_number_taken(part))
+                }
+                result => Ok(result?),
+            }
+        }
+
+        fn insert_schedule(&self, schedule: &mut Schedule) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            insert_schedule(&mut tx, schedule)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_user(&self, user: &mut User) -> store::Result<()> {
+            Ok(insert_user(&mut self.conn()?, user)?)
+        }
+
+        fn insert_work_order(
+            &self,
+            order: &mut WorkOrder,
+            item: &Item,
+            author: &Author,
+        ) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            insert_work_order(&mut tx, order, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_work_order_line(&self, line: &mut WorkOrderLine) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            insert_work_order_line(&mut tx, line)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn now(&self) -> store::Result<String> {
+            let now: Option<String> = self.conn()?.query_first("SELECT CAST(NOW() AS CHAR)")?;
+
+            Ok(now.unwrap_or_default())
+        }
+
+        fn remove_schedule(&self, id: u32) -> store::Result<()> {
+            Ok(remove_schedule(&mut self.conn()?, id)?)
+        }
+
+        fn revoke_api_token(&self, id: u32) -> store::Result<()> {
+            Ok(revoke_api_token(&mut self.conn()?, id)?)
+        }
+
+        fn title_taken(&self, title: &str, table_name: &str) -> store::Result<bool> {
+            title_taken(&mut self.conn()?, title, table_name)
+        }
+
+        fn touch_api_token(&self, id: u32) -> store::Result<()> {
+            Ok(touch_api_token(&mut self.conn()?, id)?)
+        }
+
+        fn update_category(&self, category: &Category) -> store::Result<()> {
+            Ok(update_category(&mut self.conn()?, category)?)
+        }
+
+        fn update_entry(&self, entry: &Entry) -> store::Result<()> {
+            Ok(update_entry(&mut self.conn()?, entry)?)
+        }
+
+        fn update_item(&self, item: &Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            update_item(&mut tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn update_work_order(
+            &self,
+            order: &WorkOrder,
+            item: &Item,
+            author: &Author,
+        ) -> store::Result<()> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            update_work_order(&mut tx, order, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn update_items(
+            &self,
+            items: &[Item],
+            author: &Author,
+            check: &dyn Fn(&Item, &Item) -> store::Result<()>,
+        ) -> store::Result<SaveReport> {
+            let mut conn = self.conn()?;
+            let mut tx = conn.start_transaction(TxOpts::default())?;
+
+            let ids = store::item_ids(items);
+            if !ids.is_empty() {
+                let current = items_from_rows(tx.exec(store::items_by_id_query(ids.len()), ids)?)?;
+                store::check_all(&current, items, check)?;
+            }
+
+            let report = store::save_all(items, |item| update_item(&mut tx, item, author));
+
+            if report.saved {
+                tx.commit()?;
+            } else {
+                tx.rollback()?;
+            }
+
+            Ok(report)
+        }
+
+        fn update_part(&self, part: &Part) -> store::Result<()> {
+            Ok(update_part(&mut self.conn()?, part)?)
+        }
+    }
+}
+
+pub mod migrations {
+    use super::store::{self, Store};
+    use crate::core::errors::AppError;
+    use crate::core::structs::Backend;
+    use crate::data::constants::{
+        TABLE_NAME_API_TOKEN, TABLE_NAME_CATEGORY, TABLE_NAME_ENTRY, TABLE_NAME_ENTRY_PART,
+        TABLE_NAME_ITEM, TABLE_NAME_PART, TABLE_NAME_SCHEDULE, TABLE_NAME_USER,
+        TABLE_NAME_WORK_ORDER, TABLE_NAME_WORK_ORDER_LINE,
+    };
+
+    #[derive(Debug)]
+    pub struct Migration {
+        /// A numbered schema change with SQL for each backend.
+        pub version: u32,
+        pub name: &'static str,
+        pub mysql: &'static [&'static str],
+        pub sqlite: &'static [&'static str],
+    }
+
+    impl Migration {
+        pub fn statements(&self, backend: &Backend) -> &'static [&'static str] {
+            match backend {
+                Backend::MySql => self.mysql,
+                Backend::Sqlite => self.sqlite,
+            }
+        }
+    }
+
+    pub const MYSQL_MIGRATIONS_TABLE: &str = r"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT NOT NULL PRIMARY KEY,
+            name VARCHAR(100) NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        ";
+
+    pub const SQLITE_MIGRATIONS_TABLE: &str = r"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER NOT NULL PRIMARY KEY,
+            name VARCHAR(100) NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        ";
+
+    /// Every schema change, in order. Never edit or renumber a migration
+    /// once it has shipped; add a new one instead.
+    pub const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "create_schema",
+            mysql: &[
+                r"
+            CREATE TABLE IF NOT EXISTS category (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                title VARCHAR(30) NOT NULL,
+                removed TINYINT NOT NULL DEFAULT 0
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS item (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                title VARCHAR(30) NOT NULL,
+                category_id INT NOT NULL,
+                FOREIGN KEY (category_id) REFERENCES category (id)
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS entry (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                item_id INT NOT NULL,
+                cost INT,
+                note TEXT,
+                status TINYINT NOT NULL DEFAULT 0,
+                visible TINYINT NOT NULL DEFAULT 1,
+                removed TINYINT NOT NULL DEFAULT 0,
+                date DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (item_id) REFERENCES item (id)
+            )
+            ",
+            ],
+            sqlite: &[
+                r"
+            CREATE TABLE IF NOT EXISTS category (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title VARCHAR(30) NOT NULL,
+                removed TINYINT NOT NULL DEFAULT 0
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS item (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title VARCHAR(30) NOT NULL,
+                category_id INT NOT NULL REFERENCES category (id)
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS entry (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                item_id INT NOT NULL REFERENCES item (id),
+                cost INT,
+                note TEXT,
+                status TINYINT NOT NULL DEFAULT 0,
+                visible TINYINT NOT NULL DEFAULT 1,
+                removed TINYINT NOT NULL DEFAULT 0,
+                date DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+            ],
+        },
+        Migration {
+            version: 2,
+            name: "index_entry_item_id",
+            mysql: &["CREATE INDEX entry_item_id ON entry (item_id, id)"],
+            sqlite: &["CREATE INDEX IF NOT EXISTS entry_item_id ON entry (item_id, id)"],
+        },
+        Migration {
+            version: 3,
+            name: "create_user",
+            mysql: &[r"
+            CREATE TABLE IF NOT EXISTS user (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                username VARCHAR(30) NOT NULL UNIQUE,
+                password_hash VARCHAR(255) NOT NULL,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            "],
+            sqlite: &[r"
+            CREATE TABLE IF NOT EXISTS user (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username VARCHAR(30) NOT NULL UNIQUE,
+                password_hash VARCHAR(255) NOT NULL,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            "],
+        },
+        Migration {
+            version: 4,
+            name: "add_user_role",
+            // accounts made before roles existed become technicians rather
+            // than admins, the first admin is made with `user add admin` or
+            // prompted for at the next start
+            mysql: &[
+                "ALTER TABLE user ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'viewer'",
+                "UPDATE user SET role = 'technician'",
+            ],
+            sqlite: &[
+                "ALTER TABLE user ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'viewer'",
+                "UPDATE user SET role = 'technician'",
+            ],
+        },
+        Migration {
+            version: 5,
+            name: "add_entry_author",
+            // the name is kept as well, so entries outlive their author's
+            // account and can be written with login turned off
+            mysql: &[r"
+            ALTER TABLE entry
+            ADD COLUMN user_id INT NULL,
+            ADD COLUMN author VARCHAR(30) NULL,
+            ADD FOREIGN KEY (user_id) REFERENCES user (id)
+            "],
+            sqlite: &[
+                "ALTER TABLE entry ADD COLUMN user_id INT REFERENCES user (id)",
+                "ALTER TABLE entry ADD COLUMN author VARCHAR(30)",
+            ],
+        },
+        Migration {
+            version: 6,
+            name: "create_api_token",
+            mysql: &[r"
+            CREATE TABLE IF NOT EXISTS api_token (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                user_id INT NOT NULL,
+                name VARCHAR(50) NOT NULL,
+                token_hash CHAR(64) NOT NULL UNIQUE,
+                scope VARCHAR(20) NULL,
+                expires DATETIME NULL,
+                last_used DATETIME NULL,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP,
+                revoked TINYINT NOT NULL DEFAULT 0,
+                FOREIGN KEY (user_id) REFERENCES user (id)
+            )
+            "],
+            sqlite: &[r"
+            CREATE TABLE IF NOT EXISTS api_token (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INT NOT NULL REFERENCES user (id),
+                name VARCHAR(50) NOT NULL,
+                token_hash CHAR(64) NOT NULL UNIQUE,
+                scope VARCHAR(20),
+                expires DATETIME,
+                last_used DATETIME,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP,
+                revoked TINYINT NOT NULL DEFAULT 0
+            )
+            "],
+        },
+        Migration {
+            version: 7,
+            name: "create_schedule",
+            mysql: &[
+                r"
+            CREATE TABLE IF NOT EXISTS schedule (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                item_id INT NOT NULL,
+                title VARCHAR(30) NOT NULL,
+                kind VARCHAR(10) NOT NULL,
+                period INT NOT NULL,
+                grace INT NOT NULL DEFAULT 0,
+                meter_start INT NOT NULL DEFAULT 0,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP,
+                removed TINYINT NOT NULL DEFAULT 0,
+                FOREIGN KEY (item_id) REFERENCES item (id)
+            )
+            ",
+                r"
+            ALTER TABLE entry
+            ADD COLUMN schedule_id INT NULL,
+            ADD COLUMN meter INT NULL,
+            ADD FOREIGN KEY (schedule_id) REFERENCES schedule (id)
+            ",
+            ],
+            sqlite: &[
+                r"
+            CREATE TABLE IF NOT EXISTS schedule (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                item_id INT NOT NULL REFERENCES item (id),
+                title VARCHAR(30) NOT NULL,
+                kind VARCHAR(10) NOT NULL,
+                period INT NOT NULL,
+                grace INT NOT NULL DEFAULT 0,
+                meter_start INT NOT NULL DEFAULT 0,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP,
+                removed TINYINT NOT NULL DEFAULT 0
+            )
+            ",
+                "ALTER TABLE entry ADD COLUMN schedule_id INT REFERENCES schedule (id)",
+                "ALTER TABLE entry ADD COLUMN meter INT",
+            ],
+        },
+        Migration {
+            version: 8,
+            name: "add_entry_due_date",
+            mysql: &[r"
+            ALTER TABLE entry
+            ADD COLUMN due_date DATE NULL,
+            ADD COLUMN priority TINYINT NULL
+            "],
+            sqlite: &[
+                "ALTER TABLE entry ADD COLUMN due_date DATE",
+                "ALTER TABLE entry ADD COLUMN priority TINYINT",
+            ],
+        },
+        Migration {
+            version: 9,
+            name: "create_work_order",
+            mysql: &[
+                r"
+            CREATE TABLE IF NOT EXISTS work_order (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                item_id INT NOT NULL,
+                state VARCHAR(10) NOT NULL DEFAULT 'open',
+                problem TEXT NOT NULL,
+                assignee_id INT NULL,
+                resolution TEXT NULL,
+                opened DATETIME DEFAULT CURRENT_TIMESTAMP,
+                closed DATETIME NULL,
+                FOREIGN KEY (item_id) REFERENCES item (id),
+                FOREIGN KEY (assignee_id) REFERENCES user (id)
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS work_order_line (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                work_order_id INT NOT NULL,
+                kind VARCHAR(10) NOT NULL,
+                description VARCHAR(100) NOT NULL,
+                quantity INT NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (work_order_id) REFERENCES work_order (id)
+            )
+            ",
+                r"
+            ALTER TABLE entry
+            ADD COLUMN work_order_id INT NULL,
+            ADD FOREIGN KEY (work_order_id) REFERENCES work_order (id)
+            ",
+            ],
+            sqlite: &[
+                r"
+            CREATE TABLE IF NOT EXISTS work_order (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                item_id INT NOT NULL REFERENCES item (id),
+                state VARCHAR(10) NOT NULL DEFAULT 'open',
+                problem TEXT NOT NULL,
+                assignee_id INT REFERENCES user (id),
+                resolution TEXT,
+                opened DATETIME DEFAULT CURRENT_TIMESTAMP,
+                closed DATETIME
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS work_order_line (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                work_order_id INT NOT NULL REFERENCES work_order (id),
+                kind VARCHAR(10) NOT NULL,
+                description VARCHAR(100) NOT NULL,
+                quantity INT NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0,
+                created DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+                "ALTER TABLE entry ADD COLUMN work_order_id INT REFERENCES work_order (id)",
+            ],
+        },
+        Migration {
+            version: 10,
+            name: "add_item_assignee",
+            mysql: &[r"
+            ALTER TABLE item
+            ADD COLUMN assignee_id INT NULL,
+            ADD FOREIGN KEY (assignee_id) REFERENCES user (id)
+            "],
+            sqlite: &["ALTER TABLE item ADD COLUMN assignee_id INT REFERENCES user (id)"],
+        },
+        Migration {
+            version: 11,
+            name: "create_part",
+            mysql: &[
+                r"
+            CREATE TABLE IF NOT EXISTS part (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                part_number VARCHAR(30) NOT NULL UNIQUE,
+                description VARCHAR(100) NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0,
+                quantity INT NOT NULL DEFAULT 0,
+                reorder_point INT NOT NULL DEFAULT 0
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS entry_part (
+                id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                entry_id INT NOT NULL,
+                part_id INT NOT NULL,
+                quantity INT NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0,
+                FOREIGN KEY (entry_id) REFERENCES entry (id),
+                FOREIGN KEY (part_id) REFERENCES part (id)
+            )
+            ",
+                r"
+            ALTER TABLE work_order_line
+            ADD COLUMN part_id INT NULL,
+            ADD FOREIGN KEY (part_id) REFERENCES part (id)
+            ",
+            ],
+            sqlite: &[
+                r"
+            CREATE TABLE IF NOT EXISTS part (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                part_number VARCHAR(30) NOT NULL UNIQUE,
+                description VARCHAR(100) NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0,
+                quantity INT NOT NULL DEFAULT 0,
+                reorder_point INT NOT NULL DEFAULT 0
+            )
+            ",
+                r"
+            CREATE TABLE IF NOT EXISTS entry_part (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                entry_id INT NOT NULL REFERENCES entry (id),
+                part_id INT NOT NULL REFERENCES part (id),
+                quantity INT NOT NULL,
+                unit_cost INT NOT NULL DEFAULT 0
+            )
+            ",
+                "ALTER TABLE work_order_line ADD COLUMN part_id INT REFERENCES part (id)",
+            ],
+        },
+        Migration {
+            version: 12,
+            name: "add_entry_assignment",
+            // kept apart from the note, which is the item's fault description
+            mysql: &["ALTER TABLE entry ADD COLUMN assignment VARCHAR(50) NULL"],
+            sqlite: &["ALTER TABLE entry ADD COLUMN assignment VARCHAR(50)"],
+        },
+    ];
+
+    /// Columns read by the `FromRow` impls in `core::structs`.
+    pub const EXPECTED_COLUMNS: &[(&str, &[&str])] = &[
+        (TABLE_NAME_CATEGORY, &["id", "title", "removed"]),
+        (
+            TABLE_NAME_ITEM,
+            &["id", "title", "category_id", "assignee_id"],
+        ),
+        (
+            TABLE_NAME_ENTRY,
+            &[
+                "id",
+                "item_id",
+                "cost",
+                "note",
+                "status",
+                "visible",
+                "removed",
+                "user_id",
+                "author",
+                "date",
+                "schedule_id",
+                "meter",
+                "due_date",
+                "priority",
+                "work_order_id",
+                "assignment",
+            ],
+        ),
+        (
+            TABLE_NAME_USER,
+            &["id", "username", "password_hash", "role"],
+        ),
+        (
+            TABLE_NAME_API_TOKEN,
+            &[
+                "id",
+                "user_id",
+                "name",
+                "token_hash",
+                "scope",
+                "expires",
+                "last_used",
+                "created",
+                "revoked",
+            ],
+        ),
+        (
+            TABLE_NAME_SCHEDULE,
+            &[
+                "id",
+                "item_id",
+                "title",
+                "kind",
+                "period",
+                "grace",
+                "meter_start",
+                "created",
+            ],
+        ),
+        (
+            TABLE_NAME_WORK_ORDER,
+            &[
+                "id",
+                "item_id",
+                "state",
+                "problem",
+                "assignee_id",
+                "resolution",
+                "opened",
+                "closed",
+            ],
+        ),
+        (
+            TABLE_NAME_WORK_ORDER_LINE,
+            &[
+                "id",
+                "work_order_id",
+                "kind",
+                "description",
+                "quantity",
+                "unit_cost",
+                "created",
+                "part_id",
+            ],
+        ),
+        (
+            TABLE_NAME_PART,
+            &[
+                "id",
+                "part_number",
+                "description",
+                "unit_cost",
+                "quantity",
+                "reorder_point",
+            ],
+        ),
+        (
+            TABLE_NAME_ENTRY_PART,
+            &["id", "entry_id", "part_id", "quantity", "unit_cost"],
+        ),
+    ];
+
+    pub fn already_applied(e: &mysql::Error) -> bool {
+        /// Whether a MySQL statement failed only because what it creates is
+        /// already there: a table, column, index or foreign key. Each
+        /// statement is atomic, so this means an earlier run applied it.
+        matches!(
+            e,
+            mysql::Error::MySqlError(mysql::MySqlError {
+                code: 1050 | 1060 | 1061 | 1826,
+                ..
+            })
+        )
+    }
+
+    pub fn pending(store: &dyn Store) -> store::Result<Vec<&'static Migration>> {
+        /// Get the migrations that have not been applied yet.
+        let applied = store.applied_migrations()?;
+
+        Ok(MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .collect())
+    }
+
+    pub fn run(store: &dyn Store, dry_run: bool) -> store::Result<Vec<&'static Migration>> {
+        /// Apply all pending migrations in order and check the resulting schema.
+        /// With `dry_run`, nothing is changed and the pending list is returned.
+        let pending = pending(store)?;
+
+        if dry_run {
+            return Ok(pending);
+        }
+
+        for migration in pending.iter() {
+            tide::log::info!(
+                "Applying migration {:03} ({})",
+                migration.version,
+                migration.name
+            );
+            store.apply_migration(migration)?;
+        }
+
+        verify(store)?;
+
+        Ok(pending)
+    }
+
+    pub fn verify(store: &dyn Store) -> store::Result<()> {
+        /// Make sure every column the application reads actually exists.
+        for (table_name, columns) in EXPECTED_COLUMNS {
+            store.check_columns(table_name, columns).map_err(|e| {
+                AppError::Schema(format!(
+                    "Table \"{}\" does not match the expected schema: {}",
+                    table_name, e
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+pub mod archive {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::store::{self, Store};
+    use crate::core::errors::AppError;
+    use crate::core::structs::*;
+    use crate::data::constants::APP_VERSION;
+
+    pub fn export(store: &dyn Store) -> store::Result<Archive> {
+        /// Collect every category, part, item and entry, removed ones included.
+        let mut items = vec![];
+
+        for (id, item) in store.collect_items()? {
+            let mut entries = store.collect_item_entries(id)?;
+            entries.sort_by_key(|entry| entry.id);
+
+            let filter = WorkOrderFilter {
+                item_id: Some(id),
+                ..WorkOrderFilter::default()
+            };
+            let work_orders = store
+                .collect_work_orders(&filter)?
+                .into_iter()
+                .map(|order| store.get_work_order(order.id.unwrap_or_default()))
+                .collect::<store::Result<Vec<WorkOrder>>>()?;
+
+            items.push(ArchivedItem {
+                item,
+                schedules: store.collect_schedules(Some(id))?,
+                work_orders,
+                entries,
+            });
+        }
+
+        // in id order, so an imported database exports the same way
+        let mut categories = store.collect_categories()?;
+        categories.sort_by_key(|category| category.id);
+        let mut parts = store.collect_parts()?;
+        parts.sort_by_key(|part| part.id);
+
+        Ok(Archive {
+            version: APP_VERSION.to_owned(),
+            categories,
+            parts,
+            items,
+        })
+    }
+
+    pub fn check(archive: &Archive) -> store::Result<()> {
+        /// Validate every record of an archive and every reference between
+        /// them, so a bad archive is refused before anything is written.
+        let mut category_ids = BTreeSet::new();
+        for category in &archive.categories {
+            let id = category.id.ok_or_else(|| {
+                AppError::Validation(format!("Category \"{}\" has no id.", category.title))
+            })?;
+            category.validate()?;
+            category_ids.insert(id);
+        }
+
+        let mut part_ids = BTreeSet::new();
+        let mut part_numbers = BTreeSet::new();
+        for part in &archive.parts {
+            let id = part.id.ok_or_else(|| {
+                AppError::Validation(format!("Part \"{}\" has no id.", part.part_number))
+            })?;
+            part.validate()?;
+            if !part_numbers.insert(&part.part_number) {
+                return Err(AppError::Validation(format!(
+                    "Part number \"{}\" appears more than once.",
+                    part.part_number
+                )));
+            }
+            part_ids.insert(id);
+        }
+        let check_part = |part_id: u32| match part_ids.contains(&part_id) {
+            true => Ok(()),
+            false => Err(AppError::Validation(format!(
+                "Part {} is used but not in the archive.",
+                part_id
+            ))),
+        };
+
+        for archived in &archive.items {
+            let item = &archived.item;
+            if !category_ids.contains(&item.category_id) {
+                return Err(AppError::Validation(format!(
+                    "Item \"{}\" belongs to category {}, which is not in the archive.",
+                    item.title, item.category_id
+                )));
+            }
+
+            // old ids mapped to themselves, to check entries the way
+            // `Store::import` translates them
+            let mut schedule_ids = BTreeMap::new();
+            for schedule in &archived.schedules {
+                schedule.validate()?;
+                if let Some(id) = schedule.id {
+                    schedule_ids.insert(id, Some(id));
+                }
+            }
+
+            let mut work_order_ids = BTreeMap::new();
+            for order in &archived.work_orders {
+                order.validate()?;
+                for line in &order.lines {
+                    line.part_id.map(check_part).transpose()?;
+                }
+                if let Some(id) = order.id {
+                    work_order_ids.insert(id, Some(id));
+                }
+            }
+
+            for entry in &archived.entries {
+                store::imported_id(entry, entry.schedule_id, &schedule_ids, "schedule")?;
+                store::imported_id(entry, entry.work_order_id, &work_order_ids, "work order")?;
+                for part in &entry.parts {
+                    check_part(part.part_id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn import(store: &dyn Store, archive: &Archive) -> store::Result<()> {
+        /// Load an archive into an empty database. The archive is checked
+        /// first and written in one transaction, so a bad one leaves the
+        /// database empty for the next attempt.
+        check(archive)?;
+
+        store.import(archive)
+    }
+}
+
+pub mod sqlite {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    use rusqlite::{named_params, Connection, OptionalExtension};
+
+    use super::migrations::{self, Migration};
+    use super::store::{self, Store};
+    use crate::core::errors::AppError;
+    use crate::core::structs::*;
+    use crate::data::constants::{TABLE_NAME_CATEGORY, TABLE_NAME_ITEM};
+
+    #[derive(Debug)]
+    pub struct SqliteStore {
+        /// Store backed by a local SQLite file.
+        conn: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: &str) -> store::Result<Self> {
+            /// Open (or create) a SQLite database file.
+            Self::from_connection(Connection::open(path)?)
+        }
+
+        pub fn open_in_memory() -> store::Result<Self> {
+            /// Open a throwaway in-memory database.
+            Self::from_connection(Connection::open_in_memory()?)
+        }
+
+        fn from_connection(conn: Connection) -> store::Result<Self> {
+            // SQLite only enforces `REFERENCES` when asked to
+            conn.pragma_update(None, "foreign_keys", true)?;
+
+            Ok(SqliteStore {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+            self.conn.lock().unwrap()
+        }
+
+        #[cfg(test)]
+        pub fn vm_steps(&self, sql: &str) -> i32 {
+            /// How many steps SQLite's virtual machine takes to run a query
+            /// to the end, with every parameter left `NULL`. Unlike timings,
+            /// the count is the same on every run.
+            let conn = self.conn();
+            let mut stmt = conn.prepare(sql).unwrap();
+            let nulls = vec![rusqlite::types::Null; stmt.parameter_count()];
+            let mut rows = stmt.query(rusqlite::params_from_iter(nulls)).unwrap();
+            while rows.next().unwrap().is_some() {}
+            drop(rows);
+
+            stmt.get_status(rusqlite::StatementStatus::VmStep)
+        }
+
+        #[cfg(test)]
+        pub fn query_plan(&self, sql: &str) -> Vec<String> {
+            /// The steps SQLite plans for a query, with every parameter
+            /// left `NULL`.
+            let conn = self.conn();
+            let mut stmt = conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+                .unwrap();
+            let nulls = vec![rusqlite::types::Null; stmt.parameter_count()];
+            stmt.query_map(rusqlite::params_from_iter(nulls), |row| {
+                row.get::<_, String>("detail")
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap()
+        }
+    }
+
+    fn consume_part(conn: &Connection, part_id: u32, quantity: u32) -> store::Result<u32> {
+        /// Take parts out of stock, returning their unit cost. Fails
+        /// rather than leaving fewer than none on hand.
+        let part = conn
+            .query_row(
+                "SELECT * FROM part WHERE id = :id",
+                named_params! { ":id": part_id },
+                |row| Part::try_from(row),
+            )
+            .optional()?
+            .ok_or_else(|| store::unknown_part(part_id))?;
+
+        let consumed = conn.execute(
+            store::CONSUME_PART,
+            named_params! { ":id": part_id, ":quantity": quantity },
+        )?;
+        if consumed == 0 {
+            return Err(store::short_of_part(&part, quantity));
+        }
+
+        Ok(part.unit_cost)
+    }
+
+    fn insert_entry_parts(
+        conn: &Connection,
+        entry_id: u32,
+        parts: &[PartUse],
+    ) -> store::Result<()> {
+        /// Record the parts an entry used.
+        for part in parts {
+            conn.execute(
+                r"
+                INSERT INTO entry_part (entry_id, part_id, quantity, unit_cost)
+                VALUES (:entry_id, :part_id, :quantity, :unit_cost)
+                ",
+                named_params! {
+                    ":entry_id": entry_id,
+                    ":part_id": part.part_id,
+                    ":quantity": part.quantity,
+                    ":unit_cost": part.unit_cost,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn collect_entry_parts(
+        conn: &Connection,
+        item_id: Option<u32>,
+        entry_id: Option<u32>,
+    ) -> store::Result<Vec<(u32, PartUse)>> {
+        /// Get the parts used by an item's entries, or by one entry.
+        let mut stmt = conn.prepare(store::ENTRY_PARTS_QUERY)?;
+        let parts = stmt
+            .query_map(
+                named_params! { ":item_id": item_id, ":entry_id": entry_id },
+                |row| Ok((row.get("entry_id")?, PartUse::try_from(row)?)),
+            )?
+            .collect::<rusqlite::Result<Vec<(u32, PartUse)>>>()?;
+
+        Ok(parts)
+    }
+
+    fn insert_entry(conn: &Connection, item: &Item, author: &Author) -> store::Result<u32> {
+        /// Insert an entry for an item, taking the parts it used out of
+        /// stock and adding them to its cost. Returns the entry's id.
+        let details = item
+            .details
+            .as_ref()
+            .ok_or_else(|| AppError::Validation("Missing item details.".to_owned()))?;
+
+        if let Some(schedule_id) = details.schedule_id {
+            let owned: Option<u32> = conn
+                .query_row(
+                    r"
+                    SELECT id FROM schedule
+                    WHERE id = :id AND item_id = :item_id AND removed = 0
+                    ",
+                    named_params! { ":id": schedule_id, ":item_id": item.id },
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if owned.is_none() {
+                return Err(store::foreign_schedule(schedule_id, item));
+            }
+        }
+
+        let mut cost = details.cost;
+        let mut parts = details.parts.clone();
+        for part in parts.iter_mut() {
+            part.unit_cost = consume_part(conn, part.part_id, part.quantity)?;
+            cost = Some(store::add_part_cost(cost, part)?);
+        }
+
+        conn.execute(
+            r"
+            INSERT INTO entry (
+                item_id, cost, note, status, visible, removed, user_id, author, schedule_id, meter,
+                due_date, priority, work_order_id, assignment
+            )
+            VALUES (
+                :item_id, :cost, :note, :status, :visible, :removed, :user_id, :author,
+                :schedule_id, :meter, :due_date, :priority, :work_order_id, :assignment
+            )
+            ",
+            named_params! {
+                ":item_id": item.id,
+                ":cost": cost,
+                ":note": details.note,
+                ":status": details.status,
+                ":visible": details.visible,
+                ":removed": details.removed,
+                ":user_id": author.user_id,
+                ":author": author.name,
+                ":schedule_id": details.schedule_id,
+                ":meter": details.meter,
+                ":due_date": details.due_date,
+                ":priority": details.priority,
+                ":work_order_id": details.work_order_id,
+                ":assignment": details.assignment,
+            },
+        )?;
+
+        let entry_id = conn.last_insert_rowid() as u32;
+        insert_entry_parts(conn, entry_id, &parts)?;
+
+        Ok(entry_id)
+    }
+
+    fn import_work_order(conn: &Connection, order: &mut WorkOrder) -> store::Result<()> {
+        /// Insert an exported work order with its lines, assigned to the
+        /// user of the same name if there is one.
+        conn.execute(
+            r"
+            INSERT INTO work_order (
+                item_id, state, problem, assignee_id, resolution, opened, closed
+            )
+            VALUES (
+                :item_id,
+                :state,
+                :problem,
+                (SELECT id FROM user WHERE username = :assignee),
+                :resolution,
+                COALESCE(:opened, datetime('now')),
+                :closed
+            )
+            ",
+            named_params! {
+                ":item_id": order.item_id,
+                ":state": order.state.as_str(),
+                ":problem": order.problem,
+                ":assignee": order.assignee,
+                ":resolution": order.resolution,
+                ":opened": order.opened,
+                ":closed": order.closed,
+            },
+        )?;
+
+        order.id = Some(conn.last_insert_rowid() as u32);
+
+        for line in order.lines.iter_mut() {
+            line.work_order_id = order.id.unwrap_or_default();
+            conn.execute(
+                r"
+                INSERT INTO work_order_line (
+                    work_order_id, kind, part_id, description, quantity, unit_cost, created
+                )
+                VALUES (
+                    :work_order_id, :kind, :part_id, :description, :quantity, :unit_cost,
+                    COALESCE(:created, datetime('now'))
+                )
+                ",
+                named_params! {
+                    ":work_order_id": line.work_order_id,
+                    ":kind": line.kind.as_str(),
+                    ":part_id": line.part_id,
+                    ":description": line.description,
+                    ":quantity": line.quantity,
+                    ":unit_cost": line.unit_cost,
+                    ":created": line.created,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn insert_schedule(conn: &Connection, schedule: &mut Schedule) -> store::Result<()> {
+        /// Insert a schedule for an item.
+        conn.execute(
+            r"
+            INSERT INTO schedule (item_id, title, kind, period, grace, meter_start)
+            VALUES (:item_id, :title, :kind, :period, :grace, :meter_start)
+            ",
+            named_params! {
+                ":item_id": schedule.item_id,
+                ":title": schedule.title,
+                ":kind": schedule.kind.as_str(),
+                ":period": schedule.period,
+                ":grace": schedule.grace,
+                ":meter_start": schedule.meter_start,
+            },
+        )?;
+
+        schedule.id = Some(conn.last_insert_rowid() as u32);
+
+        Ok(())
+    }
+
+    fn update_item(conn: &Connection, item: &Item, author: &Author) -> store::Result<()> {
+        /// Update an item and record a new entry for it.
+        let updated = conn.execute(
+            r"
+            UPDATE item
+            SET title = :title,
+            category_id = :category_id
+            WHERE id = :id
+            ",
+            named_params! {
+                ":id": item.id,
+                ":title": item.title,
+                ":category_id": item.category_id,
+            },
+        )?;
+
+        if updated == 0 {
+            return Err(AppError::NotFound(format!(
+                "No item with id {}.",
+                item.id.unwrap_or_default()
+            )));
+        }
+
+        // create a new entry with updated information
+        insert_entry(conn, item, author)?;
+
+        Ok(())
+    }
+
+    fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<(u32, Item)> {
+        /// Convert a row of `store::COLLECT_ITEMS_QUERY` into an item and
+        /// its id.
+        let mut item = Item::try_from(row)?;
+
+        item.details = Some(match row.get::<_, Option<u32>>("entry_id")? {
+            Some(_) => ItemDetails::try_from(row)?,
+            None => ItemDetails::new(),
+        });
+
+        Ok((item.id.unwrap_or_default(), item))
+    }
+
+    fn latest_details(conn: &Connection, item_id: u32) -> rusqlite::Result<Option<ItemDetails>> {
+        /// Get the details from the most recent entry for an item.
+        conn.query_row(
+            r"
+            SELECT cost, note, status, visible, removed, due_date, priority
+            FROM entry WHERE item_id = :item_id ORDER BY id DESC LIMIT 1
+            ",
+            named_params! { ":item_id": item_id },
+            |row| ItemDetails::try_from(row),
+        )
+        .optional()
+    }
+
+    fn import_item(tx: &Connection, archived: &mut ArchivedItem) -> store::Result<()> {
+        /// Insert an exported item with its schedules and entries, keeping
+        /// the entries' dates and author names.
+        tx.execute(
+            store::IMPORT_ITEM,
+            named_params! {
+                ":title": archived.item.title,
+                ":category_id": archived.item.category_id,
+                ":assignee": archived.item.assignee,
+            },
+        )?;
+
+        let item_id = tx.last_insert_rowid() as u32;
+        archived.item.id = Some(item_id);
+
+        let mut schedule_ids = BTreeMap::new();
+        for schedule in archived.schedules.iter_mut() {
+            let old_id = schedule.id;
+            schedule.item_id = item_id;
+            insert_schedule(tx, schedule)?;
+
+            if let Some(old_id) = old_id {
+                schedule_ids.insert(old_id, schedule.id);
+            }
+        }
+
+        let mut work_order_ids = BTreeMap::new();
+        for order in archived.work_orders.iter_mut() {
+            let old_id = order.id;
+            order.item_id = item_id;
+            import_work_order(tx, order)?;
+
+            if let Some(old_id) = old_id {
+                work_order_ids.insert(old_id, order.id);
+            }
+        }
+
+        for entry in archived.entries.iter() {
+            tx.execute(
+                r"
+                INSERT INTO entry (
+                    item_id, cost, note, status, visible, removed, author, date,
+                    schedule_id, meter, due_date, priority, work_order_id, assignment
+                )
+                VALUES (
+                    :item_id, :cost, :note, :status, :visible, :removed, :author,
+                    COALESCE(:date, datetime('now')), :schedule_id, :meter, :due_date,
+                    :priority, :work_order_id, :assignment
+                )
+                ",
+                named_params! {
+                    ":item_id": item_id,
+                    ":cost": entry.cost,
+                    ":note": entry.note,
+                    ":status": entry.status,
+                    ":visible": entry.visible,
+                    ":removed": entry.removed,
+                    ":author": entry.author,
+                    ":date": entry.date,
+                    ":schedule_id": store::imported_id(
+                        entry,
+                        entry.schedule_id,
+                        &schedule_ids,
+                        "schedule"
+                    )?,
+                    ":meter": entry.meter,
+                    ":due_date": entry.due_date,
+                    ":priority": entry.priority,
+                    ":work_order_id": store::imported_id(
+                        entry,
+                        entry.work_order_id,
+                        &work_order_ids,
+                        "work order"
+                    )?,
+                    ":assignment": entry.assignment,
+                },
+            )?;
+
+            insert_entry_parts(tx, tx.last_insert_rowid() as u32, &entry.parts)?;
+        }
+
+        Ok(())
+    }
+
+    impl Store for SqliteStore {
+        fn assign_item(&self, item: &Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            tx.execute(
+                "UPDATE item SET assignee_id = :assignee_id WHERE id = :id",
+                named_params! {
+                    ":id": item.id,
+                    ":assignee_id": item.assignee_id,
+                },
+            )?;
+            insert_entry(&tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::Sqlite
+        }
+
+        fn applied_migrations(&self) -> store::Result<Vec<u32>> {
+            let conn = self.conn();
+            conn.execute_batch(migrations::SQLITE_MIGRATIONS_TABLE)?;
+
+            let mut stmt =
+                conn.prepare("SELECT version FROM schema_migrations ORDER BY version")?;
+            let versions = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<u32>>>()?;
+
+            Ok(versions)
+        }
+
+        fn apply_migration(&self, migration: &Migration) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            for statement in migration.sqlite {
+                tx.execute_batch(statement)?;
+            }
+
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+                named_params! {
+                    ":version": migration.version,
+                    ":name": migration.name,
+                },
+            )?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn check_columns(&self, table_name: &str, columns: &[&str]) -> store::Result<()> {
+            self.conn().prepare(&format!(
+                "SELECT {} FROM {} LIMIT 0",
+                columns.join(", "),
+                store::checked_table_name(table_name)?
+            ))?;
+
+            Ok(())
+        }
+
+        fn collect_api_tokens(&self, user_id: u32) -> store::Result<Vec<ApiToken>> {
+            let conn = self.conn();
+            let mut stmt =
+                conn.prepare("SELECT * FROM api_token WHERE user_id = :user_id ORDER BY id")?;
+            let tokens = stmt
+                .query_map(named_params! { ":user_id": user_id }, |row| {
+                    ApiToken::try_from(row)
+                })?
+                .collect::<rusqlite::Result<Vec<ApiToken>>>()?;
+
+            Ok(tokens)
+        }
+
+        fn collect_categories(&self) -> store::Result<Vec<Category>> {
+            let conn = self.conn();
+            let mut stmt = conn.prepare("SELECT * FROM category ORDER BY title")?;
+            let categories = stmt
+                .query_map([], |row| Category::try_from(row))?
+                .collect::<rusqlite::Result<Vec<Category>>>()?;
+
+            Ok(categories)
+        }
+
+        fn collect_items(&self) -> store::Result<BTreeMap<u32, Item>> {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(store::COLLECT_ITEMS_QUERY)?;
+            let items = stmt
+                .query_map([], item_from_row)?
+                .collect::<rusqlite::Result<BTreeMap<u32, Item>>>()?;
+
+            Ok(items)
+        }
+
+        fn count_admins(&self) -> store::Result<u32> {
+            Ok(self.conn().query_row(
+                "SELECT COUNT(*) FROM user WHERE role = :role",
+                named_params! { ":role": Role::Admin.as_str() },
+                |row| row.get(0),
+            )?)
+        }
+
+        fn collect_item_entries(&self, item_id: u32) -> store::Result<Vec<Entry>> {
+            let conn = self.conn();
+            let mut stmt =
+                conn.prepare("SELECT * FROM entry WHERE item_id = :item_id ORDER BY date, id")?;
+            let mut entries = stmt
+                .query_map(named_params! { ":item_id": item_id }, |row| {
+                    Entry::try_from(row)
+                })?
+                .collect::<rusqlite::Result<Vec<Entry>>>()?;
+            store::attach_parts(
+                &mut entries,
+                collect_entry_parts(&conn, Some(item_id), None)?,
+            );
+
+            Ok(entries)
+        }
+
+        fn collect_parts(&self) -> store::Result<Vec<Part>> {
+            let conn = self.conn();
+            let mut stmt = conn.prepare("SELECT * FROM part ORDER BY part_number")?;
+            let parts = stmt
+                .query_map([], |row| Part::try_from(row))?
+                .collect::<rusqlite::Result<Vec<Part>>>()?;
+
+            Ok(parts)
+        }
+
+        fn collect_schedules(&self, item_id: Option<u32>) -> store::Result<Vec<Schedule>> {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(
+                r"
+                SELECT * FROM schedule
+                WHERE removed = 0 AND (:item_id IS NULL OR item_id = :item_id)
+                ORDER BY id
+                ",
+            )?;
+            let schedules = stmt
+                .query_map(named_params! { ":item_id": item_id }, |row| {
+                    Schedule::try_from(row)
+                })?
+                .collect::<rusqlite::Result<Vec<Schedule>>>()?;
+
+            Ok(schedules)
+        }
+
+        fn collect_work_orders(&self, filter: &WorkOrderFilter) -> store::Result<Vec<WorkOrder>> {
+            let conn = self.conn();
+            let mut stmt = conn.prepare(&format!(
+                "{} {}",
+                store::WORK_ORDER_QUERY,
+                store::WORK_ORDER_FILTER
+            ))?;
+            let orders = stmt
+                .query_map(
+                    named_params! {
+                        ":state": filter.state.map(|state| state.as_str()),
+                        ":item_id": filter.item_id,
+                        ":category_id": filter.category_id,
+                        ":assignee_id": filter.assignee_id,
+                    },
+                    |row| WorkOrder::try_from(row),
+                )?
+                .collect::<rusqlite::Result<Vec<WorkOrder>>>()?;
+
+            Ok(orders)
+        }
+
+        fn delete_category(&self, id: u32) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE category SET removed = 1 WHERE id = :id",
+                named_params! { ":id": id },
+            )?;
+
+            Ok(())
+        }
+
+        fn delete_item(&self, item_id: u32) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE entry SET removed = 1 WHERE item_id = :item_id",
+                named_params! { ":item_id": item_id },
+            )?;
+
+            Ok(())
+        }
+
+        fn find_api_token(&self, token_hash: &str) -> store::Result<ApiToken> {
+            self.conn()
+                .query_row(
+                    r"
+                    SELECT * FROM api_token
+                    WHERE token_hash = :token_hash AND revoked = 0
+                    AND (expires IS NULL OR expires > datetime('now'))
+                    ",
+                    named_params! { ":token_hash": token_hash },
+                    |row| ApiToken::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound("No such API token.".to_owned()))
+        }
+
+        fn get_api_token(&self, id: u32) -> store::Result<ApiToken> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM api_token WHERE id = :id",
+                    named_params! { ":id": id },
+                    |row| ApiToken::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No API token with id {}.", id)))
+        }
+
+        fn get_category(&self, id: u32) -> store::Result<Category> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM category WHERE id = :id",
+                    named_params! { ":id": id },
+                    |row| Category::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No category with id {}.", id)))
+        }
+
+        fn get_entry(&self, item_id: u32) -> store::Result<Entry> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM entry WHERE item_id = :item_id ORDER BY id DESC LIMIT 1",
+                    named_params! { ":item_id": item_id },
+                    |row| Entry::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No entries with item_id {}.", item_id)))
+        }
+
+        fn get_entry_by_id(&self, id: u32) -> store::Result<Entry> {
+            let conn = self.conn();
+            let mut entry = conn
+                .query_row(
+                    "SELECT * FROM entry WHERE id = :id",
+                    named_params! { ":id": id },
+                    |row| Entry::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No entry with id {}.", id)))?;
+            store::attach_parts(
+                std::slice::from_mut(&mut entry),
+                collect_entry_parts(&conn, None, Some(id))?,
+            );
+
+            Ok(entry)
+        }
+
+        fn get_item(&self, id: u32) -> store::Result<Item> {
+            let conn = self.conn();
+            let mut item = conn
+                .query_row(store::ITEM_QUERY, named_params! { ":id": id }, |row| {
+                    Item::try_from(row)
+                })
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No item with id {}.", id)))?;
+            item.details = Some(latest_details(&conn, id)?.unwrap_or_else(ItemDetails::new));
+
+            Ok(item)
+        }
+
+        fn get_part(&self, id: u32) -> store::Result<Part> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM part WHERE id = :id",
+                    named_params! { ":id": id },
+                    |row| Part::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No part with id {}.", id)))
+        }
+
+        fn get_schedule(&self, id: u32) -> store::Result<Schedule> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM schedule WHERE id = :id AND removed = 0",
+                    named_params! { ":id": id },
+                    |row| Schedule::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No schedule with id {}.", id)))
+        }
+
+        fn get_user(&self, id: u32) -> store::Result<User> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM user WHERE id = :id",
+                    named_params! { ":id": id },
+                    |row| User::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No user with id {}.", id)))
+        }
+
+        fn get_user_by_name(&self, username: &str) -> store::Result<User> {
+            self.conn()
+                .query_row(
+                    "SELECT * FROM user WHERE username = :username",
+                    named_params! { ":username": username },
+                    |row| User::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No user named \"{}\".", username)))
+        }
+
+        fn get_work_order(&self, id: u32) -> store::Result<WorkOrder> {
+            let conn = self.conn();
+            let mut order = conn
+                .query_row(
+                    &format!("{} WHERE work_order.id = :id", store::WORK_ORDER_QUERY),
+                    named_params! { ":id": id },
+                    |row| WorkOrder::try_from(row),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("No work order with id {}.", id)))?;
+
+            let mut stmt = conn.prepare(store::WORK_ORDER_LINES_QUERY)?;
+            order.lines = stmt
+                .query_map(named_params! { ":work_order_id": id }, |row| {
+                    WorkOrderLine::try_from(row)
+                })?
+                .collect::<rusqlite::Result<Vec<WorkOrderLine>>>()?;
+
+            Ok(order)
+        }
+
+        fn insert_api_token(
+            &self,
+            token: &mut ApiToken,
+            token_hash: &str,
+            expires_in_days: Option<u32>,
+        ) -> store::Result<()> {
+            let conn = self.conn();
+            conn.execute(
+                r"
+                INSERT INTO api_token (user_id, name, token_hash, scope, expires)
+                VALUES (
+                    :user_id,
+                    :name,
+                    :token_hash,
+                    :scope,
+                    datetime('now', '+' || :expires_in_days || ' days')
+                )
+                ",
+                named_params! {
+                    ":user_id": token.user_id,
+                    ":name": token.name,
+                    ":token_hash": token_hash,
+                    ":scope": token.scope.map(|scope| scope.as_str()),
+                    ":expires_in_days": expires_in_days,
+                },
+            )?;
+
+            token.id = Some(conn.last_insert_rowid() as u32);
+
+            Ok(())
+        }
+
+        fn import(&self, archive: &Archive) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            let used: bool = tx.query_row(store::ARCHIVED_TABLES_USED, [], |row| row.get(0))?;
+            if used {
+                return Err(store::database_not_empty());
+            }
+
+            let mut category_ids = BTreeMap::new();
+            for category in &archive.categories {
+                tx.execute(
+                    store::IMPORT_CATEGORY,
+                    named_params! {
+                        ":title": category.title,
+                        ":removed": category.removed,
+                    },
+                )?;
+                category_ids.insert(
+                    category.id.unwrap_or_default(),
+                    tx.last_insert_rowid() as u32,
+                );
+            }
+
+            let mut part_ids = BTreeMap::new();
+            for part in &archive.parts {
+                tx.execute(
+                    store::IMPORT_PART,
+                    named_params! {
+                        ":part_number": part.part_number,
+                        ":description": part.description,
+                        ":unit_cost": part.unit_cost,
+                        ":quantity": part.quantity,
+                        ":reorder_point": part.reorder_point,
+                    },
+                )?;
+                part_ids.insert(part.id.unwrap_or_default(), tx.last_insert_rowid() as u32);
+            }
+
+            for archived in &archive.items {
+                let mut archived = store::imported_item(archived, &category_ids, &part_ids)?;
+                import_item(&tx, &mut archived)?;
+            }
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_category(&self, title: &str) -> store::Result<u32> {
+            let conn = self.conn();
+            conn.execute(
+                "INSERT INTO category (title, removed) VALUES (:title, 0)",
+                named_params! { ":title": title },
+            )?;
+
+            Ok(conn.last_insert_rowid() as u32)
+        }
+
+        fn insert_entry(&self, item: &Item, author: &Author) -> store::Result<u32> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            let id = insert_entry(&tx, item, author)?;
+            tx.commit()?;
+
+            Ok(id)
+        }
+
+        fn insert_item(&self, item: &mut Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            tx.execute(
+                "INSERT INTO item (title, category_id) VALUES (:title, :category_id)",
+                named_params! {
+                    ":title": item.title,
+                    ":category_id": item.category_id,
+                },
+            )?;
+
+            item.id = Some(tx.last_insert_rowid() as u32);
+            insert_entry(&tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_part(&self, part: &mut Part) -> store::Result<()> {
+            let conn = self.conn();
+            conn.execute(
+                r"
+                INSERT INTO part (part_number, description, unit_cost, quantity, reorder_point)
+                VALUES (:part_number, :description, :unit_cost, :quantity, :reorder_point)
+                ",
+                named_params! {
+                    ":part_number": part.part_number,
+                    ":description": part.description,
+                    ":unit_cost": part.unit_cost,
+                    ":quantity": part.quantity,
+                    ":reorder_point": part.reorder_point,
+                },
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE =>
+                {
+                    store::part_number_taken(part)
+                }
+                e => e.into(),
+            })?;
+
+            part.id = Some(conn.last_insert_rowid() as u32);
+
+            Ok(())
+        }
+
+        fn insert_schedule(&self, schedule: &mut Schedule) -> store::Result<()> {
+            insert_schedule(&self.conn(), schedule)
+        }
+
+        fn insert_user(&self, user: &mut User) -> store::Result<()> {
+            let conn = self.conn();
+            conn.execute(
+                r"
+                INSERT INTO user (username, password_hash, role)
+                VALUES (:username, :password_hash, :role)
+                ",
+                named_params! {
+                    ":username": user.username,
+                    ":password_hash": user.password_hash,
+                    ":role": user.role.as_str(),
+                },
+            )?;
+
+            user.id = Some(conn.last_insert_rowid() as u32);
+
+            Ok(())
+        }
+
+        fn insert_work_order(
+            &self,
+            order: &mut WorkOrder,
+            item: &Item,
+            author: &Author,
+        ) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            tx.execute(
+                r"
+                INSERT INTO work_order (item_id, state, problem, assignee_id)
+                VALUES (:item_id, :state, :problem, :assignee_id)
+                ",
+                named_params! {
+                    ":item_id": order.item_id,
+                    ":state": order.state.as_str(),
+                    ":problem": order.problem,
+                    ":assignee_id": order.assignee_id,
+                },
+            )?;
+
+            order.id = Some(tx.last_insert_rowid() as u32);
+            insert_entry(&tx, &store::work_order_entry(order, item), author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn insert_work_order_line(&self, line: &mut WorkOrderLine) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            if let Some(part_id) = line.part_id {
+                line.unit_cost = consume_part(&tx, part_id, line.quantity)?;
+            }
+
+            tx.execute(
+                r"
+                INSERT INTO work_order_line (
+                    work_order_id, kind, part_id, description, quantity, unit_cost
+                )
+                VALUES (:work_order_id, :kind, :part_id, :description, :quantity, :unit_cost)
+                ",
+                named_params! {
+                    ":work_order_id": line.work_order_id,
+                    ":kind": line.kind.as_str(),
+                    ":part_id": line.part_id,
+                    ":description": line.description,
+                    ":quantity": line.quantity,
+                    ":unit_cost": line.unit_cost,
+                },
+            )?;
+
+            line.id = Some(tx.last_insert_rowid() as u32);
+
+            Ok(tx.commit()?)
+        }
+
+        fn now(&self) -> store::Result<String> {
+            Ok(self
+                .conn()
+                .query_row("SELECT datetime('now')", [], |row| row.get(0))?)
+        }
+
+        fn remove_schedule(&self, id: u32) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE schedule SET removed = 1 WHERE id = :id",
+                named_params! { ":id": id },
+            )?;
+
+            Ok(())
+        }
+
+        fn revoke_api_token(&self, id: u32) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE api_token SET revoked = 1 WHERE id = :id",
+                named_params! { ":id": id },
+            )?;
+
+            Ok(())
+        }
+
+        fn title_taken(&self, title: &str, table_name: &str) -> store::Result<bool> {
+            let taken: Option<u8> = self
+                .conn()
+                .query_row(
+                    store::title_taken_query(table_name)?,
+                    named_params! { ":title": title },
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            Ok(taken.is_some())
+        }
+
+        fn touch_api_token(&self, id: u32) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE api_token SET last_used = datetime('now') WHERE id = :id",
+                named_params! { ":id": id },
+            )?;
+
+            Ok(())
+        }
+
+        fn update_category(&self, category: &Category) -> store::Result<()> {
+            self.conn().execute(
+                "UPDATE category SET title = :title, removed = :removed WHERE id = :id",
+                named_params! {
+                    ":id": category.id,
+                    ":title": category.title,
+                    ":removed": category.removed,
+                },
+            )?;
+
+            Ok(())
+        }
+
+        fn update_entry(&self, entry: &Entry) -> store::Result<()> {
+            self.conn().execute(
+                r"
+                UPDATE entry
+                SET cost = :cost, note = :note, status = :status,
+                visible = :visible, removed = :removed, due_date = :due_date,
+                priority = :priority
+                WHERE id = :id
+                ",
+                named_params! {
+                    ":id": entry.id,
+                    ":cost": entry.cost,
+                    ":note": entry.note,
+                    ":status": entry.status.unwrap_or(0),
+                    ":visible": entry.visible,
+                    ":removed": entry.removed,
+                    ":due_date": entry.due_date,
+                    ":priority": entry.priority,
+                },
+            )?;
+
+            Ok(())
+        }
+
+        fn update_item(&self, item: &Item, author: &Author) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            update_item(&tx, item, author)?;
+
+            Ok(tx.commit()?)
+        }
+
+        fn update_items(
+            &self,
+            items: &[Item],
+            author: &Author,
+            check: &dyn Fn(&Item, &Item) -> store::Result<()>,
+        ) -> store::Result<SaveReport> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            let ids = store::item_ids(items);
+            if !ids.is_empty() {
+                let current = tx
+                    .prepare(&store::items_by_id_query(ids.len()))?
+                    .query_map(rusqlite::params_from_iter(&ids), item_from_row)?
+                    .collect::<rusqlite::Result<BTreeMap<u32, Item>>>()?;
+                store::check_all(&current, items, check)?;
+            }
+
+            let report = store::save_all(items, |item| update_item(&tx, item, author));
+
+            if report.saved {
+                tx.commit()?;
+            } else {
+                tx.rollback()?;
+            }
+
+            Ok(report)
+        }
+
+        fn update_part(&self, part: &Part) -> store::Result<()> {
+            self.conn().execute(
+                r"
+                UPDATE part
+                SET description = :description, unit_cost = :unit_cost,
+                quantity = :quantity, reorder_point = :reorder_point
+                WHERE id = :id
+                ",
+                named_params! {
+                    ":id": part.id,
+                    ":description": part.description,
+                    ":unit_cost": part.unit_cost,
+                    ":quantity": part.quantity,
+                    ":reorder_point": part.reorder_point,
+                },
+            )?;
+
+            Ok(())
+        }
+
+        fn update_work_order(
+            &self,
+            order: &WorkOrder,
+            item: &Item,
+            author: &Author,
+        ) -> store::Result<()> {
+            let mut conn = self.conn();
+            let tx = conn.transaction()?;
+
+            tx.execute(
+                store::UPDATE_WORK_ORDER,
+                named_params! {
+                    ":id": order.id,
+                    ":state": order.state.as_str(),
+                    ":assignee_id": order.assignee_id,
+                    ":resolution": order.resolution,
+                },
+            )?;
+            insert_entry(&tx, &store::work_order_entry(order, item), author)?;
+
+            Ok(tx.commit()?)
+        }
+    }
+}
+
+pub mod store {
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    use super::migrations::Migration;
+    use crate::core::errors::AppError;
+    use crate::core::structs::*;
+    use crate::data::constants::{
+        TABLE_NAME_API_TOKEN, TABLE_NAME_CATEGORY, TABLE_NAME_ENTRY, TABLE_NAME_ENTRY_PART,
+        TABLE_NAME_ITEM, TABLE_NAME_PART, TABLE_NAME_SCHEDULE, TABLE_NAME_USER,
+        TABLE_NAME_WORK_ORDER, TABLE_NAME_WORK_ORDER_LINE,
+    };
+
+    pub use crate::core::errors::Result;
+
+    /// Every item joined with its most recent entry. Shared by the SQL
+    /// backends so the index page costs one query however many items exist.
+    pub const COLLECT_ITEMS_QUERY: &str = r"
+        SELECT item.id, item.title, item.category_id, item.assignee_id,
+        user.username AS assignee, entry.id AS entry_id,
+        entry.cost, entry.note, entry.status, entry.visible, entry.removed,
+        CAST(entry.due_date AS CHAR) AS due_date, entry.priority
+        FROM item
+        LEFT JOIN user ON user.id = item.assignee_id
+        LEFT JOIN entry ON entry.id = (
+            SELECT MAX(id) FROM entry WHERE entry.item_id = item.id
+        )
+        ORDER BY item.id
+        ";
+
+    /// One item with its assignee's name, without its details.
+    pub const ITEM_QUERY: &str = r"
+        SELECT item.id, item.title, item.category_id, item.assignee_id,
+        user.username AS assignee
+        FROM item
+        LEFT JOIN user ON user.id = item.assignee_id
+        WHERE item.id = :id
+        ";
+
+    /// Insert an exported item, assigned to the user of the same name if
+    /// there is one.
+    pub const IMPORT_ITEM: &str = r"
+        INSERT INTO item (title, category_id, assignee_id)
+        VALUES (:title, :category_id, (SELECT id FROM user WHERE username = :assignee))
+        ";
+
+    /// Whether any table an archive is imported into has rows. Accounts
+    /// are not exported, so users may exist already.
+    pub const ARCHIVED_TABLES_USED: &str = r"
+        SELECT EXISTS (SELECT 1 FROM category) OR EXISTS (SELECT 1 FROM part)
+        OR EXISTS (SELECT 1 FROM item) OR EXISTS (SELECT 1 FROM entry)
+        OR EXISTS (SELECT 1 FROM schedule) OR EXISTS (SELECT 1 FROM work_order)
+        ";
+
+    pub const IMPORT_CATEGORY: &str =
+        "INSERT INTO category (title, removed) VALUES (:title, :removed)";
+
+    pub const IMPORT_PART: &str = r"
+        INSERT INTO part (part_number, description, unit_cost, quantity, reorder_point)
+        VALUES (:part_number, :description, :unit_cost, :quantity, :reorder_point)
+        ";
+
+    /// Work orders with their assignee's name and the total of their
+    /// lines. Followed by a `WHERE` clause.
+    pub const WORK_ORDER_QUERY: &str = r"
+        SELECT work_order.id, work_order.item_id, work_order.state, work_order.problem,
+        work_order.assignee_id, user.username AS assignee, work_order.resolution,
+        CAST(work_order.opened AS CHAR) AS opened, CAST(work_order.closed AS CHAR) AS closed,
+        COALESCE((
+            SELECT SUM(quantity * unit_cost) FROM work_order_line
+            WHERE work_order_line.work_order_id = work_order.id
+        ), 0) AS cost
+        FROM work_order
+        JOIN item ON item.id = work_order.item_id
+        LEFT JOIN user ON user.id = work_order.assignee_id
+        ";
+
+    /// Filter for `WORK_ORDER_QUERY`; a `NULL` parameter matches everything.
+    pub const WORK_ORDER_FILTER: &str = r"
+        WHERE (:state IS NULL OR work_order.state = :state)
+        AND (:item_id IS NULL OR work_order.item_id = :item_id)
+        AND (:category_id IS NULL OR item.category_id = :category_id)
+        AND (:assignee_id IS NULL OR work_order.assignee_id = :assignee_id)
+        ORDER BY work_order.id
+        ";
+
+    pub const WORK_ORDER_LINES_QUERY: &str = r"
+        SELECT id, work_order_id, kind, part_id, description, quantity, unit_cost,
+        CAST(created AS CHAR) AS created
+        FROM work_order_line
+        WHERE work_order_id = :work_order_id
+        ORDER BY id
+        ";
+
+    /// Take parts out of stock. Changes no row when there are too few on
+    /// hand, so stock never goes below zero.
+    pub const CONSUME_PART: &str = r"
+        UPDATE part SET quantity = quantity - :quantity
+        WHERE id = :id AND quantity >= :quantity
+        ";
+
+    /// Parts used by the entries of one item or by one entry; a `NULL`
+    /// parameter matches everything.
+    pub const ENTRY_PARTS_QUERY: &str = r"
+        SELECT entry_part.entry_id, entry_part.part_id, entry_part.quantity,
+        entry_part.unit_cost
+        FROM entry_part
+        JOIN entry ON entry.id = entry_part.entry_id
+        WHERE (:item_id IS NULL OR entry.item_id = :item_id)
+        AND (:entry_id IS NULL OR entry_part.entry_id = :entry_id)
+        ORDER BY entry_part.id
+        ";
+
+    /// Save a work order's state; `closed` is stamped the first time it closes.
+    pub const UPDATE_WORK_ORDER: &str = r"
+        UPDATE work_order
+        SET state = :state,
+        assignee_id = :assignee_id,
+        resolution = :resolution,
+        closed = CASE WHEN :state = 'closed' THEN COALESCE(closed, CURRENT_TIMESTAMP) END
+        WHERE id = :id
+        ";
+
+    /// Whether a category that is not removed has the title.
+    const CATEGORY_TITLE_TAKEN: &str = r"
+        SELECT 1 FROM category WHERE title = :title AND removed = 0 LIMIT 1
+        ";
+
+    /// Whether an item that is not removed, going by its latest entry, has
+    /// the title.
+    const ITEM_TITLE_TAKEN: &str = r"
+        SELECT 1 FROM item
+        LEFT JOIN entry ON entry.id = (
+            SELECT MAX(id) FROM entry WHERE entry.item_id = item.id
+        )
+        WHERE item.title = :title AND COALESCE(entry.removed, 0) = 0
+        LIMIT 1
+        ";
+
+    pub fn title_taken_query(table_name: &str) -> Result<&'static str> {
+        /// The query checking titles in `table_name`, selecting a row when
+        /// the `:title` is taken.
+        match table_name {
+            TABLE_NAME_CATEGORY => Ok(CATEGORY_TITLE_TAKEN),
+            TABLE_NAME_ITEM => Ok(ITEM_TITLE_TAKEN),
+            _ => Err(AppError::InvalidTable(table_name.to_owned())),
+        }
+    }
+
+    pub fn checked_table_name(table_name: &str) -> Result<&'static str> {
+        /// Match a table name against the `IsTable` tables, for the few places
+        /// where a name has to be formatted into SQL rather than bound.
+        [
+            TABLE_NAME_API_TOKEN,
+            TABLE_NAME_CATEGORY,
+            TABLE_NAME_ENTRY,
+            TABLE_NAME_ENTRY_PART,
+            TABLE_NAME_ITEM,
+            TABLE_NAME_PART,
+            TABLE_NAME_SCHEDULE,
+            TABLE_NAME_USER,
+            TABLE_NAME_WORK_ORDER,
+            TABLE_NAME_WORK_ORDER_LINE,
+        ]
+        .into_iter()
+        .find(|known| *known == table_name)
+        .ok_or_else(|| AppError::InvalidTable(table_name.to_owned()))
+    }
+
+    pub fn foreign_schedule(schedule_id: u32, item: &Item) -> AppError {
+        /// Error for an entry completing a schedule of some other item.
+        AppError::Validation(format!(
+            "Item {} has no schedule with id {}.",
+            item.id.unwrap_or_default(),
+            schedule_id
+        ))
+    }
+
+    pub fn unknown_part(part_id: u32) -> AppError {
+        /// Error for using a part that is not in the catalogue.
+        AppError::Validation(format!("No part with id {}.", part_id))
+    }
+
+    pub fn add_part_cost(cost: Option<u32>, part: &PartUse) -> Result<u32> {
+        /// An entry's cost with a part it used added, refused rather than
+        /// wrapped when it no longer fits.
+        part.quantity
+            .checked_mul(part.unit_cost)
+            .and_then(|parts_cost| parts_cost.checked_add(cost.unwrap_or(0)))
+            .ok_or_else(|| AppError::Validation("The total cost is too large.".to_owned()))
+    }
+
+    pub fn part_number_taken(part: &Part) -> AppError {
+        /// Error for adding a part whose number the catalogue already has.
+        AppError::Conflict(format!(
+            "Part number \"{}\" already exists.",
+            part.part_number
+        ))
+    }
+
+    pub fn database_not_empty() -> AppError {
+        /// Error for importing over existing records.
+        AppError::Conflict(
+            "The database already has categories, parts or items, import needs an empty one."
+                .to_owned(),
+        )
+    }
+
+    pub fn imported_item(
+        archived: &ArchivedItem,
+        category_ids: &BTreeMap<u32, u32>,
+        part_ids: &BTreeMap<u32, u32>,
+    ) -> Result<ArchivedItem> {
+        /// An archived item with its category and parts renumbered to the
+        /// ids they were imported under.
+        let mut archived = archived.clone();
+        let item = &mut archived.item;
+        item.category_id = *category_ids.get(&item.category_id).ok_or_else(|| {
+            AppError::Validation(format!(
+                "Item \"{}\" belongs to category {}, which is not in the archive.",
+                item.title, item.category_id
+            ))
+        })?;
+
+        let imported_part = |part_id: &mut u32| -> Result<()> {
+            *part_id = *part_ids.get(part_id).ok_or_else(|| {
+                AppError::Validation(format!("Part {} is used but not in the archive.", part_id))
+            })?;
+
+            Ok(())
+        };
+        for part_id in archived
+            .work_orders
+            .iter_mut()
+            .flat_map(|order| order.lines.iter_mut())
+            .filter_map(|line| line.part_id.as_mut())
+        {
+            imported_part(part_id)?;
+        }
+        for part in archived
+            .entries
+            .iter_mut()
+            .flat_map(|entry| entry.parts.iter_mut())
+        {
+            imported_part(&mut part.part_id)?;
+        }
+
+        Ok(archived)
+    }
+
+    pub fn short_of_part(part: &Part, quantity: u32) -> AppError {
+        /// Error for using more of a part than is on hand.
+        AppError::Conflict(format!(
+            "Only {} of part {} on hand, {} needed.",
+            part.quantity, part.part_number, quantity
+        ))
+    }
+
+    pub fn attach_parts(entries: &mut [Entry], parts: Vec<(u32, PartUse)>) {
+        /// Hand each entry the parts it used, from `(entry_id, part)` pairs.
+        for (entry_id, part) in parts {
+            if let Some(entry) = entries.iter_mut().find(|entry| entry.id == Some(entry_id)) {
+                entry.parts.push(part);
+            }
+        }
+    }
+
+    pub fn imported_id(
+        entry: &Entry,
+        old_id: Option<u32>,
+        new_ids: &BTreeMap<u32, Option<u32>>,
+        what: &str,
+    ) -> Result<Option<u32>> {
+        /// Translate the schedule or work order an exported entry refers
+        /// to into the one imported with it.
+        match old_id {
+            Some(old_id) => new_ids.get(&old_id).copied().ok_or_else(|| {
+                AppError::Validation(format!(
+                    "Entry {} refers to {} {}, which is not in the archive.",
+                    entry.id.unwrap_or_default(),
+                    what,
+                    old_id
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn work_order_entry(order: &WorkOrder, item: &Item) -> Item {
+        /// The item with its details tagged as recording the work order.
+        let mut item = item.clone();
+        if let Some(details) = item.details.as_mut() {
+            details.work_order_id = order.id;
+        }
+
+        item
+    }
+
+    pub fn item_ids(items: &[Item]) -> Vec<u32> {
+        /// The ids of the items that have one.
+        items.iter().filter_map(|item| item.id).collect()
+    }
+
+    pub fn items_by_id_query(count: usize) -> String {
+        /// `COLLECT_ITEMS_QUERY` narrowed to `count` item ids, bound in
+        /// order.
+        COLLECT_ITEMS_QUERY.replacen(
+            "ORDER BY item.id",
+            &format!(
+                "WHERE item.id IN ({}) ORDER BY item.id",
+                vec!["?"; count].join(", ")
+            ),
+            1,
+        )
+    }
+
+    pub fn check_all(
+        current: &BTreeMap<u32, Item>,
+        items: &[Item],
+        check: &dyn Fn(&Item, &Item) -> Result<()>,
+    ) -> Result<()> {
+        /// Run `check` on each item that exists, with its current version.
+        /// Unknown items are left for the save to report.
+        for item in items {
+            if let Some(old) = item.id.and_then(|id| current.get(&id)) {
+                check(old, item)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn save_all<F>(items: &[Item], mut save: F) -> SaveReport
+    where
+        F: FnMut(&Item) -> Result<()>,
+    {
+        /// Run `save` for every item and collect the outcome of each.
+        /// The caller commits only if the report says everything was saved.
+        let results = items
+            .iter()
+            .map(|item| {
+                let result = match (&item.id, &item.details) {
+                    (None, _) => Err(AppError::Validation("Missing item id.".to_owned())),
+                    (_, None) => Err(AppError::Validation("Missing item details.".to_owned())),
+                    _ => item.validate().and_then(|_| save(item)),
+                };
+
+                ItemSaveResult {
+                    id: item.id,
+                    error: result.err().map(|e| e.to_string()),
+                }
+            })
+            .collect::<Vec<ItemSaveResult>>();
+
+        SaveReport {
+            saved: results.iter().all(|result| result.error.is_none()),
+            items: results,
+        }
+    }
+
+    pub trait Store: fmt::Debug + Send + Sync {
+        /// Storage backend for categories, items and entries.
+        ///
+        /// Handlers only talk to the database through this trait, so the
+        /// backend can be swapped without touching `main.rs`.
+        fn backend(&self) -> Backend;
+        fn applied_migrations(&self) -> Result<Vec<u32>>;
+        fn apply_migration(&self, migration: &Migration) -> Result<()>;
+        /// Put an item on its `assignee_id` and record the item's details
+        /// as an entry for it, together.
+        fn assign_item(&self, item: &Item, author: &Author) -> Result<()>;
+        fn check_columns(&self, table_name: &str, columns: &[&str]) -> Result<()>;
+        fn collect_api_tokens(&self, user_id: u32) -> Result<Vec<ApiToken>>;
+        fn collect_categories(&self) -> Result<Vec<Category>>;
+        fn collect_items(&self) -> Result<BTreeMap<u32, Item>>;
+        /// Get an item's entries with the parts each one used, oldest
+        /// first.
+        fn collect_item_entries(&self, item_id: u32) -> Result<Vec<Entry>>;
+        /// Get the parts catalogue, by part number.
+        fn collect_parts(&self) -> Result<Vec<Part>>;
+        /// Get the schedules of one item, or of every item with `None`.
+        fn collect_schedules(&self, item_id: Option<u32>) -> Result<Vec<Schedule>>;
+        /// Get the work orders matching a filter, without their lines.
+        fn collect_work_orders(&self, filter: &WorkOrderFilter) -> Result<Vec<WorkOrder>>;
+        fn count_admins(&self) -> Result<u32>;
+        fn delete_category(&self, id: u32) -> Result<()>;
+        fn delete_item(&self, item_id: u32) -> Result<()>;
+        /// Get the API token with the given hash, unless it was revoked
+        /// or has expired.
+        fn find_api_token(&self, token_hash: &str) -> Result<ApiToken>;
+        fn get_api_token(&self, id: u32) -> Result<ApiToken>;
+        fn get_category(&self, id: u32) -> Result<Category>;
+        /// Get the most recent entry for an item.
+        fn get_entry(&self, item_id: u32) -> Result<Entry>;
+        fn get_entry_by_id(&self, id: u32) -> Result<Entry>;
+        fn get_item(&self, id: u32) -> Result<Item>;
+        fn get_part(&self, id: u32) -> Result<Part>;
+        fn get_schedule(&self, id: u32) -> Result<Schedule>;
+        fn get_user(&self, id: u32) -> Result<User>;
+        fn get_user_by_name(&self, username: &str) -> Result<User>;
+        /// Get a work order with its lines.
+        fn get_work_order(&self, id: u32) -> Result<WorkOrder>;
+        /// Insert an exported item with its schedules, work orders and
+        /// entries as they were, leaving out the accounts that wrote them.
+        /// Load a checked archive into an empty database, all of it or
+        /// none, renumbering the records as they go.
+        fn import(&self, archive: &Archive) -> Result<()>;
+        fn insert_api_token(
+            &self,
+            token: &mut ApiToken,
+            token_hash: &str,
+            expires_in_days: Option<u32>,
+        ) -> Result<()>;
+        fn insert_category(&self, title: &str) -> Result<u32>;
+        /// Record the item's details as a new entry written by `author`,
+        /// taking the parts it used out of stock.
+        fn insert_entry(&self, item: &Item, author: &Author) -> Result<u32>;
+        fn insert_item(&self, item: &mut Item, author: &Author) -> Result<()>;
+        fn insert_part(&self, part: &mut Part) -> Result<()>;
+        fn insert_schedule(&self, schedule: &mut Schedule) -> Result<()>;
+        fn insert_user(&self, user: &mut User) -> Result<()>;
+        /// Open a work order and record the item's details as an entry
+        /// for it, together.
+        fn insert_work_order(
+            &self,
+            order: &mut WorkOrder,
+            item: &Item,
+            author: &Author,
+        ) -> Result<()>;
+        /// Book a line against a work order, taking a catalogue part out of
+        /// stock at its current unit cost.
+        fn insert_work_order_line(&self, line: &mut WorkOrderLine) -> Result<()>;
+        /// The database's current time, in the format entry dates use.
+        fn now(&self) -> Result<String>;
+        fn remove_schedule(&self, id: u32) -> Result<()>;
+        fn revoke_api_token(&self, id: u32) -> Result<()>;
+        fn title_taken(&self, title: &str, table_name: &str) -> Result<bool>;
+        fn touch_api_token(&self, id: u32) -> Result<()>;
+        fn update_category(&self, category: &Category) -> Result<()>;
+        fn update_entry(&self, entry: &Entry) -> Result<()>;
+        fn update_item(&self, item: &Item, author: &Author) -> Result<()>;
+        /// Update several items in one transaction; either every item
+        /// is saved or none are. `check` is handed each existing item as
+        /// the transaction reads it and its new version, and stops the
+        /// save by failing.
+        fn update_items(
+            &self,
+            items: &[Item],
+            author: &Author,
+            check: &dyn Fn(&Item, &Item) -> Result<()>,
+        ) -> Result<SaveReport>;
+        fn update_part(&self, part: &Part) -> Result<()>;
+        /// Save a work order's new state and record the item's details as
+        /// an entry for it, together.
+        fn update_work_order(&self, order: &WorkOrder, item: &Item, author: &Author) -> Result<()>;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::sqlite::SqliteStore;
+    use super::store::{self, Store};
+    use super::{archive, migrations};
+    use crate::core::errors::AppError;
+    use crate::core::structs::*;
+    use crate::data::constants::{TABLE_NAME_CATEGORY, TABLE_NAME_ITEM};
+
+    fn test_author() -> Author {
+        Author {
+            user_id: None,
+            name: "Tester".to_owned(),
+        }
+    }
+
+    fn test_store() -> SqliteStore {
+        let store = SqliteStore::open_in_memory().unwrap();
+        migrations::run(&store, false).unwrap();
+
+        store
+    }
+
+    fn seed(store: &SqliteStore, item_count: u32, entries_per_item: u32) {
+        store.insert_category("Vehicles").unwrap();
+
+        for n in 0..item_count {
+            let mut item = Item::new(format!("Item {}", n), 1, Some(ItemDetails::new()));
+            store.insert_item(&mut item, &test_author()).unwrap();
+
+            for status in 1..entries_per_item {
+                item.details.as_mut().unwrap().status = status % 3;
+                store.insert_entry(&item, &test_author()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn hostile_titles_are_stored_verbatim() {
+        let store = test_store();
+        let titles = ["O'Brien", "'); DROP TABLE item; --", "\" OR 1=1 --"];
+
+        for title in titles {
+            store.insert_category(title).unwrap();
+            assert!(store.title_taken(title, TABLE_NAME_CATEGORY).unwrap());
+
+            let mut item = Item::new(title.to_owned(), 1, Some(ItemDetails::new()));
+            store.insert_item(&mut item, &test_author()).unwrap();
+            assert!(store.title_taken(title, TABLE_NAME_ITEM).unwrap());
+        }
+
+        let categories = store.collect_categories().unwrap();
+        let items = store.collect_items().unwrap();
+
+        assert_eq!(categories.len(), titles.len());
+        assert_eq!(items.len(), titles.len());
+        for title in titles {
+            assert!(categories.iter().any(|category| category.title == title));
+            assert!(items.values().any(|item| item.title == title));
+        }
+    }
+
+    #[test]
+    fn entries_are_scoped_to_their_item() {
+        let store = test_store();
+        seed(&store, 2, 3);
+
+        let entries = store.collect_item_entries(1).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.author.as_deref() == Some("Tester")));
+        assert!(store.collect_item_entries(99).unwrap().is_empty());
+    }
+
+    #[test]
+    fn mysql_retries_skip_what_was_already_created() {
+        let error = |code| {
+            mysql::Error::MySqlError(mysql::MySqlError {
+                state: "42S21".to_owned(),
+                message: "Duplicate".to_owned(),
+                code,
+            })
+        };
+
+        // table, column, index and foreign key already there
+        for code in [1050, 1060, 1061, 1826] {
+            assert!(migrations::already_applied(&error(code)), "{}", code);
+        }
+        // unknown column, syntax error
+        for code in [1054, 1064] {
+            assert!(!migrations::already_applied(&error(code)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn dangling_references_are_conflicts() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TABLE part (id INTEGER PRIMARY KEY);
+            CREATE TABLE entry_part (part_id INT NOT NULL REFERENCES part (id));
+            INSERT INTO part (id) VALUES (1);
+            INSERT INTO entry_part (part_id) VALUES (1);
+            ",
+        )
+        .unwrap();
+
+        for statement in [
+            "DELETE FROM part WHERE id = 1",
+            "INSERT INTO entry_part (part_id) VALUES (2)",
+        ] {
+            let e = AppError::from(conn.execute(statement, []).unwrap_err());
+            assert!(matches!(e, AppError::Conflict(_)), "{}: {}", statement, e);
+        }
+
+        let e = AppError::from(mysql::Error::MySqlError(mysql::MySqlError {
+            state: "23000".to_owned(),
+            message: "Cannot delete or update a parent row".to_owned(),
+            code: 1451,
+        }));
+        assert!(matches!(e, AppError::Conflict(_)), "{}", e);
+    }
+
+    #[test]
+    fn unknown_table_names_are_rejected() {
+        let store = test_store();
+
+        for table_name in ["item; DROP TABLE item", "schema_migrations", ""] {
+            assert!(store::checked_table_name(table_name).is_err());
+            assert!(store.title_taken("Car", table_name).is_err());
+            assert!(store.check_columns(table_name, &["id"]).is_err());
+        }
+
+        assert_eq!(store::checked_table_name("item").unwrap(), TABLE_NAME_ITEM);
+    }
+
+    #[test]
+    fn export_imports_into_an_empty_database() {
+        let store = test_store();
+        store.insert_category("Unused").unwrap();
+        seed(&store, 2, 3);
+        let mut part = Part {
+            id: None,
+            part_number: "TY-16".to_owned(),
+            description: "Tyre".to_owned(),
+            unit_cost: 8000,
+            quantity: 4,
+            reorder_point: 1,
+        };
+        store.insert_part(&mut part).unwrap();
+
+        let mut item = store.get_item(2).unwrap();
+        let mut order = WorkOrder {
+            id: None,
+            item_id: 2,
+            state: WorkOrderState::Open,
+            problem: "Flat tyre".to_owned(),
+            assignee_id: None,
+            assignee: None,
+            resolution: None,
+            opened: None,
+            closed: None,
+            cost: 0,
+            lines: vec![],
+        };
+        store
+            .insert_work_order(&mut order, &item, &test_author())
+            .unwrap();
+        store
+            .insert_work_order_line(&mut WorkOrderLine {
+                id: None,
+                work_order_id: order.id.unwrap(),
+                kind: WorkOrderLineKind::Part,
+                part_id: part.id,
+                description: "Tyre".to_owned(),
+                quantity: 1,
+                unit_cost: 0,
+                created: None,
+            })
+            .unwrap();
+        order.state = WorkOrderState::Closed;
+        order.resolution = Some("Replaced".to_owned());
+        store
+            .update_work_order(&order, &item, &test_author())
+            .unwrap();
+        item.details.as_mut().unwrap().parts = vec![PartUse {
+            part_id: 1,
+            quantity: 2,
+            unit_cost: 0,
+        }];
+        store.insert_entry(&item, &test_author()).unwrap();
+
+        let archive = archive::export(&store).unwrap();
+        let copy = test_store();
+        archive::import(&copy, &archive).unwrap();
+
+        // ids may be renumbered, so compare through a round trip
+        let round_trip = archive::export(&copy).unwrap();
+        assert_eq!(
+            serde_json::to_value(&round_trip).unwrap(),
+            serde_json::to_value(&archive).unwrap()
+        );
+        assert_eq!(copy.collect_item_entries(2).unwrap().len(), 6);
+        assert_eq!(copy.get_work_order(1).unwrap().cost, 8000);
+        // stock was taken when the parts were used, not again on import
+        assert_eq!(copy.get_part(1).unwrap().quantity, 1);
+
+        assert!(matches!(
+            archive::import(&copy, &archive),
+            Err(AppError::Conflict(_))
+        ));
+
+        // a bad reference in the last item is found before anything is
+        // written, so the import can be retried once the archive is fixed
+        let mut broken: Archive =
+            serde_json::from_value(serde_json::to_value(&archive).unwrap()).unwrap();
+        let last = broken.items.last_mut().unwrap();
+        last.entries.last_mut().unwrap().parts[0].part_id = 99;
+        let empty = test_store();
+        assert!(matches!(
+            archive::import(&empty, &broken),
+            Err(AppError::Validation(_))
+        ));
+        assert!(empty.collect_categories().unwrap().is_empty());
+        assert!(empty.collect_parts().unwrap().is_empty());
+        archive::import(&empty, &archive).unwrap();
+    }
+
+    #[test]
+    fn item_history_is_oldest_first() {
+        let path = std::env::temp_dir().join(format!("mt-history-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = SqliteStore::open(path.to_str().unwrap()).unwrap();
+        migrations::run(&store, false).unwrap();
+        seed(&store, 1, 4);
+
+        // an imported entry can be older than ones written before it
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "UPDATE entry SET date = '2001-01-01 00:00:00' WHERE id = 3",
+            [],
+        )
+        .unwrap();
+
+        let ids = store
+            .collect_item_entries(1)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id.unwrap())
+            .collect::<Vec<u32>>();
+        assert_eq!(ids, [3, 1, 2, 4]);
+
+        drop(conn);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bulk_saves_check_items_as_they_are() {
+        let store = test_store();
+        seed(&store, 2, 1);
+        let mut items: Vec<Item> = store.collect_items().unwrap().into_values().collect();
+        for item in items.iter_mut() {
+            item.details.as_mut().unwrap().note = Some("Checked".to_owned());
+        }
+        items[1].title = "Renamed".to_owned();
+
+        // a refused item stops the whole save
+        let seen = std::cell::RefCell::new(vec![]);
+        let e = store
+            .update_items(&items, &test_author(), &|old, new| {
+                seen.borrow_mut().push(old.title.clone());
+                match old.title == new.title {
+                    true => Ok(()),
+                    false => Err(AppError::Forbidden("No renaming.".to_owned())),
+                }
+            })
+            .unwrap_err();
+        assert!(matches!(e, AppError::Forbidden(_)));
+        assert_eq!(*seen.borrow(), ["Item 0", "Item 1"]);
+        assert!(store.collect_items().unwrap()[&1]
+            .details
+            .as_ref()
+            .unwrap()
+            .note
+            .is_none());
+
+        let report = store
+            .update_items(&items, &test_author(), &|_, _| Ok(()))
+            .unwrap();
+        assert!(report.saved);
+        assert_eq!(store.get_item(2).unwrap().title, "Renamed");
+    }
+
+    #[test]
+    fn imports_are_all_or_nothing() {
+        let store = test_store();
+        seed(&store, 3, 2);
+        store
+            .insert_part(&mut Part {
+                id: None,
+                part_number: "T-1".to_owned(),
+                description: "Tyre".to_owned(),
+                unit_cost: 100,
+                quantity: 4,
+                reorder_point: 0,
+            })
+            .unwrap();
+        let archive = archive::export(&store).unwrap();
+
+        // the database refuses the last item's entries, after the
+        // categories, parts and other items went in
+        let path = std::env::temp_dir().join(format!("mt-import-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let copy = SqliteStore::open(path.to_str().unwrap()).unwrap();
+        migrations::run(&copy, false).unwrap();
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TRIGGER refuse_entry BEFORE INSERT ON entry WHEN NEW.item_id = 3
+            BEGIN SELECT RAISE(ABORT, 'disk full'); END
+            ",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            archive::import(&copy, &archive),
+            Err(AppError::Sqlite(_))
+        ));
+        assert!(copy.collect_categories().unwrap().is_empty());
+        assert!(copy.collect_parts().unwrap().is_empty());
+        assert!(copy.collect_items().unwrap().is_empty());
+
+        conn.execute_batch("DROP TRIGGER refuse_entry").unwrap();
+        archive::import(&copy, &archive).unwrap();
+        assert_eq!(copy.collect_items().unwrap().len(), 3);
+        drop(conn);
+        drop(copy);
+        std::fs::remove_file(&path).unwrap();
+
+        // a catalogue alone is enough to refuse an import
+        let stocked = test_store();
+        stocked.insert_part(&mut archive.parts[0].clone()).unwrap();
+        assert!(matches!(
+            archive::import(&stocked, &archive),
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn collect_items_uses_latest_entry() {
+        let store = test_store();
+        seed(&store, 3, 3);
+
+        let items = store.collect_items().unwrap();
+
+        assert_eq!(items.len(), 3);
+        for item in items.values() {
+            assert_eq!(item.details.as_ref().unwrap().status, 2);
+        }
+    }
+
+    #[test]
+    fn item_queries_never_scan_entries() {
+        let store = test_store();
+        seed(&store, 20, 5);
+
+        // a full scan of `entry`, once or per item, grows with the history;
+        // every query touching entries must go through an index instead
+        for sql in [
+            store::COLLECT_ITEMS_QUERY,
+            store::title_taken_query(TABLE_NAME_ITEM).unwrap(),
+        ] {
+            let plan = store.query_plan(sql);
+            assert!(plan.iter().any(|step| step.contains("entry")), "{:?}", plan);
+            for step in plan.iter().filter(|step| step.contains("entry")) {
+                assert!(step.starts_with("SEARCH entry USING "), "{:?}", plan);
+            }
+        }
+    }
+
+    #[test]
+    fn collect_items_scales_linearly() {
+        let steps = |item_count: u32, entries_per_item: u32| {
+            let store = test_store();
+            seed(&store, item_count, entries_per_item);
+            assert_eq!(store.collect_items().unwrap().len(), item_count as usize);
+
+            f64::from(store.vm_steps(store::COLLECT_ITEMS_QUERY)) / f64::from(item_count)
+        };
+
+        // a per-item query (or a scan of `entry` per item) makes the cost
+        // of each item grow with the table; a set-based query keeps it flat
+        let (small, large) = (steps(100, 5), steps(1_000, 5));
+        assert!(
+            large <= small * 1.1,
+            "{} steps per item, then {}",
+            small,
+            large
+        );
+
+        // and a longer history does not slow the page down either
+        let (short, long) = (steps(100, 2), steps(100, 50));
+        assert!(
+            long <= short * 1.1,
+            "{} steps per item, then {}",
+            short,
+            long
+        );
     }
 }
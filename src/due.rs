@@ -0,0 +1,98 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::core::errors::AppError;
+use crate::core::structs::*;
+use crate::data::constants::DUE_SOON_DAYS;
+use crate::db::store::{self, Store};
+
+pub fn due_items(items: impl IntoIterator<Item = Item>, today: NaiveDate) -> Vec<DueItem> {
+    /// Warning and Stopped items that are overdue or due within
+    /// `DUE_SOON_DAYS`, soonest first, then by priority and title.
+    let mut due = items
+        .into_iter()
+        .filter_map(|item| {
+            let details = item.details.as_ref()?;
+            if details.removed || details.status == 0 {
+                return None;
+            }
+
+            let due_date =
+                NaiveDate::parse_from_str(details.due_date.as_deref()?, "%Y-%m-%d").ok()?;
+            let days_left = (due_date - today).num_days();
+
+            (days_left <= DUE_SOON_DAYS).then_some(DueItem {
+                item,
+                days_left,
+                overdue: days_left < 0,
+            })
+        })
+        .collect::<Vec<DueItem>>();
+
+    // items without a priority come after the lowest one
+    due.sort_by_cached_key(|due| {
+        let details = due.item.details.as_ref();
+        (
+            due.days_left,
+            details
+                .and_then(|details| details.priority)
+                .unwrap_or(u32::MAX),
+            due.item.title.clone(),
+        )
+    });
+
+    due
+}
+
+pub fn collect(store: &dyn Store) -> store::Result<Vec<DueItem>> {
+    /// The overdue and due-this-week list, counted from the database's date.
+    let now = store.now()?;
+    let today = NaiveDateTime::parse_from_str(&now, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| AppError::Parse(format!("Invalid date \"{}\".", now)))?
+        .date();
+
+    Ok(due_items(store.collect_items()?.into_values(), today))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u32, status: u32, due_date: Option<&str>, priority: Option<u32>) -> Item {
+        let mut details = ItemDetails::new();
+        details.status = status;
+        details.due_date = due_date.map(str::to_owned);
+        details.priority = priority;
+
+        let mut item = Item::new(format!("Item {}", id), 1, Some(details));
+        item.id = Some(id);
+
+        item
+    }
+
+    #[test]
+    fn due_items_are_sorted_soonest_first() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let items = vec![
+            item(1, 1, Some("2024-05-17"), None),
+            item(2, 2, Some("2024-05-09"), Some(3)),
+            // fixed already, or not due for a while
+            item(3, 0, Some("2024-05-01"), Some(1)),
+            item(4, 1, Some("2024-05-18"), Some(1)),
+            item(5, 1, None, Some(1)),
+            item(6, 2, Some("2024-05-17"), Some(2)),
+            item(7, 1, Some("2024-05-10"), None),
+        ];
+
+        let due = due_items(items, today);
+        let ids = due
+            .iter()
+            .map(|due| due.item.id.unwrap())
+            .collect::<Vec<u32>>();
+
+        assert_eq!(ids, [2, 7, 6, 1]);
+        assert!(due[0].overdue);
+        assert_eq!(due[0].days_left, -1);
+        assert!(!due[1].overdue);
+        assert_eq!(due[3].days_left, 7);
+    }
+}
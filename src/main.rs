@@ -1,23 +1,40 @@
 #![allow(unused)]
 
 use std::collections::BTreeMap;
-use std::env::{args, var};
-use std::io;
+use std::env::var;
+use std::io::{self, IsTerminal};
 use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
 
+use clap::Parser;
 use dotenv::dotenv;
-use mysql::{prelude::*, *};
 use rpassword::read_password;
 use tera::Tera;
+use tide::listener::ConcurrentListener;
 use tide_tera::prelude::*;
 
+mod api;
+mod auth;
+mod cli;
 mod core;
+mod credentials;
 mod data;
 mod db;
+mod due;
+mod my_work;
+mod parts;
+mod schedule;
+mod tls;
 
-use crate::core::{functions, structs::*};
+use crate::cli::{Cli, Command, ConfigCommand, UserCommand};
+use crate::core::{errors::AppError, functions, structs::*};
 use data::*;
-use db::database;
+use db::archive;
+use db::database::{self, MySqlStore};
+use db::migrations;
+use db::sqlite::SqliteStore;
+use db::store::{self, Store};
 
 #[derive(Clone, Debug)]
 struct State {
@@ -25,176 +42,261 @@ struct State {
     app_title: Option<String>,
     app_version: Option<String>,
     config: Config,
-    db_credentials: DbCredentials,
+    store: Arc<dyn Store>,
     tera: Tera,
 }
 
 impl State {
-    fn new(tera_instance: Tera, config: Config, credentials: DbCredentials) -> Self {
+    fn new(tera_instance: Tera, config: Config, store: Arc<dyn Store>) -> Self {
         State {
             app_title: None,
             app_version: None,
-            config: config,
-            db_credentials: credentials,
+            config,
+            store,
             tera: tera_instance,
         }
     }
 }
 
-fn read_json<T>(filepath: &Path) -> T
+fn read_json<T>(filepath: &Path) -> store::Result<T>
 where
     T: serde::Serialize,
     T: serde::de::DeserializeOwned,
 {
-    serde_json::from_str::<T>(&std::fs::read_to_string(filepath).unwrap()).unwrap()
+    /// Read a JSON file, naming it in the error.
+    std::fs::read_to_string(filepath)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str::<T>(&json).map_err(|e| e.to_string()))
+        .map_err(|e| AppError::Parse(format!("Could not read \"{}\": {}", filepath.display(), e)))
 }
 
-fn write_json<T>(object: &T, filename: &str)
+fn write_json<T>(object: &T, filename: &str) -> io::Result<()>
 where
     T: serde::Serialize,
 {
-    std::fs::write(
-        format!("{}", filename),
-        serde_json::to_string_pretty(object).unwrap(),
-    )
-    .unwrap();
+    std::fs::write(filename, serde_json::to_string_pretty(object)?)
 }
 
-#[async_std::main]
-async fn main() -> Result<()> {
-    dotenv().ok();
-    tide::log::start();
+fn saved_credentials(credentials_filepath: &Path) -> tide::Result<Option<DbCredentials>> {
+    /// Read MySQL credentials from the credentials file, overridden by
+    /// any `MT_DB_` environment variables.
+    let key = credentials::key(|name| var(name).ok())?;
+    let saved = credentials::load(credentials_filepath, key.as_ref())?;
 
-    let config_filepath = Path::new(constants::CONFIG_FILE);
-    let credentials_filepath = Path::new(constants::CREDENTIALS_FILE);
+    Ok(DbCredentials::from_env(saved, |name| var(name).ok())?)
+}
 
-    let save = args().last() == Some("-s".to_owned());
-    let other = args().last() == Some("-o".to_owned());
+fn write_config(config: &Config, filename: &str) -> io::Result<()> {
+    /// Save the config in the format its file name asks for.
+    if filename.ends_with(".toml") {
+        let toml = toml::to_string_pretty(config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(filename, toml)
+    } else {
+        write_json(config, filename)
+    }
+}
 
-    let mut conn: PooledConn;
-    let mut conn_string: String = String::new();
-    let mut config: Config;
+fn mysql_credentials(
+    credentials_filepath: &Path,
+    command: &Command,
+) -> tide::Result<DbCredentials> {
+    /// Read saved MySQL credentials, or prompt for them until they are valid.
+    /// `setup` and `once` always prompt, and `setup` saves the answer.
     let mut credentials: DbCredentials;
 
-    if config_filepath.exists() {
-        // if a config file exists, read it
-        config = read_json::<Config>(&config_filepath);
-    } else {
-        // write a config file if one doesn't exist
-        config = Config::from_prompt();
+    if !matches!(command, Command::Setup | Command::Once) {
+        // if credentials are saved or set in the environment, use them
+        if let Some(credentials) = saved_credentials(credentials_filepath)? {
+            if database::test_auth(&credentials).is_err() {
+                return Err(AppError::Unauthorized(
+                    constants::SAVED_CREDENTIALS_INVALID_MSG.to_owned(),
+                )
+                .into());
+            }
+
+            return Ok(credentials);
+        }
 
-        write_json(&config, constants::CONFIG_FILE);
+        if !command.interactive() {
+            return Err(AppError::Unauthorized(constants::NO_CREDENTIALS_MSG.to_owned()).into());
+        }
     }
 
-    if credentials_filepath.exists() && !save && !other {
-        // if credentials file exists and no flags are passed
-        credentials = read_json::<DbCredentials>(&credentials_filepath);
+    loop {
+        credentials = DbCredentials::from_prompt()?;
 
         match database::test_auth(&credentials) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("{}", constants::SAVED_CREDENTIALS_INVALID_MSG);
-                panic!();
-            }
-        }
-    } else if save {
-        // if `-s` flag is passed, overwrite existing configuration & credentials
-        loop {
-            config = Config::from_prompt();
-            credentials = DbCredentials::from_prompt();
-
-            match database::test_auth(&credentials) {
-                Ok(_) => break,
-                Err(_) => {
-                    println!("{}", constants::CREDENTIALS_INVALID_MSG);
-                    continue;
-                }
+            Ok(_) => break,
+            Err(_) => {
+                println!("{}", constants::CREDENTIALS_INVALID_MSG);
+                continue;
             }
         }
+    }
 
-        write_json(&config, constants::CONFIG_FILE);
-        write_json(&credentials, constants::CREDENTIALS_FILE);
-    } else {
-        // if `-o` flag is passed, get other configuration settings
-        loop {
-            config = Config::from_prompt();
-            credentials = DbCredentials::from_prompt();
-
-            match database::test_auth(&credentials) {
-                Ok(_) => break,
-                Err(_) => {
-                    println!("{}", constants::CREDENTIALS_INVALID_MSG);
-                    continue;
-                }
-            }
-        }
+    if *command == Command::Setup {
+        // `setup` overwrites existing credentials, encrypted if a key is set
+        let key = credentials::key(|name| var(name).ok())?;
+        credentials::save(credentials_filepath, &credentials, key.as_ref())?;
     }
 
-    conn = database::connect(&credentials).unwrap();
+    Ok(credentials)
+}
 
-    // get a clone of config.port for use in launching the application
-    let port = config.port.clone();
+fn build_app(state: State) -> tide::Server<State> {
+    /// Register middleware and routes.
+    let mut app = tide::with_state(state);
 
-    // we're using tera for templating
-    let mut tera = Tera::new("templates/**/*").expect("Error parsing templates directory.");
-    tera.autoescape_on(vec!["html"]);
+    // turn application errors into a status code and a JSON body
+    app.with(tide::utils::After(|mut res: tide::Response| async move {
+        if let Some(err) = res.downcast_error::<AppError>() {
+            let status = err.status();
+            let body = tide::Body::from_json(&api::ErrorBody {
+                error: err.public_message(),
+            })?;
 
-    let mut state = State::new(tera, config, credentials);
-    let mut app = tide::with_state(state);
+            if status == tide::StatusCode::InternalServerError {
+                tide::log::error!("{}", err);
+            }
+            if status == tide::StatusCode::ServiceUnavailable {
+                res.insert_header("Retry-After", "1");
+            }
+
+            res.set_status(status);
+            res.set_body(body);
+        }
+
+        Ok(res)
+    }));
 
-    // get existing database items
-    let mut items = database::collect_items(&mut conn);
+    app.with(auth::sessions());
+    app.with(auth::bearer_token);
+    app.with(auth::require_login);
+    app.with(auth::verify_csrf);
 
     app.at("/static").serve_dir("./static").unwrap();
 
+    // login and logout
+    auth::register(&mut app);
+
+    // JSON API for scripts
+    api::register(&mut app);
+
     // index page
     app.at("/")
-        .get(|req: tide::Request<State>| async move {
+        .get(|mut req: tide::Request<State>| async move {
             /// Get information from the database.
+            let csrf_token = auth::csrf_token(&mut req)?;
             let tera = req.state().tera.clone();
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
+            let store = &req.state().store;
 
             tera.render_response(
                 "index.html",
                 &context! {
                     "app_title" => constants::APP_TITLE.to_owned(),
                     "app_version" => constants::APP_VERSION.to_owned(),
-                    "categories" => database::collect_categories(&mut c),
-                    "items" => database::collect_items(&mut c),
+                    "categories" => store.collect_categories()?,
+                    "csrf_token" => csrf_token,
+                    "due" => due::collect(store.as_ref())?,
+                    "items" => store.collect_items()?,
+                    "low_stock" => parts::collect(store.as_ref())?,
+                    "require_login" => req.state().config.require_login,
+                    "username" => match auth::current_user(&req) {
+                        Some(id) => Some(store.get_user(id)?.username),
+                        None => None,
+                    },
                 },
             )
         })
         .post(|mut req: tide::Request<State>| async move {
             /// Update information in the database.
             let req_string = req.body_string().await?;
-            let items = functions::parse_json_string(req_string);
-
-            for (id, item) in items {
-                match database::update_item(
-                    &mut database::connect(&req.state().db_credentials).unwrap(),
-                    &item,
-                ) {
-                    Ok(_) => {}
-                    Err(e) => return Ok(format!("Error updating item {}: {}", id, e)),
-                };
-            }
+            let mut items: Vec<Item> = functions::parse_json_string(req_string)?
+                .into_values()
+                .collect();
+            items.sort_by_key(|item| item.id);
+
+            let store = &req.state().store;
+            let role = auth::require_role(&req, Role::Technician)?;
+
+            // all items are checked against what they are now and saved in
+            // one transaction, or none are
+            let report = store.update_items(&items, &auth::author(&req)?, &|old, new| {
+                auth::check_item_change(&req, role, old, new)
+            })?;
 
-            Ok("OK".to_owned())
+            let mut res = tide::Response::new(match report.saved {
+                true => tide::StatusCode::Ok,
+                false => tide::StatusCode::UnprocessableEntity,
+            });
+            res.set_body(tide::Body::from_json(&report)?);
+
+            Ok(res)
+        });
+
+    // overdue and due-this-week items
+    app.at("due").get(|req: tide::Request<State>| async move {
+        let tera = req.state().tera.clone();
+        let store = &req.state().store;
+
+        tera.render_response(
+            "due.html",
+            &context! {
+                "app_title" => constants::APP_TITLE.to_owned(),
+                "app_version" => constants::APP_VERSION.to_owned(),
+                "due" => due::collect(store.as_ref())?,
+            },
+        )
+    });
+
+    // parts at or below their reorder point
+    app.at("low-stock")
+        .get(|req: tide::Request<State>| async move {
+            let tera = req.state().tera.clone();
+            let store = &req.state().store;
+
+            tera.render_response(
+                "low_stock.html",
+                &context! {
+                    "app_title" => constants::APP_TITLE.to_owned(),
+                    "app_version" => constants::APP_VERSION.to_owned(),
+                    "parts" => parts::collect(store.as_ref())?,
+                },
+            )
+        });
+
+    // items and open work orders assigned to the logged-in user
+    app.at("my-work")
+        .get(|req: tide::Request<State>| async move {
+            let tera = req.state().tera.clone();
+            let store = &req.state().store;
+            let user_id = auth::current_user(&req)
+                .ok_or_else(|| AppError::Unauthorized(constants::LOGIN_REQUIRED_MSG.to_owned()))?;
+
+            tera.render_response(
+                "my_work.html",
+                &context! {
+                    "app_title" => constants::APP_TITLE.to_owned(),
+                    "app_version" => constants::APP_VERSION.to_owned(),
+                    "items" => store.collect_items()?,
+                    "username" => store.get_user(user_id)?.username,
+                    "work" => my_work::collect(store.as_ref(), user_id)?,
+                },
+            )
         });
 
     // ajax history
     app.at("history/:id")
         .get(|mut req: tide::Request<State>| async move {
             // get item id from URL
-            let id = req.param("id").unwrap();
+            let id = functions::parse_id(req.param("id")?)?;
 
             // get all entries with matching id
-            let mut entries = database::collect_item_entries(
-                &mut database::connect(&req.state().db_credentials).unwrap(),
-                &id,
-            );
+            req.state().store.get_item(id)?;
+            let mut entries = req.state().store.collect_item_entries(id)?;
 
-            // build HTML response
+            // build HTML response, escaping what users typed in
             let mut html_str = String::from("");
             for entry in entries {
                 html_str.push_str(&format!(
@@ -203,13 +305,17 @@ async fn main() -> Result<()> {
                         <p>{}</p>
                         <p>{}</p>
                         <p>{}</p>
+                        <p>{}</p>
                         <p class=\"note\">{}</p>
+                        <p class=\"assignment\">{}</p>
                     </div>
                     ",
-                    entry.date.unwrap(),
+                    entry.date.unwrap_or_default(),
+                    tera::escape_html(&entry.author.unwrap_or_default()),
                     entry.status.unwrap_or(0),
                     entry.cost.unwrap_or(0),
-                    entry.note.unwrap_or("No Description.".to_string())
+                    tera::escape_html(&entry.note.unwrap_or("No Description.".to_string())),
+                    tera::escape_html(&entry.assignment.unwrap_or_default())
                 ));
             }
 
@@ -218,76 +324,1926 @@ async fn main() -> Result<()> {
 
     app.at("add/category")
         .post(|mut req: tide::Request<State>| async move {
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
-            let category = serde_json::from_str::<Category>(&req.body_string().await?)?;
+            auth::require_role(&req, Role::Supervisor)?;
+            let store = req.state().store.clone();
+            let category = functions::parse_json::<Category>(&req.body_string().await?)?;
+            category.validate()?;
 
-            if database::title_taken(&mut c, &category.title, category.table_name()) {
-                return Ok(format!(
+            if store.title_taken(&category.title, category.table_name())? {
+                return Err(AppError::Conflict(format!(
                     "Category named \"{}\" already exists.",
                     &category.title
-                ));
+                ))
+                .into());
             }
 
-            match database::insert_category(&mut c, &category.title) {
-                Ok(()) => Ok("OK".to_owned()),
-                Err(e) => Ok(format!("Error inserting category: {}", e)),
-            }
+            store.insert_category(&category.title)?;
+
+            Ok("OK")
         });
     app.at("delete/category")
         .post(|mut req: tide::Request<State>| async move {
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
-            let category_id: u32 = serde_json::from_str(&req.body_string().await?)?;
+            auth::require_role(&req, Role::Admin)?;
+            let store = req.state().store.clone();
+            let category_id: u32 = functions::parse_json(&req.body_string().await?)?;
 
-            database::delete_category(&mut c, category_id);
+            store.delete_category(category_id)?;
 
             Ok("OK")
         });
 
     app.at("add/item")
         .post(|mut req: tide::Request<State>| async move {
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
-            let mut item = serde_json::from_str::<Item>(&req.body_string().await?)?;
+            auth::require_role(&req, Role::Supervisor)?;
+            let store = req.state().store.clone();
+            let mut item = functions::parse_json::<Item>(&req.body_string().await?)?;
             item.details = Some(ItemDetails::new());
+            item.validate()?;
 
-            if database::title_taken(&mut c, &item.title, item.table_name()) {
-                return Ok(format!("Item named \"{}\" already exists.", &item.title));
+            if store.title_taken(&item.title, item.table_name())? {
+                return Err(AppError::Conflict(format!(
+                    "Item named \"{}\" already exists.",
+                    &item.title
+                ))
+                .into());
             }
 
-            println!("{:?}", item);
+            store.insert_item(&mut item, &auth::author(&req)?)?;
 
-            match database::insert_item(&mut c, &mut item) {
-                Ok(()) => Ok("OK".to_owned()),
-                Err(e) => Ok(format!("Error inserting item: {}", e)),
-            }
+            Ok("OK")
         });
     app.at("delete/item")
         .post(|mut req: tide::Request<State>| async move {
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
-            let item_id: u32 = serde_json::from_str(&req.body_string().await?)?;
+            auth::require_role(&req, Role::Admin)?;
+            let store = req.state().store.clone();
+            let item_id: u32 = functions::parse_json(&req.body_string().await?)?;
 
-            database::delete_item(&mut c, item_id);
+            store.delete_item(item_id)?;
 
             Ok("OK")
         });
     app.at("update/item")
         .post(|mut req: tide::Request<State>| async move {
-            let mut c = database::connect(&req.state().db_credentials).unwrap();
+            let store = req.state().store.clone();
+
+            let role = auth::require_role(&req, Role::Technician)?;
+            let item = functions::parse_json::<Item>(&req.body_string().await?)?;
+            item.validate()?;
+
+            if let Some(id) = item.id {
+                auth::check_item_change(&req, role, &store.get_item(id)?, &item)?;
+            }
 
-            let item = match serde_json::from_str::<Item>(&req.body_string().await?) {
-                Ok(item) => item,
-                Err(e) => {
-                    return Ok(format!("Error parsing item: {}", e));
+            store.update_item(&item, &auth::author(&req)?)?;
+
+            Ok("OK")
+        });
+
+    app
+}
+
+fn listeners(config: &Config) -> io::Result<ConcurrentListener<State>> {
+    /// One listener per bind address, speaking HTTPS on TCP addresses
+    /// when TLS is configured. Unix sockets are left to a reverse proxy
+    /// to encrypt.
+    let mut listener = ConcurrentListener::new();
+    let server_config = match &config.tls {
+        Some(tls_config) => Some(tls::server_config(tls_config)?),
+        None => None,
+    };
+
+    for bind in &config.bind {
+        match (bind.socket_addr(config.port), &server_config) {
+            (Some(addr), Some(server_config)) => {
+                listener.add(tls::listener(server_config, addr)?)?
+            }
+            (Some(addr), None) => listener.add(addr)?,
+            (None, _) => {
+                if let BindAddress::Unix(path) = bind {
+                    remove_stale_socket(path)?;
+                    listener.add(format!("http+unix://{}", path.display()))?;
                 }
-            };
+            }
+        }
+    }
+
+    if let Some(redirect_port) = config.tls.as_ref().and_then(|tls| tls.redirect_port) {
+        // send plain HTTP visitors to the HTTPS listeners
+        let mut redirect = ConcurrentListener::new();
 
-            match database::update_item(&mut c, &item) {
-                Ok(()) => Ok("OK".to_owned()),
-                Err(e) => Ok(format!("Error updating item: {}", e)),
+        for bind in &config.bind {
+            if let Some(addr) = bind.socket_addr(redirect_port) {
+                redirect.add(addr)?;
+            }
+        }
+
+        let https_port = config.port;
+        async_std::task::spawn(async move {
+            if let Err(e) = tls::redirect_app(https_port).listen(redirect).await {
+                tide::log::error!("HTTP redirect listener stopped: {}", e);
             }
         });
+    }
+
+    Ok(listener)
+}
+
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    /// Remove a socket left behind by an earlier run, which would otherwise
+    /// keep the new one from binding. Anything else at the path is kept.
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+fn exit_code(err: &tide::Error) -> ExitCode {
+    /// Exit with 3 when the database could not be reached or refused the
+    /// credentials, and 1 for everything else. Usage errors exit with 2.
+    match err.downcast_ref::<AppError>() {
+        Some(AppError::MySql(_) | AppError::Sqlite(_) | AppError::Unauthorized(_)) => {
+            ExitCode::from(constants::EXIT_CODE_DATABASE)
+        }
+        _ => ExitCode::FAILURE,
+    }
+}
+
+#[async_std::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    if cli.command().interactive() {
+        tide::log::start();
+    }
+
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code(&e)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> tide::Result<ExitCode> {
+    /// Run the chosen subcommand.
+    let command = cli.command();
+    let config_path = cli
+        .config
+        .clone()
+        .unwrap_or(constants::CONFIG_FILE.to_owned());
+    let config_filepath = Path::new(&config_path);
+    let credentials_filepath = Path::new(constants::CREDENTIALS_FILE);
+    let env_configured = std::env::vars().any(|(name, _)| name.starts_with(constants::ENV_PREFIX));
+
+    let file_exists = config_filepath.exists() || cli.config.is_some();
+    let mut config = if file_exists {
+        // if a config file exists or was asked for, read it
+        Config::load(config_filepath)?
+    } else {
+        Config::default()
+    };
+    // without one, write one, unless configured from the environment or
+    // there is nobody to prompt
+    let first_run = !file_exists && !env_configured && command.interactive();
+
+    if first_run || matches!(command, Command::Setup | Command::Once) {
+        // `setup` and `once` ask for the main settings again, keeping the
+        // rest of the file
+        config.prompt(&mut io::stdin().lock())?;
+
+        if first_run || *command == Command::Setup {
+            write_config(&config, &config_path)?;
+        }
+    }
+
+    // the environment overrides the file, but is never saved into it
+    config.apply_env(|name| var(name).ok())?;
+
+    if let Command::Config {
+        command: ConfigCommand::Check,
+    } = command
+    {
+        // `config check` prints the settings the server would run with
+        let credentials = match config.backend {
+            Backend::MySql => saved_credentials(credentials_filepath)?,
+            Backend::Sqlite => None,
+        };
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "config": config,
+                "credentials": credentials.map(|credentials| credentials.masked()),
+            }))?
+        );
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let store: Arc<dyn Store> = match config.backend {
+        // a local SQLite file needs no credentials
+        Backend::Sqlite => Arc::new(SqliteStore::open(&config.sqlite_path)?),
+        Backend::MySql => Arc::new(MySqlStore::new(
+            &mysql_credentials(credentials_filepath, command)?,
+            &config.pool,
+        )?),
+    };
+
+    match command {
+        Command::Migrate { dry_run } => {
+            // apply (or list) pending migrations and exit
+            let applied = migrations::run(store.as_ref(), *dry_run)?;
+
+            if applied.is_empty() {
+                println!("{}", constants::SCHEMA_UP_TO_DATE_MSG);
+            }
+
+            for migration in applied {
+                if *dry_run {
+                    println!("Pending {:03} {}", migration.version, migration.name);
+
+                    for statement in migration.statements(&config.backend) {
+                        println!("{}", statement.trim());
+                    }
+                } else {
+                    println!("Applied {:03} {}", migration.version, migration.name);
+                }
+            }
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::CheckDb => {
+            // report on the schema without changing it
+            let pending = migrations::pending(store.as_ref())?;
+
+            if !pending.is_empty() {
+                for migration in pending {
+                    println!("Pending {:03} {}", migration.version, migration.name);
+                }
+                println!("{}", constants::SCHEMA_PENDING_MSG);
+
+                return Ok(ExitCode::FAILURE);
+            }
+
+            migrations::verify(store.as_ref())?;
+            println!("{}", constants::SCHEMA_UP_TO_DATE_MSG);
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    // bring the schema up to date before touching any data
+    migrations::run(store.as_ref(), false)?;
+
+    match command {
+        Command::Export { path } => {
+            let json = serde_json::to_string_pretty(&archive::export(store.as_ref())?)?;
+
+            match path {
+                Some(path) => std::fs::write(path, json + "\n")?,
+                None => println!("{}", json),
+            }
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::Import { path } => {
+            let archive = read_json::<Archive>(path)?;
+            archive::import(store.as_ref(), &archive)?;
+            println!(
+                "Imported {} categories and {} items.",
+                archive.categories.len(),
+                archive.items.len()
+            );
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::User {
+            command: UserCommand::Add { role },
+        } => {
+            let user = auth::add_user(store.as_ref(), *role)?;
+            println!("Added {} \"{}\".", user.role, user.username);
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    if config.require_login {
+        // nobody could log in to create the first account from the web UI
+        auth::create_first_admin(store.as_ref(), io::stdin().is_terminal())?;
+    }
+
+    // set up listeners while the config is still at hand
+    let listener = listeners(&config)?;
+
+    // flag items whose preventive maintenance is due
+    schedule::watch(store.clone());
+
+    // we're using tera for templating
+    let mut tera = Tera::new("templates/**/*")?;
+    tera.autoescape_on(vec!["html"]);
+
+    let mut state = State::new(tera, config, store);
+    let mut app = build_app(state);
 
     // run the application
-    app.listen(format!("0.0.0.0:{}", port)).await?;
+    app.listen(listener).await?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tide::http::{Method, Request, Response, Url};
+
+    use super::*;
+
+    fn test_app_with(require_login: bool) -> tide::Server<State> {
+        let store = SqliteStore::open_in_memory().unwrap();
+        migrations::run(&store, false).unwrap();
+
+        if require_login {
+            // one account per role, named after it
+            for role in [
+                Role::Viewer,
+                Role::Technician,
+                Role::Supervisor,
+                Role::Admin,
+            ] {
+                let mut user = User::new(role.to_string(), "correct horse", role).unwrap();
+                store.insert_user(&mut user).unwrap();
+            }
+        }
+
+        let config = Config {
+            port: 80,
+            bind: vec!["localhost".parse().unwrap()],
+            backend: Backend::Sqlite,
+            sqlite_path: String::new(),
+            pool: PoolConfig::default(),
+            require_login,
+            author: "Front Desk".to_owned(),
+            tls: None,
+        };
+        let tera = Tera::new("templates/**/*").unwrap();
+
+        build_app(State::new(tera, config, Arc::new(store)))
+    }
+
+    fn test_app() -> tide::Server<State> {
+        test_app_with(false)
+    }
+
+    async fn send_raw(
+        app: &tide::Server<State>,
+        cookie: Option<&str>,
+        csrf_token: Option<&str>,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> Response {
+        let url = Url::parse("http://localhost/").unwrap().join(path).unwrap();
+        let mut req = Request::new(method, url);
+        req.set_body(body);
+
+        if let Some(cookie) = cookie {
+            req.insert_header("Cookie", cookie);
+        }
+        if let Some(csrf_token) = csrf_token {
+            req.insert_header("X-CSRF-Token", csrf_token);
+        }
+
+        app.respond(req).await.unwrap()
+    }
+
+    async fn open_page(
+        app: &tide::Server<State>,
+        cookie: Option<&str>,
+        path: &str,
+    ) -> (String, String) {
+        // load a page like a browser would, keeping its session cookie
+        // and the CSRF token it was rendered with
+        let mut res = send_raw(app, cookie, None, Method::Get, path, "").await;
+        let cookie = match res.header("Set-Cookie") {
+            Some(_) => session_cookie(&res),
+            None => cookie.unwrap().to_owned(),
+        };
+        let body = res.body_string().await.unwrap();
+        let csrf_token = body
+            .split(r#"<meta name="csrf-token" content=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_owned();
+
+        (cookie, csrf_token)
+    }
+
+    async fn send_as(
+        app: &tide::Server<State>,
+        cookie: Option<&str>,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> Response {
+        if method == Method::Get {
+            return send_raw(app, cookie, None, method, path, body).await;
+        }
+
+        let (cookie, csrf_token) = open_page(app, cookie, "/").await;
+        send_raw(app, Some(&cookie), Some(&csrf_token), method, path, body).await
+    }
+
+    async fn send_with_token(
+        app: &tide::Server<State>,
+        token: &str,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> Response {
+        let url = Url::parse("http://localhost/").unwrap().join(path).unwrap();
+        let mut req = Request::new(method, url);
+        req.set_body(body);
+        req.insert_header("Authorization", format!("Bearer {}", token));
+
+        app.respond(req).await.unwrap()
+    }
+
+    async fn send(app: &tide::Server<State>, method: Method, path: &str, body: &str) -> Response {
+        send_as(app, None, method, path, body).await
+    }
+
+    async fn login(app: &tide::Server<State>, username: &str, password: &str) -> Response {
+        let url = Url::parse("http://localhost/login").unwrap();
+        let mut req = Request::new(Method::Post, url);
+        req.set_body(
+            tide::Body::from_form(&json!({
+                "username": username,
+                "password": password,
+            }))
+            .unwrap(),
+        );
+
+        app.respond(req).await.unwrap()
+    }
+
+    fn session_cookie(res: &Response) -> String {
+        // only the `name=value` part is sent back
+        res["Set-Cookie"]
+            .as_str()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_owned()
+    }
+
+    /// A user logged in to a test app, sending requests with their session.
+    struct Session<'a> {
+        app: &'a tide::Server<State>,
+        cookie: String,
+    }
+
+    impl Session<'_> {
+        async fn send(&self, method: Method, path: &str, body: &str) -> Response {
+            send_as(self.app, Some(&self.cookie), method, path, body).await
+        }
+    }
+
+    async fn as_role<'a>(app: &'a tide::Server<State>, username: &str) -> Session<'a> {
+        // the accounts `test_app_with` makes share a password
+        Session {
+            app,
+            cookie: session_cookie(&login(app, username, "correct horse").await),
+        }
+    }
+
+    #[test]
+    fn config_checks_ports_and_bind_addresses() {
+        let config = |json: &str| serde_json::from_str::<Config>(json).map_err(|e| e.to_string());
+
+        let parsed = config(
+            r#"{"port": 8080, "bind": ["0.0.0.0", "::", "localhost:8081", "[::1]:8082",
+            "unix:/run/maintenance_tracker.sock"]}"#,
+        )
+        .unwrap();
+        let addrs = parsed
+            .bind
+            .iter()
+            .map(|bind| bind.socket_addr(parsed.port).map(|addr| addr.to_string()))
+            .collect::<Vec<Option<String>>>();
+        assert_eq!(
+            addrs,
+            [
+                Some("0.0.0.0:8080".to_owned()),
+                Some("[::]:8080".to_owned()),
+                Some("127.0.0.1:8081".to_owned()),
+                Some("[::1]:8082".to_owned()),
+                None,
+            ]
+        );
+
+        // listening everywhere stays the default
+        let parsed = config(r#"{"port": 80}"#).unwrap();
+        assert_eq!(parsed.bind[0].to_string(), "0.0.0.0");
+
+        for (json, error) in [
+            (r#"{"port": 99999}"#, "Port 99999 is out of range"),
+            (r#"{"port": 0}"#, "Port 0 is out of range"),
+            (r#"{"port": 80, "bind": ["localhost:70000"]}"#, "Port 70000"),
+            (
+                r#"{"port": 80, "bind": ["plant-floor"]}"#,
+                "Invalid bind address",
+            ),
+            (
+                r#"{"port": 443, "tls": {"cert_path": "c", "key_path": "k", "redirect_port": -1}}"#,
+                "Port -1 is out of range",
+            ),
+        ] {
+            let e = config(json).unwrap_err();
+            assert!(e.contains(error), "{}: {}", json, e);
+        }
+    }
+
+    #[test]
+    fn first_admin_is_not_prompted_for_without_a_terminal() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        migrations::run(&store, false).unwrap();
+
+        let e = auth::create_first_admin(&store, false).unwrap_err();
+        assert!(matches!(e, AppError::Input(_)));
+        assert!(e.to_string().contains("user add admin"));
+        assert_eq!(exit_code(&tide::Error::from(e)), ExitCode::FAILURE);
+
+        // accounts without the highest role, such as those upgraded from
+        // before roles existed, still leave an admin to be made
+        let mut user = User::new("tech".to_owned(), "correct horse", Role::Technician).unwrap();
+        store.insert_user(&mut user).unwrap();
+        assert!(auth::create_first_admin(&store, false).is_err());
+
+        // once an admin exists there is nothing to ask
+        let mut user = User::new("admin".to_owned(), "correct horse", Role::Admin).unwrap();
+        store.insert_user(&mut user).unwrap();
+        auth::create_first_admin(&store, false).unwrap();
+    }
+
+    #[test]
+    fn command_line_chooses_subcommand() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args);
+
+        assert_eq!(*parse(&["mt"]).unwrap().command(), Command::Serve);
+        assert_eq!(
+            *parse(&["mt", "migrate", "--dry-run"]).unwrap().command(),
+            Command::Migrate { dry_run: true }
+        );
+        assert_eq!(
+            *parse(&["mt", "user", "add"]).unwrap().command(),
+            Command::User {
+                command: UserCommand::Add {
+                    role: Role::Technician
+                }
+            }
+        );
+
+        // flags work in any position
+        let cli = parse(&["mt", "check-db", "--config", "plant.toml"]).unwrap();
+        assert_eq!(cli.config.as_deref(), Some("plant.toml"));
+        assert!(!cli.command().interactive());
+
+        assert!(parse(&["mt", "user", "add", "wizard"]).is_err());
+        assert!(parse(&["mt", "-s"]).is_err());
+    }
+
+    #[test]
+    fn setup_keeps_the_rest_of_the_config_file() {
+        let path = std::env::temp_dir().join(format!("mt-setup-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"port": 8443, "bind": ["127.0.0.1", "[::1]:9443"], "require_login": false,
+            "tls": {"cert_path": "cert.pem", "key_path": "key.pem"}}"#,
+        )
+        .unwrap();
+
+        // a new port, the sqlite backend and its default file
+        let mut config = Config::load(&path).unwrap();
+        config.prompt(&mut "8444\nsqlite\n\n".as_bytes()).unwrap();
+        write_config(&config, path.to_str().unwrap()).unwrap();
+
+        let saved = Config::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.port, 8444);
+        assert_eq!(saved.backend, Backend::Sqlite);
+        assert_eq!(saved.sqlite_path, constants::DEFAULT_SQLITE_PATH);
+        assert_eq!(saved.bind, config.bind);
+        assert_eq!(saved.bind.len(), 2);
+        assert!(!saved.require_login);
+        assert_eq!(saved.tls.unwrap().cert_path, "cert.pem");
+
+        // nobody to answer is an error, not a config of defaults
+        let e = Config::default().prompt(&mut "".as_bytes()).unwrap_err();
+        assert!(matches!(e, AppError::Input(_)), "{}", e);
+    }
+
+    #[test]
+    fn environment_overrides_config_file() {
+        let path = std::env::temp_dir().join(format!("mt-config-{}.toml", std::process::id()));
+        let file_config = Config {
+            port: 8080,
+            pool: PoolConfig {
+                max_connections: 4,
+                ..PoolConfig::default()
+            },
+            ..Config::default()
+        };
+        write_config(&file_config, path.to_str().unwrap()).unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.port, 8080);
+
+        let env = BTreeMap::from([
+            ("MT_PORT", "9090"),
+            ("MT_BIND", "127.0.0.1, ::1"),
+            ("MT_BACKEND", "sqlite"),
+            ("MT_REQUIRE_LOGIN", "no"),
+            ("MT_TLS_CERT_PATH", "cert.pem"),
+            ("MT_TLS_KEY_PATH", "key.pem"),
+            ("MT_SQLITE_PATH", ""),
+        ]);
+        config
+            .apply_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.bind.len(), 2);
+        assert_eq!(config.backend, Backend::Sqlite);
+        assert!(!config.require_login);
+        assert_eq!(config.tls.unwrap().key_path, "key.pem");
+        // left alone: set only in the file, or empty in the environment
+        assert_eq!(config.pool.max_connections, 4);
+        assert_eq!(config.sqlite_path, constants::DEFAULT_SQLITE_PATH);
+
+        let e = Config::default()
+            .apply_env(|name| (name == "MT_PORT").then(|| "99999".to_owned()))
+            .unwrap_err();
+        assert!(e.to_string().starts_with("MT_PORT: Port 99999"), "{}", e);
+
+        let db_env = |name: &str| match name {
+            "MT_DB_USER" => Some("tracker".to_owned()),
+            "MT_DB_PASSWORD" => Some("hunter22".to_owned()),
+            _ => None,
+        };
+        assert!(DbCredentials::from_env(None, db_env).is_err());
+
+        let saved = DbCredentials {
+            user: "root".to_owned(),
+            pass: "saved".to_owned(),
+            db_url: "db.plant.local".to_owned(),
+            db_name: "maintenance".to_owned(),
+        };
+        let credentials = DbCredentials::from_env(Some(saved), db_env)
+            .unwrap()
+            .unwrap();
+        assert_eq!(credentials.user, "tracker");
+        assert_eq!(credentials.db_url, "db.plant.local");
+        assert!(!serde_json::to_string(&credentials.masked())
+            .unwrap()
+            .contains("hunter22"));
+
+        // Docker secrets end with a line break that is not part of the password
+        let secret = std::env::temp_dir().join(format!("mt-secret-{}", std::process::id()));
+        std::fs::write(&secret, "s3cret pass\n").unwrap();
+        let secret_env = |name: &str| match name {
+            "MT_DB_USER" => Some("tracker".to_owned()),
+            "MT_DB_PASSWORD_FILE" => Some(secret.display().to_string()),
+            "MT_DB_HOST" => Some("db.plant.local".to_owned()),
+            "MT_DB_NAME" => Some("maintenance".to_owned()),
+            _ => None,
+        };
+        let from_file = DbCredentials::from_env(None, secret_env);
+        let both = DbCredentials::from_env(None, |name| {
+            secret_env(name).or_else(|| (name == "MT_DB_PASSWORD").then(|| "x".to_owned()))
+        });
+        std::fs::remove_file(&secret).unwrap();
+
+        assert_eq!(from_file.unwrap().unwrap().pass, "s3cret pass");
+        assert!(both.is_err());
+    }
+
+    #[async_std::test]
+    async fn history_rejects_hostile_ids() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "O'Brien", "removed": false}"#,
+        )
+        .await;
+        send(
+            &app,
+            Method::Post,
+            "/add/item",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+
+        for path in [
+            "/history/1%20OR%201=1",
+            "/history/1;DROP%20TABLE%20entry",
+            "/history/-1",
+        ] {
+            let res = send(&app, Method::Get, path, "").await;
+            assert_eq!(res.status(), tide::StatusCode::BadRequest, "{}", path);
+        }
+
+        let mut res = send(&app, Method::Get, "/history/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+        assert_eq!(
+            res.body_string()
+                .await
+                .unwrap()
+                .matches("class=\"entry\"")
+                .count(),
+            1
+        );
+    }
+
+    #[async_std::test]
+    async fn bulk_save_is_all_or_nothing() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "Vehicles", "removed": false}"#,
+        )
+        .await;
+        send(
+            &app,
+            Method::Post,
+            "/add/item",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+        send(
+            &app,
+            Method::Post,
+            "/add/item",
+            r#"{"title": "Truck", "category_id": 1}"#,
+        )
+        .await;
+
+        // the second item points at a category that does not exist
+        let body = r#"[
+            {"id": 1, "title": "Car", "category_id": 1,
+             "details": {"status": 2, "cost": 100, "note": "flat", "visible": true, "removed": false}},
+            {"id": 2, "title": "Truck", "category_id": 99,
+             "details": {"status": 1, "cost": null, "note": null, "visible": true, "removed": false}}
+        ]"#;
+        let mut res = send(&app, Method::Post, "/", body).await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+
+        let report: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(report["saved"], false);
+        assert!(report["items"][0]["error"].is_null());
+        assert!(report["items"][1]["error"].is_string());
+
+        let mut res = send(&app, Method::Get, "/history/1", "").await;
+        assert_eq!(
+            res.body_string()
+                .await
+                .unwrap()
+                .matches("class=\"entry\"")
+                .count(),
+            1
+        );
+    }
+
+    #[async_std::test]
+    async fn quoted_category_titles_are_accepted() {
+        let app = test_app();
+
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "O'Brien", "removed": false}"#,
+        )
+        .await;
+        assert_eq!(res.body_string().await.unwrap(), "OK");
+
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "O'Brien", "removed": false}"#,
+        )
+        .await;
+        assert!(res.body_string().await.unwrap().contains("already exists"));
+    }
+
+    #[async_std::test]
+    async fn errors_map_to_statuses() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "Vehicles", "removed": false}"#,
+        )
+        .await;
+
+        let cases = [
+            (Method::Get, "/history/99", "", tide::StatusCode::NotFound),
+            (
+                Method::Post,
+                "/add/category",
+                r#"{"title": "Vehicles", "removed": false}"#,
+                tide::StatusCode::Conflict,
+            ),
+            (
+                Method::Post,
+                "/add/category",
+                "not json",
+                tide::StatusCode::BadRequest,
+            ),
+            (
+                Method::Post,
+                "/add/item",
+                r#"{"title": "", "category_id": 1}"#,
+                tide::StatusCode::UnprocessableEntity,
+            ),
+        ];
+
+        for (method, path, body, status) in cases {
+            let mut res = send(&app, method, path, body).await;
+            assert_eq!(res.status(), status, "{} {}", method, path);
+
+            let error: serde_json::Value = res.body_json().await.unwrap();
+            assert!(error["error"].is_string(), "{} {}", method, path);
+        }
+    }
+
+    #[async_std::test]
+    async fn api_resources_round_trip() {
+        let app = test_app();
+
+        let res = send(
+            &app,
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        assert_eq!(res["Location"], "/api/v1/categories/1");
+
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let item: Item = res.body_json().await.unwrap();
+        assert_eq!(item.id, Some(1));
+        assert_eq!(item.details, Some(ItemDetails::new()));
+
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 2, "cost": 100, "note": "flat", "visible": true, "removed": false}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(entry["item_id"], 1);
+        assert_eq!(entry["status"], 2);
+
+        let mut res = send(&app, Method::Get, "/api/v1/items/1", "").await;
+        let item: Item = res.body_json().await.unwrap();
+        assert_eq!(item.details.unwrap().status, 2);
+
+        let mut res = send(
+            &app,
+            Method::Patch,
+            "/api/v1/entries/2",
+            r#"{"note": null}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert!(entry["note"].is_null());
+        assert_eq!(entry["cost"], 100);
+
+        let mut res = send(
+            &app,
+            Method::Patch,
+            "/api/v1/items/1",
+            r#"{"title": "Sedan"}"#,
+        )
+        .await;
+        let item: Item = res.body_json().await.unwrap();
+        assert_eq!(item.title, "Sedan");
+
+        // history is corrected, never deleted
+        let res = send(&app, Method::Delete, "/api/v1/entries/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::Conflict);
+        let res = send(&app, Method::Delete, "/api/v1/entries/99", "").await;
+        assert_eq!(res.status(), tide::StatusCode::NotFound);
+
+        let mut res = send(&app, Method::Get, "/api/v1/items/1/entries", "").await;
+        let entries: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert_eq!(entries.len(), 3);
+
+        let res = send(&app, Method::Delete, "/api/v1/categories/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::NoContent);
+        let mut res = send(&app, Method::Get, "/api/v1/categories/1", "").await;
+        let category: Category = res.body_json().await.unwrap();
+        assert!(category.removed);
+    }
+
+    #[async_std::test]
+    async fn api_rejects_bad_requests() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+
+        let cases = [
+            (
+                Method::Get,
+                "/api/v1/items/1",
+                "",
+                tide::StatusCode::NotFound,
+            ),
+            (
+                Method::Get,
+                "/api/v1/items/x",
+                "",
+                tide::StatusCode::BadRequest,
+            ),
+            (
+                Method::Post,
+                "/api/v1/categories",
+                r#"{"title": "Vehicles"}"#,
+                tide::StatusCode::Conflict,
+            ),
+            (
+                Method::Post,
+                "/api/v1/items",
+                r#"{"title": "Car", "category_id": 9}"#,
+                tide::StatusCode::UnprocessableEntity,
+            ),
+            (
+                Method::Patch,
+                "/api/v1/categories/1",
+                r#"{"colour": "red"}"#,
+                tide::StatusCode::BadRequest,
+            ),
+            (
+                Method::Delete,
+                "/api/v1/entries/5",
+                "",
+                tide::StatusCode::NotFound,
+            ),
+        ];
+
+        for (method, path, body, status) in cases {
+            let res = send(&app, method, path, body).await;
+            assert_eq!(res.status(), status, "{} {}", method, path);
+        }
+    }
+
+    #[async_std::test]
+    async fn changes_require_login() {
+        let app = test_app_with(true);
+        let category = r#"{"title": "Vehicles", "removed": false}"#;
+
+        let res = send(&app, Method::Post, "/add/category", category).await;
+        assert_eq!(res.status(), tide::StatusCode::Unauthorized);
+        let res = send(&app, Method::Delete, "/api/v1/categories/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::Unauthorized);
+        let res = send(&app, Method::Get, "/", "").await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+
+        let res = login(&app, "admin", "wrong password").await;
+        assert_eq!(res.status(), tide::StatusCode::Unauthorized);
+        assert!(res.header("Set-Cookie").is_none());
+
+        let res = login(&app, "admin", "correct horse").await;
+        assert_eq!(res.status(), tide::StatusCode::SeeOther);
+        let cookie = session_cookie(&res);
+
+        let mut res = send_as(&app, Some(&cookie), Method::Post, "/add/category", category).await;
+        assert_eq!(res.body_string().await.unwrap(), "OK");
+
+        let res = send_as(&app, Some(&cookie), Method::Post, "/logout", "").await;
+        assert_eq!(res.status(), tide::StatusCode::SeeOther);
+
+        let res = send_as(&app, Some(&cookie), Method::Post, "/add/category", category).await;
+        assert_eq!(res.status(), tide::StatusCode::Unauthorized);
+    }
+
+    #[async_std::test]
+    async fn changes_require_csrf_token() {
+        let app = test_app();
+        let category = r#"{"title": "Vehicles", "removed": false}"#;
+        let (cookie, csrf_token) = open_page(&app, None, "/").await;
+        let (_, other_token) = open_page(&app, None, "/").await;
+        assert_ne!(csrf_token, other_token);
+
+        for token in [None, Some("forged"), Some(other_token.as_str())] {
+            for (method, path) in [
+                (Method::Post, "/add/category"),
+                (Method::Post, "/"),
+                (Method::Delete, "/api/v1/categories/1"),
+            ] {
+                let res = send_raw(&app, Some(&cookie), token, method, path, category).await;
+                assert_eq!(
+                    res.status(),
+                    tide::StatusCode::Forbidden,
+                    "{:?} {}",
+                    token,
+                    path
+                );
+            }
+        }
+
+        let mut res = send_raw(
+            &app,
+            Some(&cookie),
+            Some(&csrf_token),
+            Method::Post,
+            "/add/category",
+            category,
+        )
+        .await;
+        assert_eq!(res.body_string().await.unwrap(), "OK");
+
+        // plain HTML forms send the token as a field
+        let logout = |csrf_token: &str| {
+            let url = Url::parse("http://localhost/logout").unwrap();
+            let mut req = Request::new(Method::Post, url);
+            req.insert_header("Cookie", cookie.as_str());
+            req.set_body(tide::Body::from_form(&json!({ "csrf_token": csrf_token })).unwrap());
+            req
+        };
+        let res: Response = app.respond(logout("forged")).await.unwrap();
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+        let res: Response = app.respond(logout(&csrf_token)).await.unwrap();
+        assert_eq!(res.status(), tide::StatusCode::SeeOther);
+    }
+
+    #[async_std::test]
+    async fn roles_limit_changes() {
+        let app = test_app_with(true);
+        let mut cookies = BTreeMap::new();
+        for role in ["viewer", "technician", "supervisor", "admin"] {
+            cookies.insert(
+                role,
+                session_cookie(&login(&app, role, "correct horse").await),
+            );
+        }
 
-    Ok(())
+        let res = send_as(
+            &app,
+            Some(&cookies["technician"]),
+            Method::Post,
+            "/add/category",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+
+        send_as(
+            &app,
+            Some(&cookies["supervisor"]),
+            Method::Post,
+            "/add/category",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        send_as(
+            &app,
+            Some(&cookies["supervisor"]),
+            Method::Post,
+            "/add/item",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+
+        let item = |status: u32, cost: u32| {
+            format!(
+                r#"{{"id": 1, "title": "Car", "category_id": 1, "details":
+                {{"status": {}, "cost": {}, "note": "", "visible": true, "removed": false}}}}"#,
+                status, cost
+            )
+        };
+
+        let cases = [
+            ("viewer", item(1, 0), tide::StatusCode::Forbidden),
+            ("technician", item(1, 0), tide::StatusCode::Ok),
+            ("technician", item(1, 500), tide::StatusCode::Forbidden),
+            ("supervisor", item(1, 500), tide::StatusCode::Ok),
+        ];
+
+        for (role, body, status) in cases {
+            let res = send_as(
+                &app,
+                Some(&cookies[role]),
+                Method::Post,
+                "/update/item",
+                &body,
+            )
+            .await;
+            assert_eq!(res.status(), status, "{} {}", role, body);
+        }
+
+        for (role, status) in [
+            ("supervisor", tide::StatusCode::Forbidden),
+            ("admin", tide::StatusCode::NoContent),
+        ] {
+            let res = send_as(
+                &app,
+                Some(&cookies[role]),
+                Method::Delete,
+                "/api/v1/items/1",
+                "",
+            )
+            .await;
+            assert_eq!(res.status(), status, "{}", role);
+        }
+    }
+
+    #[async_std::test]
+    async fn overdue_items_are_listed() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        for title in ["Car", "Truck"] {
+            send(
+                &app,
+                Method::Post,
+                "/api/v1/items",
+                &json!({"title": title, "category_id": 1}).to_string(),
+            )
+            .await;
+        }
+
+        let res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 2, "due_date": "2000-01-01", "priority": 1, "visible": true, "removed": false}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/items/2/entries",
+            r#"{"status": 1, "due_date": "2999-01-01", "visible": true, "removed": false}"#,
+        )
+        .await;
+
+        for details in [
+            r#"{"status": 1, "due_date": "01/02/2000", "visible": true, "removed": false}"#,
+            r#"{"status": 1, "priority": 4, "visible": true, "removed": false}"#,
+        ] {
+            let res = send(&app, Method::Post, "/api/v1/items/2/entries", details).await;
+            assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+        }
+
+        let mut res = send(&app, Method::Get, "/api/v1/due", "").await;
+        let due: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0]["item"]["title"], "Car");
+        assert_eq!(due[0]["item"]["details"]["priority"], 1);
+        assert_eq!(due[0]["overdue"], true);
+
+        let mut res = send(&app, Method::Get, "/due", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains("Car"));
+        assert!(!body.contains("Truck"));
+
+        let mut res = send(&app, Method::Get, "/", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains("Due (1)"));
+        assert!(body.contains(r#"due-date="2999-01-01""#));
+
+        // the date can be corrected like any other field of an entry
+        send(
+            &app,
+            Method::Patch,
+            "/api/v1/entries/3",
+            r#"{"due_date": null}"#,
+        )
+        .await;
+        let mut res = send(&app, Method::Get, "/api/v1/due", "").await;
+        let due: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert!(due.is_empty());
+    }
+
+    #[async_std::test]
+    async fn schedules_flag_due_items() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Forklift", "category_id": 1}"#,
+        )
+        .await;
+
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/schedules",
+            r#"{"title": "Inspect mast", "kind": "usage", "period": 500, "grace": 50}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        assert_eq!(res["Location"], "/api/v1/schedules/1");
+        let status: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(status["due_meter"], 500);
+        assert_eq!(status["status"], 0);
+
+        // a reading past the due point flags the item on the next check
+        send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "meter": 520, "visible": true, "removed": false}"#,
+        )
+        .await;
+        let store = app.state().store.clone();
+        assert_eq!(schedule::flag_due(store.as_ref()).unwrap(), 1);
+        assert_eq!(schedule::flag_due(store.as_ref()).unwrap(), 0);
+
+        let item = store.get_item(1).unwrap();
+        let details = item.details.unwrap();
+        assert_eq!(details.status, 1);
+        assert_eq!(
+            details.note.as_deref(),
+            Some("Preventive maintenance: Inspect mast due.")
+        );
+        let mut res = send(&app, Method::Get, "/api/v1/items/1/entries", "").await;
+        let entries: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert_eq!(
+            entries.last().unwrap()["author"],
+            constants::SCHEDULE_AUTHOR
+        );
+
+        // doing the work restarts the count from its reading
+        let mut res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "schedule_id": 1, "meter": 530, "visible": true, "removed": false}"#,
+        )
+        .await;
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(entry["schedule_id"], 1);
+        let mut res = send(&app, Method::Get, "/api/v1/schedules/1", "").await;
+        let status: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(status["due_meter"], 1030);
+        assert_eq!(status["status"], 0);
+        assert!(status["last_done"].is_string());
+
+        let res = send(
+            &app,
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "schedule_id": 9, "visible": true, "removed": false}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+
+        let res = send(&app, Method::Delete, "/api/v1/schedules/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::NoContent);
+        let mut res = send(&app, Method::Get, "/api/v1/schedules", "").await;
+        let statuses: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert!(statuses.is_empty());
+    }
+
+    #[async_std::test]
+    async fn work_orders_follow_their_lifecycle() {
+        let app = test_app_with(true);
+        let supervisor = session_cookie(&login(&app, "supervisor", "correct horse").await);
+        let technician = session_cookie(&login(&app, "technician", "correct horse").await);
+        let as_supervisor = |method: Method, path: &'static str, body: &'static str| {
+            send_as(&app, Some(&supervisor), method, path, body)
+        };
+        let as_technician = |method: Method, path: &'static str, body: &'static str| {
+            send_as(&app, Some(&technician), method, path, body)
+        };
+
+        as_supervisor(
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        as_supervisor(Method::Post, "/api/v1/categories", r#"{"title": "Tools"}"#).await;
+        as_supervisor(
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Forklift", "category_id": 1}"#,
+        )
+        .await;
+        as_supervisor(
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Drill", "category_id": 2}"#,
+        )
+        .await;
+
+        let mut res = as_technician(
+            Method::Post,
+            "/api/v1/items/1/work-orders",
+            r#"{"problem": "Mast will not lift", "status": 2}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        assert_eq!(res["Location"], "/api/v1/work-orders/1");
+        let order: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(order["state"], "open");
+        as_technician(
+            Method::Post,
+            "/api/v1/items/2/work-orders",
+            r#"{"problem": "Chuck slips"}"#,
+        )
+        .await;
+
+        // assigning is a supervisor's call, to an account that exists
+        let res = as_technician(
+            Method::Post,
+            "/api/v1/work-orders/1/assign",
+            r#"{"assignee_id": 2}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+        let res = as_supervisor(
+            Method::Post,
+            "/api/v1/work-orders/1/assign",
+            r#"{"assignee_id": 99}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+        let mut res = as_supervisor(
+            Method::Post,
+            "/api/v1/work-orders/1/assign",
+            r#"{"assignee_id": 2}"#,
+        )
+        .await;
+        let order: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(order["state"], "assigned");
+        assert_eq!(order["assignee"], "technician");
+
+        // costs are a supervisor's to set, by hand or through the catalogue
+        let labour = r#"{"kind": "labour", "description": "Replace hose", "quantity": 2, "unit_cost": 4500}"#;
+        let res = as_technician(Method::Post, "/api/v1/work-orders/1/lines", labour).await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+        for line in [
+            labour,
+            r#"{"kind": "part", "description": "Hydraulic hose", "quantity": 1, "unit_cost": 3000}"#,
+        ] {
+            let res = as_supervisor(Method::Post, "/api/v1/work-orders/1/lines", line).await;
+            assert_eq!(res.status(), tide::StatusCode::Created);
+        }
+        let res = as_technician(
+            Method::Post,
+            "/api/v1/work-orders/1/lines",
+            r#"{"kind": "labour", "description": "Test lift", "quantity": 1}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let res = as_technician(
+            Method::Post,
+            "/api/v1/work-orders/1/lines",
+            r#"{"kind": "part", "description": "", "quantity": 1}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+
+        // the work order's cost is added to what the item already cost
+        as_supervisor(
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 2, "cost": 500, "note": "Towed in", "visible": true, "removed": false}"#,
+        )
+        .await;
+
+        let mut res = as_technician(
+            Method::Post,
+            "/api/v1/work-orders/1/close",
+            r#"{"resolution": "Hose replaced"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+        let order: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(order["state"], "closed");
+        assert_eq!(order["cost"], 12000);
+        assert_eq!(order["lines"].as_array().unwrap().len(), 3);
+        assert!(order["closed"].is_string());
+
+        // each step is on the item's history, the last one with the cost
+        let mut res = send(&app, Method::Get, "/api/v1/items/1/entries", "").await;
+        let mut entries: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        entries.retain(|entry| entry["work_order_id"] == 1);
+        let notes = entries
+            .iter()
+            .map(|entry| entry["note"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            notes,
+            [
+                "Opened work order: Mast will not lift",
+                "Opened work order: Mast will not lift",
+                "Closed work order: Hose replaced",
+            ]
+        );
+        assert_eq!(
+            entries[1]["assignment"],
+            "Assigned work order to technician"
+        );
+        assert_eq!(entries[0]["status"], 2);
+        assert_eq!(entries[2]["status"], 0);
+        assert_eq!(entries[2]["cost"], 12500);
+
+        for (query, ids) in [
+            ("", vec![1, 2]),
+            ("?state=closed", vec![1]),
+            ("?state=open", vec![2]),
+            ("?item_id=2", vec![2]),
+            ("?category_id=1", vec![1]),
+            ("?state=open&category_id=1", vec![]),
+        ] {
+            let mut res = send(
+                &app,
+                Method::Get,
+                &format!("/api/v1/work-orders{}", query),
+                "",
+            )
+            .await;
+            let orders: Vec<serde_json::Value> = res.body_json().await.unwrap();
+            let found = orders
+                .iter()
+                .map(|order| order["id"].as_u64().unwrap())
+                .collect::<Vec<u64>>();
+            assert_eq!(found, ids, "{}", query);
+        }
+        let res = send(&app, Method::Get, "/api/v1/work-orders?state=lost", "").await;
+        assert_eq!(res.status(), tide::StatusCode::BadRequest);
+
+        // closed work orders are history
+        for (path, body) in [
+            ("/api/v1/work-orders/1/close", r#"{"resolution": "Again"}"#),
+            (
+                "/api/v1/work-orders/1/lines",
+                r#"{"kind": "part", "description": "Hose", "quantity": 1}"#,
+            ),
+            ("/api/v1/work-orders/1/assign", r#"{"assignee_id": 3}"#),
+        ] {
+            let res = as_supervisor(Method::Post, path, body).await;
+            assert_eq!(res.status(), tide::StatusCode::Conflict, "{}", path);
+        }
+    }
+
+    #[async_std::test]
+    async fn items_and_work_can_be_assigned() {
+        let app = test_app_with(true);
+        let supervisor = session_cookie(&login(&app, "supervisor", "correct horse").await);
+        let technician = session_cookie(&login(&app, "technician", "correct horse").await);
+        let as_supervisor = |method: Method, path: &'static str, body: &'static str| {
+            send_as(&app, Some(&supervisor), method, path, body)
+        };
+        let as_technician = |method: Method, path: &'static str, body: &'static str| {
+            send_as(&app, Some(&technician), method, path, body)
+        };
+
+        as_supervisor(
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        for body in [
+            r#"{"title": "Forklift", "category_id": 1}"#,
+            r#"{"title": "Crane", "category_id": 1}"#,
+        ] {
+            as_supervisor(Method::Post, "/api/v1/items", body).await;
+        }
+
+        let res = as_technician(
+            Method::Post,
+            "/api/v1/items/1/assign",
+            r#"{"assignee_id": 2}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+        let res = as_supervisor(
+            Method::Post,
+            "/api/v1/items/1/assign",
+            r#"{"assignee_id": 99}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+        let mut res = as_supervisor(
+            Method::Post,
+            "/api/v1/items/1/assign",
+            r#"{"assignee_id": 2}"#,
+        )
+        .await;
+        let item: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(item["assignee_id"], 2);
+        assert_eq!(item["assignee"], "technician");
+
+        // saving the item's details leaves its assignee alone
+        let res = as_technician(
+            Method::Post,
+            "/update/item",
+            r#"{"id": 1, "title": "Forklift", "category_id": 1, "assignee_id": null, "details":
+            {"status": 1, "note": "Leaking", "visible": true, "removed": false}}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+        let mut res = send(&app, Method::Get, "/api/v1/items/1", "").await;
+        let item: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(item["assignee"], "technician");
+        assert_eq!(item["details"]["note"], "Leaking");
+
+        let mut res = send(&app, Method::Get, "/api/v1/items/1/entries", "").await;
+        let entries: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert!(entries
+            .iter()
+            .any(|entry| entry["assignment"] == "Assigned to technician"
+                && entry["author"] == "supervisor"));
+
+        as_technician(
+            Method::Post,
+            "/api/v1/items/2/work-orders",
+            r#"{"problem": "Cable frayed"}"#,
+        )
+        .await;
+        as_technician(
+            Method::Post,
+            "/api/v1/items/2/work-orders",
+            r#"{"problem": "Hook bent"}"#,
+        )
+        .await;
+        for path in [
+            "/api/v1/work-orders/1/assign",
+            "/api/v1/work-orders/2/assign",
+        ] {
+            as_supervisor(Method::Post, path, r#"{"assignee_id": 2}"#).await;
+        }
+        as_technician(
+            Method::Post,
+            "/api/v1/work-orders/2/close",
+            r#"{"resolution": "Straightened"}"#,
+        )
+        .await;
+
+        let mut res = as_technician(Method::Get, "/api/v1/my-work", "").await;
+        let work: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(work["items"].as_array().unwrap().len(), 1);
+        assert_eq!(work["items"][0]["title"], "Forklift");
+        assert_eq!(work["work_orders"].as_array().unwrap().len(), 1);
+        assert_eq!(work["work_orders"][0]["problem"], "Cable frayed");
+
+        let mut res = as_supervisor(Method::Get, "/api/v1/my-work", "").await;
+        let work: serde_json::Value = res.body_json().await.unwrap();
+        assert!(work["items"].as_array().unwrap().is_empty());
+
+        let mut res = as_technician(Method::Get, "/my-work", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains("Assigned to technician"));
+        assert!(body.contains("Forklift"));
+        assert!(body.contains("#1 Crane"));
+        assert!(!body.contains("Hook bent"));
+
+        let mut res = as_technician(Method::Get, "/", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains(r#"assignee="technician""#));
+
+        let mut res = as_supervisor(
+            Method::Post,
+            "/api/v1/items/1/assign",
+            r#"{"assignee_id": null}"#,
+        )
+        .await;
+        let item: serde_json::Value = res.body_json().await.unwrap();
+        assert!(item["assignee"].is_null());
+
+        // the fault description stays, the reassignment is only in history
+        assert_eq!(item["details"]["note"], "Leaking");
+        let mut res = send(&app, Method::Get, "/history/1", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains("Unassigned"));
+    }
+
+    #[async_std::test]
+    async fn parts_are_taken_from_stock() {
+        let app = test_app_with(true);
+        let supervisor = as_role(&app, "supervisor").await;
+        let technician = as_role(&app, "technician").await;
+
+        supervisor
+            .send(
+                Method::Post,
+                "/api/v1/categories",
+                r#"{"title": "Vehicles"}"#,
+            )
+            .await;
+        supervisor
+            .send(
+                Method::Post,
+                "/api/v1/items",
+                r#"{"title": "Forklift", "category_id": 1}"#,
+            )
+            .await;
+
+        // the catalogue is a supervisor's to keep
+        let part = r#"{"part_number": "HF-10", "description": "Hydraulic filter", "unit_cost": 1250, "quantity": 5, "reorder_point": 2}"#;
+        let res = technician.send(Method::Post, "/api/v1/parts", part).await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+        let mut res = supervisor.send(Method::Post, "/api/v1/parts", part).await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        assert_eq!(res["Location"], "/api/v1/parts/1");
+        let created: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(created["quantity"], 5);
+        let res = supervisor.send(Method::Post, "/api/v1/parts", part).await;
+        assert_eq!(res.status(), tide::StatusCode::Conflict);
+        supervisor.send(
+            Method::Post,
+            "/api/v1/parts",
+            r#"{"part_number": "HS-20", "description": "Hydraulic hose", "unit_cost": 3000, "quantity": 1}"#,
+        )
+        .await;
+
+        // an entry's parts come out of stock and onto its cost
+        let mut res = technician.send(
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "note": "Filter swapped", "visible": true, "removed": false, "parts": [{"part_id": 1, "quantity": 2}]}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(entry["cost"], 2500);
+        assert_eq!(entry["parts"][0]["unit_cost"], 1250);
+
+        // too few on hand and nothing is taken
+        let res = supervisor.send(
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "cost": 2500, "visible": true, "removed": false, "parts": [{"part_id": 2, "quantity": 1}, {"part_id": 1, "quantity": 4}]}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Conflict);
+        let res = supervisor.send(
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "cost": 2500, "visible": true, "removed": false, "parts": [{"part_id": 9, "quantity": 1}]}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+
+        let mut res = send(&app, Method::Get, "/api/v1/parts/2", "").await;
+        let hose: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(hose["quantity"], 1);
+
+        // a cost too large to record is refused and nothing is taken
+        supervisor.send(
+            Method::Post,
+            "/api/v1/parts",
+            r#"{"part_number": "BB-30", "description": "Ball bearing", "unit_cost": 2, "quantity": 3000000000}"#,
+        )
+        .await;
+        let res = supervisor.send(
+            Method::Post,
+            "/api/v1/items/1/entries",
+            r#"{"status": 0, "cost": 2500, "visible": true, "removed": false, "parts": [{"part_id": 3, "quantity": 3000000000}]}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+        let mut res = send(&app, Method::Get, "/api/v1/parts/3", "").await;
+        let bearing: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(bearing["quantity"], 3000000000u32);
+
+        // a work order's part line is priced and described by the catalogue
+        technician
+            .send(
+                Method::Post,
+                "/api/v1/items/1/work-orders",
+                r#"{"problem": "Mast leaks"}"#,
+            )
+            .await;
+        let mut res = technician
+            .send(
+                Method::Post,
+                "/api/v1/work-orders/1/lines",
+                r#"{"kind": "part", "part_id": 2, "quantity": 1, "unit_cost": 1}"#,
+            )
+            .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let order: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(order["lines"][0]["description"], "Hydraulic hose");
+        assert_eq!(order["lines"][0]["part_id"], 2);
+        assert_eq!(order["cost"], 3000);
+        let res = technician
+            .send(
+                Method::Post,
+                "/api/v1/work-orders/1/lines",
+                r#"{"kind": "part", "part_id": 2, "quantity": 1}"#,
+            )
+            .await;
+        assert_eq!(res.status(), tide::StatusCode::Conflict);
+        let res = technician
+            .send(
+                Method::Post,
+                "/api/v1/work-orders/1/lines",
+                r#"{"kind": "labour", "part_id": 1, "description": "Fit", "quantity": 1}"#,
+            )
+            .await;
+        assert_eq!(res.status(), tide::StatusCode::UnprocessableEntity);
+
+        let mut res = send(&app, Method::Get, "/api/v1/parts/low-stock", "").await;
+        let low: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        let numbers = low
+            .iter()
+            .map(|part| part["part_number"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(numbers, ["HS-20"]);
+
+        let mut res = send(&app, Method::Get, "/low-stock", "").await;
+        let body = res.body_string().await.unwrap();
+        assert!(body.contains("Hydraulic hose"));
+        assert!(!body.contains("Hydraulic filter"));
+
+        // a delivery restocks the part
+        let mut res = supervisor
+            .send(Method::Patch, "/api/v1/parts/2", r#"{"quantity": 6}"#)
+            .await;
+        let hose: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(hose["quantity"], 6);
+        let mut res = send(&app, Method::Get, "/api/v1/parts/low-stock", "").await;
+        let low: Vec<serde_json::Value> = res.body_json().await.unwrap();
+        assert!(low.is_empty());
+    }
+
+    #[async_std::test]
+    async fn entries_record_their_author() {
+        let app = test_app();
+        send(
+            &app,
+            Method::Post,
+            "/add/category",
+            r#"{"title": "Vehicles", "removed": false}"#,
+        )
+        .await;
+        send(
+            &app,
+            Method::Post,
+            "/add/item",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+
+        // login is off, so the configured name is used
+        let mut res = send(&app, Method::Get, "/history/1", "").await;
+        assert!(res.body_string().await.unwrap().contains("Front Desk"));
+
+        let app = test_app_with(true);
+        let cookie = session_cookie(&login(&app, "supervisor", "correct horse").await);
+        send_as(
+            &app,
+            Some(&cookie),
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        send_as(
+            &app,
+            Some(&cookie),
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+
+        let mut res = send(&app, Method::Get, "/api/v1/entries/1", "").await;
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(entry["author"], "supervisor");
+        assert_eq!(entry["user_id"], 3);
+    }
+
+    #[async_std::test]
+    async fn api_tokens_act_for_their_owner() {
+        let app = test_app_with(true);
+        let cookie = session_cookie(&login(&app, "admin", "correct horse").await);
+
+        let mut tokens = Vec::new();
+        for body in [
+            r#"{"name": "Inventory script"}"#,
+            r#"{"name": "Floor tablet", "scope": "technician", "expires_in_days": 30}"#,
+        ] {
+            let mut res = send_as(&app, Some(&cookie), Method::Post, "/api/v1/tokens", body).await;
+            assert_eq!(res.status(), tide::StatusCode::Created);
+            let issued: serde_json::Value = res.body_json().await.unwrap();
+            assert!(issued["api_token"]["last_used"].is_null());
+            tokens.push(issued["token"].as_str().unwrap().to_owned());
+        }
+        let (full, scoped) = (&tokens[0], &tokens[1]);
+
+        // tokens cannot mint tokens
+        let res = send_with_token(
+            &app,
+            full,
+            Method::Post,
+            "/api/v1/tokens",
+            r#"{"name": "Copy"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Unauthorized);
+
+        let res = send_with_token(
+            &app,
+            full,
+            Method::Post,
+            "/api/v1/categories",
+            r#"{"title": "Vehicles"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+        let res = send_with_token(
+            &app,
+            full,
+            Method::Post,
+            "/api/v1/items",
+            r#"{"title": "Car", "category_id": 1}"#,
+        )
+        .await;
+        assert_eq!(res.status(), tide::StatusCode::Created);
+
+        let item = r#"{"id": 1, "title": "Car", "category_id": 1, "details":
+            {"status": 1, "cost": 0, "note": "", "visible": true, "removed": false}}"#;
+        let res = send_with_token(&app, scoped, Method::Post, "/update/item", item).await;
+        assert_eq!(res.status(), tide::StatusCode::Ok);
+
+        // the scope caps the admin's role
+        let res = send_with_token(&app, scoped, Method::Delete, "/api/v1/items/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::Forbidden);
+
+        let mut res = send(&app, Method::Get, "/api/v1/entries/2", "").await;
+        let entry: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(entry["author"], "admin");
+
+        let mut res = send_as(&app, Some(&cookie), Method::Get, "/api/v1/tokens", "").await;
+        let listed: serde_json::Value = res.body_json().await.unwrap();
+        assert!(listed[0]["last_used"].is_string());
+        assert!(listed[1]["expires"].is_string());
+
+        let res = send_as(&app, Some(&cookie), Method::Delete, "/api/v1/tokens/1", "").await;
+        assert_eq!(res.status(), tide::StatusCode::NoContent);
+
+        for token in [full.as_str(), "mt_not_a_token"] {
+            let res = send_with_token(
+                &app,
+                token,
+                Method::Post,
+                "/api/v1/categories",
+                r#"{"title": "Tools"}"#,
+            )
+            .await;
+            assert_eq!(res.status(), tide::StatusCode::Unauthorized, "{}", token);
+        }
+    }
 }
@@ -0,0 +1,23 @@
+use crate::core::structs::*;
+use crate::db::store::{self, Store};
+
+pub fn collect(store: &dyn Store, user_id: u32) -> store::Result<MyWork> {
+    /// The items and open work orders assigned to one user.
+    let items = store
+        .collect_items()?
+        .into_values()
+        .filter(|item| {
+            item.assignee_id == Some(user_id)
+                && !item.details.as_ref().is_some_and(|details| details.removed)
+        })
+        .collect();
+
+    let filter = WorkOrderFilter {
+        assignee_id: Some(user_id),
+        ..WorkOrderFilter::default()
+    };
+    let mut work_orders = store.collect_work_orders(&filter)?;
+    work_orders.retain(|order| order.state != WorkOrderState::Closed);
+
+    Ok(MyWork { items, work_orders })
+}
@@ -0,0 +1,61 @@
+use crate::core::structs::*;
+use crate::db::store::{self, Store};
+
+pub fn low_stock(parts: impl IntoIterator<Item = Part>) -> Vec<Part> {
+    /// Parts at or below their reorder point, furthest below it first,
+    /// then by part number.
+    let mut low = parts
+        .into_iter()
+        .filter(Part::low_stock)
+        .collect::<Vec<Part>>();
+
+    low.sort_by_cached_key(|part| {
+        (
+            i64::from(part.quantity) - i64::from(part.reorder_point),
+            part.part_number.clone(),
+        )
+    });
+
+    low
+}
+
+pub fn collect(store: &dyn Store) -> store::Result<Vec<Part>> {
+    /// The low-stock report.
+    Ok(low_stock(store.collect_parts()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(part_number: &str, quantity: u32, reorder_point: u32) -> Part {
+        Part {
+            id: None,
+            part_number: part_number.to_owned(),
+            description: format!("Part {}", part_number),
+            unit_cost: 100,
+            quantity,
+            reorder_point,
+        }
+    }
+
+    #[test]
+    fn low_stock_parts_are_sorted_furthest_below_first() {
+        let parts = vec![
+            part("B-2", 2, 2),
+            part("A-1", 10, 2),
+            part("C-3", 0, 5),
+            part("A-2", 1, 3),
+            // never reordered, so only low once it runs out
+            part("D-4", 0, 0),
+            part("E-5", 1, 0),
+        ];
+
+        let low = low_stock(parts)
+            .into_iter()
+            .map(|part| part.part_number)
+            .collect::<Vec<String>>();
+
+        assert_eq!(low, vec!["C-3", "A-2", "B-2", "D-4"]);
+    }
+}
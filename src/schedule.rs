@@ -0,0 +1,266 @@
+use std::collections::{btree_map, BTreeMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::core::errors::AppError;
+use crate::core::structs::*;
+use crate::data::constants::{SCHEDULE_AUTHOR, SCHEDULE_CHECK_INTERVAL_SECS};
+use crate::db::store::{self, Store};
+
+/// How both backends write dates as text.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn parse_date(date: &str) -> store::Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, DATE_FORMAT)
+        .map_err(|_| AppError::Parse(format!("Invalid date \"{}\".", date)))
+}
+
+fn status_for<T: PartialOrd>(now: T, due: T, stop: T) -> u32 {
+    /// Warning (1) once due, Stopped (2) once past the grace period.
+    if now > stop {
+        2
+    } else if now >= due {
+        1
+    } else {
+        0
+    }
+}
+
+pub fn evaluate(
+    schedule: &Schedule,
+    entries: &[Entry],
+    now: &str,
+) -> store::Result<ScheduleStatus> {
+    /// Work out when a schedule is next due from its item's entries,
+    /// oldest first.
+    let done = entries
+        .iter()
+        .rposition(|entry| entry.schedule_id.is_some() && entry.schedule_id == schedule.id);
+    let last = done.map(|i| &entries[i]);
+
+    let mut status = ScheduleStatus {
+        schedule: schedule.clone(),
+        last_done: last.and_then(|entry| entry.date.clone()),
+        due_date: None,
+        due_meter: None,
+        meter: entries.iter().rev().find_map(|entry| entry.meter),
+        status: 0,
+    };
+
+    match schedule.kind {
+        ScheduleKind::Calendar => {
+            let since = match last
+                .and_then(|entry| entry.date.as_deref())
+                .or(schedule.created.as_deref())
+            {
+                Some(date) => parse_date(date)?,
+                None => return Ok(status),
+            };
+            let due = since + TimeDelta::days(schedule.period.into());
+            let stop = due + TimeDelta::days(schedule.grace.into());
+
+            status.due_date = Some(due.format(DATE_FORMAT).to_string());
+            status.status = status_for(parse_date(now)?, due, stop);
+        }
+        ScheduleKind::Usage => {
+            // the reading taken when the work was done, or the last one before it
+            let since = match done {
+                Some(i) => entries[..=i].iter().rev().find_map(|entry| entry.meter),
+                None => None,
+            }
+            .unwrap_or(schedule.meter_start);
+            let due = since.saturating_add(schedule.period);
+
+            status.due_meter = Some(due);
+            if let Some(meter) = status.meter {
+                status.status = status_for(meter, due, due.saturating_add(schedule.grace));
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+pub fn statuses(store: &dyn Store, item_id: Option<u32>) -> store::Result<Vec<ScheduleStatus>> {
+    /// Evaluate the schedules of one item, or of every item.
+    let now = store.now()?;
+    let mut histories: BTreeMap<u32, Vec<Entry>> = BTreeMap::new();
+    let mut result = vec![];
+
+    for schedule in store.collect_schedules(item_id)? {
+        if let btree_map::Entry::Vacant(slot) = histories.entry(schedule.item_id) {
+            let mut entries = store.collect_item_entries(schedule.item_id)?;
+            entries.sort_by_key(|entry| entry.id);
+            slot.insert(entries);
+        }
+
+        result.push(evaluate(&schedule, &histories[&schedule.item_id], &now)?);
+    }
+
+    Ok(result)
+}
+
+fn due_note(due: &[ScheduleStatus]) -> String {
+    /// Say which schedules are due and which are overdue.
+    let schedules = due
+        .iter()
+        .map(|status| {
+            format!(
+                "{} {}",
+                status.schedule.title,
+                match status.status {
+                    1 => "due",
+                    _ => "overdue",
+                }
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("Preventive maintenance: {}.", schedules.join(", "))
+}
+
+pub fn flag_due(store: &dyn Store) -> store::Result<usize> {
+    /// Raise the status of every item whose schedules call for a worse
+    /// one, noting which schedules are due in the new entry. Items are
+    /// never lowered here; recording the work done does that.
+    let items = store.collect_items()?;
+    let author = Author {
+        user_id: None,
+        name: SCHEDULE_AUTHOR.to_owned(),
+    };
+
+    let mut due: BTreeMap<u32, Vec<ScheduleStatus>> = BTreeMap::new();
+    for status in statuses(store, None)? {
+        if status.status > 0 {
+            due.entry(status.schedule.item_id).or_default().push(status);
+        }
+    }
+
+    let mut flagged = 0;
+    for (item_id, due) in due {
+        let Some(mut item) = items.get(&item_id).cloned() else {
+            continue;
+        };
+        let details = item.details.get_or_insert_with(ItemDetails::new);
+        let required = due.iter().map(|status| status.status).max().unwrap_or(0);
+
+        if details.removed || details.status >= required {
+            continue;
+        }
+
+        details.status = required;
+        details.note = Some(due_note(&due));
+        store.update_item(&item, &author)?;
+        flagged += 1;
+    }
+
+    Ok(flagged)
+}
+
+pub fn watch(store: Arc<dyn Store>) {
+    /// Flag due items now, and again every few minutes while the server runs.
+    async_std::task::spawn(async move {
+        loop {
+            match flag_due(store.as_ref()) {
+                Ok(0) => {}
+                Ok(flagged) => tide::log::info!("Flagged {} items for maintenance", flagged),
+                Err(e) => tide::log::error!("Could not check maintenance schedules: {}", e),
+            }
+
+            async_std::task::sleep(Duration::from_secs(SCHEDULE_CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(kind: ScheduleKind, period: u32, grace: u32) -> Schedule {
+        Schedule {
+            id: Some(1),
+            item_id: 1,
+            title: "Change oil".to_owned(),
+            kind,
+            period,
+            grace,
+            meter_start: 100,
+            created: Some("2024-01-01 08:00:00".to_owned()),
+        }
+    }
+
+    fn entry(id: u32, date: &str, schedule_id: Option<u32>, meter: Option<u32>) -> Entry {
+        Entry {
+            id: Some(id),
+            item_id: 1,
+            cost: None,
+            note: None,
+            status: Some(0),
+            visible: true,
+            removed: false,
+            user_id: None,
+            author: None,
+            date: Some(date.to_owned()),
+            schedule_id,
+            meter,
+            due_date: None,
+            priority: None,
+            work_order_id: None,
+            assignment: None,
+            parts: vec![],
+        }
+    }
+
+    #[test]
+    fn calendar_schedules_count_from_the_last_completion() {
+        let oil = schedule(ScheduleKind::Calendar, 90, 7);
+        let status = |entries: &[Entry], now: &str| evaluate(&oil, entries, now).unwrap();
+
+        // never done: counted from when the schedule was made
+        let never = status(&[], "2024-03-31 08:00:00");
+        assert_eq!(never.due_date.as_deref(), Some("2024-03-31 08:00:00"));
+        assert_eq!(never.status, 1);
+
+        let done = [
+            entry(1, "2024-02-01 10:00:00", Some(1), None),
+            entry(2, "2024-02-02 10:00:00", Some(2), None),
+        ];
+        assert_eq!(status(&done, "2024-04-30 09:00:00").status, 0);
+        assert_eq!(status(&done, "2024-05-01 10:00:00").status, 1);
+        assert_eq!(status(&done, "2024-05-08 10:00:00").status, 1);
+        assert_eq!(status(&done, "2024-05-08 10:00:01").status, 2);
+        assert_eq!(
+            status(&done, "2024-05-08 10:00:01").last_done.as_deref(),
+            Some("2024-02-01 10:00:00")
+        );
+    }
+
+    #[test]
+    fn usage_schedules_follow_meter_readings() {
+        let inspect = schedule(ScheduleKind::Usage, 500, 50);
+        let status =
+            |entries: &[Entry]| evaluate(&inspect, entries, "2024-01-01 00:00:00").unwrap();
+
+        // no readings yet: nothing can be due
+        assert_eq!(status(&[]).due_meter, Some(600));
+        assert_eq!(status(&[]).status, 0);
+
+        let readings = [entry(1, "2024-01-02 00:00:00", None, Some(599))];
+        assert_eq!(status(&readings).status, 0);
+
+        let readings = [entry(1, "2024-01-02 00:00:00", None, Some(651))];
+        assert_eq!(status(&readings).status, 2);
+
+        // done without a reading: counted from the reading before it
+        let done = [
+            entry(1, "2024-01-02 00:00:00", None, Some(640)),
+            entry(2, "2024-01-03 00:00:00", Some(1), None),
+            entry(3, "2024-01-04 00:00:00", None, Some(1140)),
+        ];
+        assert_eq!(status(&done).due_meter, Some(1140));
+        assert_eq!(status(&done).meter, Some(1140));
+        assert_eq!(status(&done).status, 1);
+    }
+}
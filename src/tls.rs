@@ -0,0 +1,196 @@
+#![allow(unused)]
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+use tide::{Redirect, Request};
+use tide_rustls::TlsListener;
+
+use crate::core::structs::TlsConfig;
+use crate::State;
+
+pub struct CertResolver {
+    /// Hands out the current certificate, which can be swapped for a
+    /// renewed one without restarting the server.
+    config: TlsConfig,
+    current: RwLock<CertifiedKey>,
+}
+
+impl CertResolver {
+    pub fn new(config: &TlsConfig) -> io::Result<Self> {
+        Ok(CertResolver {
+            config: config.clone(),
+            current: RwLock::new(load_certified_key(config)?),
+        })
+    }
+
+    pub fn reload(&self) -> io::Result<()> {
+        /// Read the certificate and key again. On failure the current
+        /// certificate is kept, so a botched renewal does not take the
+        /// server down.
+        let key = load_certified_key(&self.config)?;
+
+        match self.current.write() {
+            Ok(mut current) => *current = key,
+            Err(poisoned) => *poisoned.into_inner() = key,
+        }
+
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<CertifiedKey> {
+        self.current.read().ok().map(|current| current.clone())
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn open(path: &str) -> io::Result<BufReader<File>> {
+    /// Open a PEM file, naming it in the error.
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| io::Error::new(e.kind(), format!("Could not open \"{}\": {}", path, e)))
+}
+
+fn load_certified_key(config: &TlsConfig) -> io::Result<CertifiedKey> {
+    /// Read the certificate chain and private key named in the config.
+    let certs = pemfile::certs(&mut open(&config.cert_path)?).unwrap_or_default();
+
+    if certs.is_empty() {
+        return Err(invalid_data(format!(
+            "No certificates found in \"{}\".",
+            config.cert_path
+        )));
+    }
+
+    // PKCS #8 ("BEGIN PRIVATE KEY") or PKCS #1 ("BEGIN RSA PRIVATE KEY")
+    let key = pemfile::pkcs8_private_keys(&mut open(&config.key_path)?)
+        .unwrap_or_default()
+        .into_iter()
+        .chain(pemfile::rsa_private_keys(&mut open(&config.key_path)?).unwrap_or_default())
+        .next()
+        .ok_or_else(|| invalid_data(format!("No private key found in \"{}\".", config.key_path)))?;
+
+    let signing_key = sign::any_supported_type(&key).map_err(|_| {
+        invalid_data(format!(
+            "Unsupported private key in \"{}\".",
+            config.key_path
+        ))
+    })?;
+
+    Ok(CertifiedKey::new(certs, Arc::new(signing_key)))
+}
+
+fn reload_on_sighup(resolver: Arc<CertResolver>) -> io::Result<()> {
+    /// Reload the certificate whenever the process receives SIGHUP.
+    let mut signals = Signals::new([SIGHUP])?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            match resolver.reload() {
+                Ok(_) => tide::log::info!("Reloaded TLS certificate"),
+                Err(e) => tide::log::error!("Keeping the current TLS certificate: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+pub fn server_config(config: &TlsConfig) -> io::Result<ServerConfig> {
+    /// TLS settings serving the configured certificate, reloaded on SIGHUP.
+    let resolver = Arc::new(CertResolver::new(config)?);
+    reload_on_sighup(resolver.clone())?;
+
+    let mut server_config = ServerConfig::new(NoClientAuth::new());
+    server_config.cert_resolver = resolver;
+
+    Ok(server_config)
+}
+
+pub fn listener(server_config: &ServerConfig, addr: SocketAddr) -> io::Result<TlsListener<State>> {
+    /// HTTPS listener for `addr`.
+    TlsListener::build()
+        .addrs(addr)
+        .config(server_config.clone())
+        .finish()
+}
+
+fn https_url(req: &Request<()>, https_port: u16) -> String {
+    /// Build the HTTPS address of the page a plain HTTP request was for.
+    let url = req.url();
+    let port = match https_port {
+        443 => String::new(),
+        port => format!(":{}", port),
+    };
+
+    format!(
+        "https://{}{}{}{}",
+        url.host_str().unwrap_or("localhost"),
+        port,
+        url.path(),
+        url.query()
+            .map(|query| format!("?{}", query))
+            .unwrap_or_default()
+    )
+}
+
+pub fn redirect_app(https_port: u16) -> tide::Server<()> {
+    /// Plain HTTP server that sends every request on to HTTPS.
+    let mut app = tide::new();
+
+    for path in ["/", "/*"] {
+        app.at(path).all(move |req: Request<()>| async move {
+            Ok(Redirect::permanent(https_url(&req, https_port)))
+        });
+    }
+
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use tide::http::{Method, Response, Url};
+
+    use super::*;
+
+    async fn redirect(https_port: u16, url: &str) -> Response {
+        let req = tide::http::Request::new(Method::Post, Url::parse(url).unwrap());
+
+        redirect_app(https_port).respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn plain_http_redirects_to_https() {
+        let res = redirect(8443, "http://plant.local:8080/history/1?a=b").await;
+        assert_eq!(res.status(), tide::StatusCode::PermanentRedirect);
+        assert_eq!(res["Location"], "https://plant.local:8443/history/1?a=b");
+
+        let res = redirect(443, "http://[::1]/").await;
+        assert_eq!(res["Location"], "https://[::1]/");
+    }
+
+    #[test]
+    fn missing_certificate_is_reported() {
+        let config = TlsConfig {
+            cert_path: "no/such/cert.pem".to_owned(),
+            key_path: "no/such/key.pem".to_owned(),
+            redirect_port: None,
+        };
+
+        let err = CertResolver::new(&config).err().unwrap();
+        assert!(err.to_string().contains("no/such/cert.pem"));
+    }
+}
@@ -12,6 +12,9 @@ class Item extends HTMLElement {
      * - visible <bool>
      * - cost <Optional[Number]> [DEFAULT: 0]
      * - status <Optional[String]> [DEFAULT: "0"]
+     * - due-date <Optional[String]> [DEFAULT: ""] (YYYY-MM-DD)
+     * - priority <Optional[String]> [DEFAULT: ""] ("1" high to "3" low)
+     * - assignee <Optional[String]> [DEFAULT: ""] (username)
      */
     constructor() {
         super();
@@ -37,11 +40,16 @@ class Item extends HTMLElement {
         this.status = this.getAttribute("status") || "0";
         this.visible = this.getAttribute("visible") || "false";
         this.removed = this.getAttribute("removed") || "false";
+        this.dueDate = this.getAttribute("due-date") || "";
+        this.priority = this.getAttribute("priority") || "";
+        this.assignee = this.getAttribute("assignee") || "";
 
         // constants
         this.LAST_NOTE = this.innerHTML.trim();
         this.LAST_REPAIR_COST = this.repairCost;
         this.LAST_STATUS = this.status;
+        this.LAST_DUE_DATE = this.dueDate;
+        this.LAST_PRIORITY = this.priority;
 
         // styles
         const wrapperStyle = {
@@ -243,6 +251,42 @@ class Item extends HTMLElement {
         this.repairCostInput.value = parseFloat(parseInt(this.repairCost)).toFixed(2);
         Object.assign(this.repairCostInput.style, costInputStyle);
 
+        // due date & priority of the current status
+        const dueDetails = this.wrapper.appendChild(document.createElement("span"));
+        Object.assign(dueDetails.style, groupStyle);
+
+        const lblDueDate = dueDetails.appendChild(document.createElement("p"));
+        lblDueDate.innerHTML = "Due:";
+        Object.assign(lblDueDate.style, lblRepairCostStyle);
+
+        const dueDateInput = dueDetails.appendChild(document.createElement("input"));
+        dueDateInput.type = "date";
+        dueDateInput.value = this.dueDate;
+        dueDateInput.oninput = () => {
+            this.dueDate = dueDateInput.value;
+            this.updateChanged();
+        };
+
+        const prioritySelect = dueDetails.appendChild(document.createElement("select"));
+        for (const [value, name] of [["", "Priority"], ["1", "High"], ["2", "Normal"], ["3", "Low"]]) {
+            let option = prioritySelect.appendChild(document.createElement("option"));
+            option.value = value;
+            option.innerText = name;
+        }
+        prioritySelect.value = this.priority;
+        prioritySelect.onchange = () => {
+            this.priority = prioritySelect.value;
+            this.updateChanged();
+            updateUnsavedChangesMsg();
+        };
+
+        // who the item is assigned to, set through the API
+        if (this.assignee) {
+            const lblAssignee = dueDetails.appendChild(document.createElement("p"));
+            lblAssignee.textContent = "Assigned to " + this.assignee;
+            Object.assign(lblAssignee.style, lblRepairCostStyle);
+        }
+
         // maintenance notes
         const note = this.wrapper.appendChild(document.createElement("textarea"));
         note.textContent = this.innerHTML.trim();
@@ -283,6 +327,8 @@ class Item extends HTMLElement {
                     "note": this.note,
                     "visible": this.visible == "true" ? true : false,
                     "removed": this.removed == "true" ? true : false,
+                    "due_date": this.dueDate || null,
+                    "priority": this.priority ? parseInt(this.priority) : null,
                 },
             }
         }
@@ -416,6 +462,8 @@ class Item extends HTMLElement {
             this.status == this.LAST_STATUS
             && this.repairCost == this.LAST_REPAIR_COST / 100
             && this.note == this.LAST_NOTE
+            && this.dueDate == this.LAST_DUE_DATE
+            && this.priority == this.LAST_PRIORITY
         ) {
             this.changed = false;
             changedItems.splice(changedItems.indexOf(this), 1);
@@ -448,6 +496,10 @@ function displayAddPanel() {
     }
 }
 
+function displayDuePanel() {
+    duePanel.classList.add("active");
+}
+
 function displayHistoryPanel(item) {
     if (!historyPanel.classList.contains("active")) {
         const xhr = new XMLHttpRequest();
@@ -474,8 +526,31 @@ function exitPanel(caller) {
 }
 
 function logReload(xhr) {
-    if (xhr.response != "OK") {
-        alert(xhr.response);
+    let response = null;
+
+    if (xhr.status == 401) {
+        // changes need a logged-in user
+        window.location.href = "/login";
+        return;
+    }
+
+    // errors are sent as `{"error": "..."}` and bulk saves
+    // answer with a `{"saved": ..., "items": [...]}` report
+    try {
+        response = JSON.parse(xhr.response);
+    } catch (e) { }
+
+    if (xhr.response != "OK" && !(response && response.saved)) {
+        if (response && response.items) {
+            let errors = response.items
+                .filter((item) => item.error)
+                .map((item) => `Item #${item.id}: ${item.error}`);
+
+            alert(`No changes were saved.\n\n${errors.join("\n")}`);
+        } else {
+            alert((response && response.error) || xhr.response);
+        }
+
         return;
     }
 
@@ -502,6 +577,10 @@ function postChange(action, content, autoReload) {
     xhr.open("POST", action);
     xhr.setRequestHeader("Accept", "application/json");
     xhr.setRequestHeader("Content-Type", "application/json");
+    xhr.setRequestHeader(
+        "X-CSRF-Token",
+        document.querySelector("meta[name=csrf-token]").content
+    );
 
     // log response to the console
     xhr.onload = () => {
@@ -724,6 +803,7 @@ const unsavedChangesMsg = document.getElementById("unsaved-changes-msg");
 const categorySection = document.getElementById("categories");
 const emptyCategorySection = document.getElementById("empty-categories");
 const hiddenItems = document.getElementById("hidden-items");
+const duePanel = document.getElementById("due-panel");
 const historyPanel = document.getElementById("history-panel");
 const historyBody = document.getElementById("history-body");
 const historyHeader = document.getElementById("history-header");
@@ -763,8 +843,8 @@ addItem.nextElementSibling.onclick = () => toggleAddSection(addItem);
 window.onload = () => {
     hideEmptyCategories()
 
-    // reset input and select fields
-    document.querySelectorAll("input").forEach((input) => { input.value = "" });
+    // reset input and select fields, keeping the CSRF token
+    document.querySelectorAll("input:not([type=hidden])").forEach((input) => { input.value = "" });
     document.querySelectorAll("select").forEach((select) => { select.value = "-1" });
 
     // hide hidden items section there are no hidden items or empty categories